@@ -0,0 +1,57 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_flow_host_resource_time;
+
+CREATE VIEW IF NOT EXISTS v_entity_balances AS
+SELECT
+    entity_id,
+    resource_type,
+    SUM(amount) AS balance
+FROM (
+    SELECT to_entity AS entity_id,
+           resource_type,
+           quantity_value AS amount
+    FROM flow_events
+    UNION ALL
+    SELECT from_entity AS entity_id,
+           resource_type,
+           -quantity_value AS amount
+    FROM flow_events
+)
+GROUP BY entity_id, resource_type;
+
+CREATE VIEW IF NOT EXISTS v_dollars_available AS
+SELECT
+    IFNULL(SUM(balance), 0) AS dollars_available
+FROM v_entity_balances
+WHERE resource_type = 'Dollars'
+ AND balance > 0;
+
+CREATE VIEW IF NOT EXISTS v_active_contributors_30d AS
+SELECT
+    COUNT(DISTINCT from_entity) AS active_contributors
+FROM flow_events
+WHERE resource_type = 'labor_time'
+  AND timestamp >= datetime('now', '-30 days');
+
+CREATE VIEW IF NOT EXISTS v_total_hours_30d AS
+SELECT
+    IFNULL(SUM(quantity_value), 0) AS total_hours
+FROM flow_events
+WHERE resource_type = 'labor_time'
+  AND timestamp >= datetime('now', '-30 days');
+
+CREATE VIEW IF NOT EXISTS v_active_projects_30d AS
+SELECT
+    COUNT(DISTINCT e.id) AS active_projects
+FROM entities e
+JOIN flow_events f
+  ON f.to_entity = e.id
+WHERE e.entity_type in ('project','team','organization')
+  AND f.timestamp >= datetime('now', '-30 days');
+
+CREATE VIEW IF NOT EXISTS v_regen_vital_signs AS
+SELECT
+    (SELECT dollars_available FROM v_dollars_available) AS dollars_available,
+    (SELECT active_contributors FROM v_active_contributors_30d) AS active_contributors_30d,
+    (SELECT total_hours FROM v_total_hours_30d) AS total_hours_30d,
+    (SELECT active_projects FROM v_active_projects_30d) AS active_projects_30d;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+-- ============================================================
+-- Drop the global ledger views.
+-- They aggregated flow_events across every host, so one host's
+-- dashboard showed another host's numbers. Vital signs are now
+-- computed per host and per date window in models::ledger_views.
+-- ============================================================
+
+DROP VIEW IF EXISTS v_regen_vital_signs;
+DROP VIEW IF EXISTS v_active_projects_30d;
+DROP VIEW IF EXISTS v_total_hours_30d;
+DROP VIEW IF EXISTS v_active_contributors_30d;
+DROP VIEW IF EXISTS v_dollars_available;
+DROP VIEW IF EXISTS v_entity_balances;
+
+-- Host + resource lookups used by the per-host aggregates
+CREATE INDEX IF NOT EXISTS idx_flow_host_resource_time
+ON flow_events(host_id, resource_type, timestamp);
@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS restrict_flow_repoint;
+DROP TRIGGER IF EXISTS prevent_flow_update;
+
+CREATE TRIGGER prevent_flow_update
+BEFORE UPDATE ON flow_events
+BEGIN
+    SELECT RAISE(FAIL, 'flow_events are append-only');
+END;
+
+DROP INDEX IF EXISTS idx_entity_alias_normalized;
+
+ALTER TABLE entity_aliases DROP COLUMN normalized;
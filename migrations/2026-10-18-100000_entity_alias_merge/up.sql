@@ -0,0 +1,51 @@
+-- Your SQL goes here
+-- ============================================================
+-- Alias-aware entity resolution and merges.
+-- Aliases keep the text as entered plus a normalised form
+-- (trimmed, lower-cased, email tags stripped) for lookups.
+-- Existing rows are normalised by run_migrations once this
+-- migration has applied, using the same rules as lookups.
+-- ============================================================
+
+ALTER TABLE entity_aliases ADD COLUMN normalized TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX idx_entity_alias_normalized
+ON entity_aliases(normalized);
+
+
+-- ============================================================
+-- APPEND-ONLY LEDGER ENFORCEMENT
+-- Flow content stays immutable. The only permitted update is
+-- repointing from_entity/to_entity onto the entity a duplicate
+-- was merged into; the merge records the old id as an alias
+-- of the canonical entity before it moves any flows.
+-- ============================================================
+
+DROP TRIGGER IF EXISTS prevent_flow_update;
+
+CREATE TRIGGER prevent_flow_update
+BEFORE UPDATE OF id, timestamp, recorded_at, host_id, resource_type,
+    quantity_value, quantity_unit, notes, details, created_by
+ON flow_events
+BEGIN
+    SELECT RAISE(FAIL, 'flow_events are append-only');
+END;
+
+CREATE TRIGGER restrict_flow_repoint
+BEFORE UPDATE OF from_entity, to_entity ON flow_events
+WHEN (
+    NEW.from_entity != OLD.from_entity
+    AND NOT EXISTS (
+        SELECT 1 FROM entity_aliases
+        WHERE entity_id = NEW.from_entity AND alias = OLD.from_entity
+    )
+) OR (
+    NEW.to_entity != OLD.to_entity
+    AND NOT EXISTS (
+        SELECT 1 FROM entity_aliases
+        WHERE entity_id = NEW.to_entity AND alias = OLD.to_entity
+    )
+)
+BEGIN
+    SELECT RAISE(FAIL, 'flow_events can only be repointed to a merged entity');
+END;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Archived entities are hidden from pickers but keep their flows.
+ALTER TABLE entities ADD COLUMN archived_at DATETIME;
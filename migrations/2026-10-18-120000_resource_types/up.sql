@@ -0,0 +1,48 @@
+-- Your SQL goes here
+-- ============================================================
+-- RESOURCE TYPE REGISTRY
+-- Per-host catalogue of what flows move. `key` matches
+-- flow_events.resource_type; `units` maps every allowed unit
+-- to its factor in default_unit (default_unit itself is 1).
+-- ============================================================
+
+CREATE TABLE resource_types (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+
+    host_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    label TEXT NOT NULL,
+
+    default_unit TEXT NOT NULL,
+    units TEXT DEFAULT '{}' NOT NULL,
+
+    is_monetary BOOLEAN DEFAULT 0 NOT NULL,
+    is_labor BOOLEAN DEFAULT 0 NOT NULL,
+
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (host_id)
+        REFERENCES hosts(id)
+        ON DELETE CASCADE,
+
+    UNIQUE (host_id, key)
+);
+
+-- Seed every real host with the resources the loaders already use.
+INSERT INTO resource_types (host_id, key, label, default_unit, units, is_monetary, is_labor)
+SELECT h.id, v.key, v.label, v.default_unit, v.units, v.is_monetary, v.is_labor
+FROM hosts h, (
+    SELECT 'labor_time' AS key, 'Labor Time' AS label, 'hours' AS default_unit,
+           '{"hours":1,"minutes":0.016666666666666666}' AS units, 0 AS is_monetary, 1 AS is_labor
+    UNION ALL SELECT 'work_done', 'Work Done', 'hours', '{"hours":1,"minutes":0.016666666666666666}', 0, 0
+    UNION ALL SELECT 'Dollars', 'Dollars', 'USD', '{"USD":1}', 1, 0
+    UNION ALL SELECT 'donation', 'Donation', 'USD', '{"USD":1}', 1, 0
+    UNION ALL SELECT 'hosted', 'Hosted', 'count', '{"count":1}', 0, 0
+    UNION ALL SELECT 'meal', 'Meal', 'count', '{"count":1}', 0, 0
+    UNION ALL SELECT 'member_of', 'Member Of', 'count', '{"count":1}', 0, 0
+    UNION ALL SELECT 'presentation', 'Presentation', 'count', '{"count":1}', 0, 0
+    UNION ALL SELECT 'tour', 'Tour', 'count', '{"count":1}', 0, 0
+    UNION ALL SELECT 'visited', 'Visited', 'count', '{"count":1}', 0, 0
+    UNION ALL SELECT 'document_submitted', 'Document Submitted', 'count', '{"count":1}', 0, 0
+) v
+WHERE h.id != 0;
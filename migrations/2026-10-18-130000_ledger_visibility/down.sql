@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE entities DROP COLUMN visibility;
+ALTER TABLE flow_events DROP COLUMN visibility;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Who may see a flow or entity: public, members, stewards or parties.
+-- Existing rows stay visible to host members, as before.
+ALTER TABLE flow_events ADD COLUMN visibility TEXT NOT NULL DEFAULT 'members';
+ALTER TABLE entities ADD COLUMN visibility TEXT NOT NULL DEFAULT 'members';
@@ -0,0 +1,45 @@
+-- Your SQL goes here
+-- ============================================================
+-- FLOW TEMPLATES
+-- A flow that repeats on a schedule. The scheduler proposes
+-- a flow for every occurrence at or before now and advances
+-- next_run; occurrences are counted from starts_at so monthly
+-- templates don't drift after short months.
+-- ============================================================
+
+CREATE TABLE flow_templates (
+    id TEXT PRIMARY KEY NOT NULL,
+
+    host_id INTEGER NOT NULL,
+    from_entity TEXT NOT NULL,
+    to_entity TEXT NOT NULL,
+
+    resource_type TEXT NOT NULL,
+    quantity_value REAL NOT NULL,
+    quantity_unit TEXT NOT NULL,
+    notes TEXT,
+    details TEXT DEFAULT '{}' NOT NULL,
+
+    recurrence TEXT NOT NULL,
+    every INTEGER DEFAULT 1 NOT NULL,
+    starts_at DATETIME NOT NULL,
+    ends_at DATETIME,
+
+    next_run DATETIME NOT NULL,
+    run_count INTEGER DEFAULT 0 NOT NULL,
+    last_run_at DATETIME,
+    paused BOOLEAN DEFAULT 0 NOT NULL,
+
+    created_by TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+    FOREIGN KEY (from_entity) REFERENCES entities(id),
+    FOREIGN KEY (to_entity) REFERENCES entities(id),
+
+    CHECK (every > 0)
+);
+
+CREATE INDEX idx_flow_templates_due ON flow_templates(paused, next_run);
+CREATE INDEX idx_flow_templates_from ON flow_templates(from_entity);
+CREATE INDEX idx_flow_templates_to ON flow_templates(to_entity);
@@ -0,0 +1,58 @@
+-- Your SQL goes here
+-- ============================================================
+-- COMMITMENTS
+-- A promised future flow (pledge, offer of hours, standing
+-- donation). Real flow_events fulfil it through
+-- commitment_fulfilments, each recording how much of the
+-- commitment (in the commitment's unit) that flow covers.
+-- ============================================================
+
+CREATE TABLE commitments (
+    id TEXT PRIMARY KEY NOT NULL,
+
+    host_id INTEGER NOT NULL,
+    from_entity TEXT NOT NULL,
+    to_entity TEXT NOT NULL,
+
+    resource_type TEXT NOT NULL,
+    quantity_value REAL NOT NULL,
+    quantity_unit TEXT NOT NULL,
+    due_date DATETIME NOT NULL,
+
+    notes TEXT,
+    details TEXT DEFAULT '{}' NOT NULL,
+
+    -- open | fulfilled | cancelled
+    status TEXT DEFAULT 'open' NOT NULL,
+
+    created_by TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+    FOREIGN KEY (from_entity) REFERENCES entities(id),
+    FOREIGN KEY (to_entity) REFERENCES entities(id),
+
+    CHECK (quantity_value > 0)
+);
+
+CREATE INDEX idx_commitments_host_status ON commitments(host_id, status);
+CREATE INDEX idx_commitments_from ON commitments(from_entity);
+CREATE INDEX idx_commitments_to ON commitments(to_entity);
+
+CREATE TABLE commitment_fulfilments (
+    commitment_id TEXT NOT NULL,
+    flow_id TEXT NOT NULL,
+
+    quantity_value REAL NOT NULL,
+
+    created_by TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (commitment_id, flow_id),
+    FOREIGN KEY (commitment_id) REFERENCES commitments(id) ON DELETE CASCADE,
+    FOREIGN KEY (flow_id) REFERENCES flow_events(id),
+
+    CHECK (quantity_value > 0)
+);
+
+CREATE INDEX idx_commitment_fulfilments_flow ON commitment_fulfilments(flow_id);
@@ -0,0 +1,34 @@
+-- Your SQL goes here
+-- ============================================================
+-- ENTITY GOALS
+-- A target for one resource flowing into a project, team or
+-- organization over a period. Progress is never stored; it is
+-- summed from confirmed flow_events whenever goals are read.
+-- ============================================================
+
+CREATE TABLE entity_goals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+
+    host_id INTEGER NOT NULL,
+    entity_id TEXT NOT NULL,
+
+    label TEXT NOT NULL,
+    resource_type TEXT NOT NULL,
+    target_value REAL NOT NULL,
+    unit TEXT NOT NULL,
+
+    period_start DATETIME NOT NULL,
+    period_end DATETIME NOT NULL,
+
+    created_by TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (host_id) REFERENCES hosts(id) ON DELETE CASCADE,
+    FOREIGN KEY (entity_id) REFERENCES entities(id) ON DELETE CASCADE,
+
+    CHECK (target_value > 0),
+    CHECK (period_end > period_start)
+);
+
+CREATE INDEX idx_entity_goals_host ON entity_goals(host_id, period_end);
+CREATE INDEX idx_entity_goals_entity ON entity_goals(entity_id);
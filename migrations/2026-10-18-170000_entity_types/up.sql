@@ -0,0 +1,41 @@
+-- Your SQL goes here
+-- ============================================================
+-- ENTITY TYPE SCHEMA
+-- Per-host list of allowed entities.entity_type values, each
+-- with the detail fields its entities carry. `fields` is a JSON
+-- array in the doc_schema.json field format:
+--   [{ "key", "label", "type", "required"?, "options"? }]
+-- ============================================================
+
+CREATE TABLE entity_types (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+
+    host_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    label TEXT NOT NULL,
+    fields TEXT DEFAULT '[]' NOT NULL,
+
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (host_id)
+        REFERENCES hosts(id)
+        ON DELETE CASCADE,
+
+    UNIQUE (host_id, key)
+);
+
+-- Seed every real host with the types already in use, with no
+-- required fields so existing entities stay valid.
+INSERT INTO entity_types (host_id, key, label, fields)
+SELECT h.id, v.key, v.label, v.fields
+FROM hosts h, (
+    SELECT 'Person' AS key, 'Person' AS label,
+           '[{"key":"email","label":"Email","type":"email"}]' AS fields
+    UNION ALL SELECT 'person_email', 'Person (email only)', '[]'
+    UNION ALL SELECT 'project', 'Project',
+           '[{"key":"description","label":"Description","type":"textarea"},{"key":"website","label":"Website","type":"url"}]'
+    UNION ALL SELECT 'team', 'Team', '[{"key":"description","label":"Description","type":"textarea"}]'
+    UNION ALL SELECT 'organization', 'Organization',
+           '[{"key":"description","label":"Description","type":"textarea"},{"key":"website","label":"Website","type":"url"}]'
+) v
+WHERE h.id != 0;
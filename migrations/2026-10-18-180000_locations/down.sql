@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS flow_events_location_set_once;
+DROP INDEX IF EXISTS idx_entities_location;
+ALTER TABLE flow_events DROP COLUMN longitude;
+ALTER TABLE flow_events DROP COLUMN latitude;
+ALTER TABLE entities DROP COLUMN longitude;
+ALTER TABLE entities DROP COLUMN latitude;
+ALTER TABLE entities DROP COLUMN geometry;
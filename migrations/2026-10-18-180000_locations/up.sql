@@ -0,0 +1,33 @@
+-- Your SQL goes here
+-- ============================================================
+-- LOCATIONS
+-- An entity may carry a GeoJSON Point or Polygon in `geometry`;
+-- latitude/longitude hold its representative point (the point
+-- itself, or the centre of the polygon's outer ring) so bbox
+-- and radius filters stay in SQL.
+-- A flow may record where it happened. Flows without one are
+-- placed at their receiving entity when mapped.
+-- ============================================================
+
+ALTER TABLE entities ADD COLUMN geometry TEXT;
+ALTER TABLE entities ADD COLUMN latitude REAL;
+ALTER TABLE entities ADD COLUMN longitude REAL;
+
+ALTER TABLE flow_events ADD COLUMN latitude REAL;
+ALTER TABLE flow_events ADD COLUMN longitude REAL;
+
+CREATE INDEX idx_entities_location
+ON entities(host_id, latitude, longitude);
+
+
+-- ============================================================
+-- A flow's location is part of the record: it can be added
+-- after the fact but not moved once set.
+-- ============================================================
+
+CREATE TRIGGER flow_events_location_set_once
+BEFORE UPDATE OF latitude, longitude ON flow_events
+WHEN OLD.latitude IS NOT NULL OR OLD.longitude IS NOT NULL
+BEGIN
+    SELECT RAISE(FAIL, 'flow_events location cannot be changed once set');
+END;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS ledger_checkpoints;
+DROP TRIGGER IF EXISTS flow_events_chain_set_once;
+DROP INDEX IF EXISTS idx_flow_events_chain;
+ALTER TABLE flow_events DROP COLUMN hash;
+ALTER TABLE flow_events DROP COLUMN prev_hash;
+ALTER TABLE flow_events DROP COLUMN chain_seq;
@@ -0,0 +1,48 @@
+-- Your SQL goes here
+-- ============================================================
+-- LEDGER HASH CHAIN
+-- Each flow is linked into its host's chain in insertion order:
+-- `hash` is SHA-256 over the flow's content, its position and
+-- the previous link's hash. Flows recorded before this migration
+-- are linked on the next write. Links are set once.
+-- Mutable columns (visibility, location) are not covered.
+-- ============================================================
+
+ALTER TABLE flow_events ADD COLUMN chain_seq INTEGER;
+ALTER TABLE flow_events ADD COLUMN prev_hash TEXT;
+ALTER TABLE flow_events ADD COLUMN hash TEXT;
+
+CREATE UNIQUE INDEX idx_flow_events_chain
+ON flow_events(host_id, chain_seq);
+
+CREATE TRIGGER flow_events_chain_set_once
+BEFORE UPDATE OF chain_seq, prev_hash, hash ON flow_events
+WHEN OLD.hash IS NOT NULL
+BEGIN
+    SELECT RAISE(FAIL, 'flow_events chain links cannot be changed');
+END;
+
+
+-- ============================================================
+-- CHECKPOINTS
+-- The chain head at a point in time, signed by the server so it
+-- can be published and later compared against the ledger.
+-- ============================================================
+
+CREATE TABLE ledger_checkpoints (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+
+    host_id INTEGER NOT NULL,
+    chain_seq INTEGER NOT NULL,
+    head_hash TEXT NOT NULL,
+    signature TEXT NOT NULL,
+
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (host_id)
+        REFERENCES hosts(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_ledger_checkpoints_host
+ON ledger_checkpoints(host_id, chain_seq);
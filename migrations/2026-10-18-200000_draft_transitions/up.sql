@@ -0,0 +1,28 @@
+-- Your SQL goes here
+-- ============================================================
+-- DRAFT TRANSITIONS
+-- Every status change a draft goes through, who made it and
+-- any notes they left. Written by DraftService::transition,
+-- which is the only place a draft's status changes.
+-- ============================================================
+
+CREATE TABLE draft_transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+
+    draft_id INTEGER NOT NULL,
+    host_id INTEGER NOT NULL,
+    action TEXT NOT NULL,          -- submit | request_changes | approve | reject | deploy
+    from_status TEXT NOT NULL,
+    to_status TEXT NOT NULL,
+    actor_id INTEGER NOT NULL,     -- user_id
+    notes TEXT,
+
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (draft_id)
+        REFERENCES drafts(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_draft_transitions_draft
+ON draft_transitions(draft_id, id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS prevent_draft_revision_update;
+DROP TABLE IF EXISTS draft_revisions;
@@ -0,0 +1,48 @@
+-- Your SQL goes here
+-- ============================================================
+-- DRAFT REVISIONS
+-- A snapshot of a draft's content on every save, numbered per
+-- draft. The draft row holds the current version; revisions are
+-- never changed. A restore saves the old content as a new
+-- revision and notes which one it came from.
+-- ============================================================
+
+CREATE TABLE draft_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+
+    draft_id INTEGER NOT NULL,
+    host_id INTEGER NOT NULL,
+    revision INTEGER NOT NULL,
+
+    title TEXT NOT NULL,
+    description TEXT,
+    tags TEXT,
+    author TEXT,
+    meta TEXT,
+    body_md TEXT NOT NULL,
+
+    restored_from INTEGER,          -- revision number
+    created_by INTEGER NOT NULL,    -- user_id
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    FOREIGN KEY (draft_id)
+        REFERENCES drafts(id)
+        ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_draft_revisions_number
+ON draft_revisions(draft_id, revision);
+
+CREATE TRIGGER prevent_draft_revision_update
+BEFORE UPDATE ON draft_revisions
+BEGIN
+    SELECT RAISE(FAIL, 'draft_revisions are immutable');
+END;
+
+-- Existing drafts start from what they hold now.
+INSERT INTO draft_revisions
+    (draft_id, host_id, revision, title, description, tags, author, meta, body_md, created_by, created_at)
+SELECT
+    id, host_id, 1, title, description, tags, author, meta, body_md, submitted_by,
+    COALESCE(submitted_at, CURRENT_TIMESTAMP)
+FROM drafts;
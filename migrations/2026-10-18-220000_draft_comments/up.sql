@@ -0,0 +1,39 @@
+-- Your SQL goes here
+-- ============================================================
+-- DRAFT COMMENTS
+-- Review threads on a draft. A thread starts with a comment
+-- whose parent_id is NULL; replies point at it. A thread can
+-- be anchored to lines of body_md, as of the revision it was
+-- written against, or to a field. Threads are resolved as a
+-- whole.
+-- ============================================================
+
+CREATE TABLE draft_comments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+
+    draft_id INTEGER NOT NULL,
+    host_id INTEGER NOT NULL,
+    parent_id INTEGER,
+
+    revision INTEGER,
+    line_start INTEGER,
+    line_end INTEGER,
+    field TEXT,
+
+    body TEXT NOT NULL,
+    author_id INTEGER NOT NULL,     -- user_id
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    resolved_by INTEGER,            -- user_id
+    resolved_at DATETIME,
+
+    FOREIGN KEY (draft_id)
+        REFERENCES drafts(id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (parent_id)
+        REFERENCES draft_comments(id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_draft_comments_draft
+ON draft_comments(draft_id, id);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE draft_transitions DROP COLUMN commit_id;
+ALTER TABLE drafts DROP COLUMN published_at;
+ALTER TABLE drafts DROP COLUMN published_commit;
+ALTER TABLE drafts DROP COLUMN published_path;
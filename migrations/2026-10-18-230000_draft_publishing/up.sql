@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- ============================================================
+-- DRAFT PUBLISHING
+-- Deploying a draft writes it into the host's site repository
+-- and commits it. The draft keeps the file it was published to
+-- and the commit; both are cleared when it is unpublished. Each
+-- transition keeps the commit it made, so the history has both.
+-- ============================================================
+
+ALTER TABLE drafts ADD COLUMN published_path TEXT;
+ALTER TABLE drafts ADD COLUMN published_commit TEXT;
+ALTER TABLE drafts ADD COLUMN published_at DATETIME;
+
+ALTER TABLE draft_transitions ADD COLUMN commit_id TEXT;
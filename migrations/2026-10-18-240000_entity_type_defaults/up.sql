@@ -0,0 +1,35 @@
+-- Your SQL goes here
+-- ============================================================
+-- BUILT-IN ENTITY TYPES
+-- Every host keeps the starter types the schema was seeded
+-- with; reports and goals look them up by key. Hosts created
+-- later get them from this trigger, and hosts that lost one
+-- get it back.
+-- ============================================================
+
+INSERT OR IGNORE INTO entity_types (host_id, key, label, fields)
+SELECT h.id, v.key, v.label, v.fields
+FROM hosts h, (
+    SELECT 'Person' AS key, 'Person' AS label,
+           '[{"key":"email","label":"Email","type":"email"}]' AS fields
+    UNION ALL SELECT 'project', 'Project',
+           '[{"key":"description","label":"Description","type":"textarea"},{"key":"website","label":"Website","type":"url"}]'
+    UNION ALL SELECT 'team', 'Team', '[{"key":"description","label":"Description","type":"textarea"}]'
+    UNION ALL SELECT 'organization', 'Organization',
+           '[{"key":"description","label":"Description","type":"textarea"},{"key":"website","label":"Website","type":"url"}]'
+) v
+WHERE h.id != 0;
+
+CREATE TRIGGER hosts_seed_entity_types
+AFTER INSERT ON hosts
+WHEN NEW.id != 0
+BEGIN
+    INSERT OR IGNORE INTO entity_types (host_id, key, label, fields) VALUES
+        (NEW.id, 'Person', 'Person', '[{"key":"email","label":"Email","type":"email"}]'),
+        (NEW.id, 'person_email', 'Person (email only)', '[]'),
+        (NEW.id, 'project', 'Project',
+         '[{"key":"description","label":"Description","type":"textarea"},{"key":"website","label":"Website","type":"url"}]'),
+        (NEW.id, 'team', 'Team', '[{"key":"description","label":"Description","type":"textarea"}]'),
+        (NEW.id, 'organization', 'Organization',
+         '[{"key":"description","label":"Description","type":"textarea"},{"key":"website","label":"Website","type":"url"}]');
+END;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS flow_actions_chain_set_once;
+DROP INDEX IF EXISTS idx_flow_actions_chain;
+ALTER TABLE flow_actions DROP COLUMN hash;
+ALTER TABLE flow_actions DROP COLUMN prev_hash;
+ALTER TABLE flow_actions DROP COLUMN chain_seq;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+-- ============================================================
+-- FLOW ACTIONS ON THE CHAIN
+-- Confirmations, rejections, reversals and merges join their
+-- host's chain after the flow they act on, numbered in the same
+-- sequence as flows. A merge records what it repointed, so the
+-- parties a flow was linked with can be read back from the chain.
+-- Actions recorded before this migration are linked on the next
+-- write. Links are set once.
+-- ============================================================
+
+ALTER TABLE flow_actions ADD COLUMN chain_seq INTEGER;
+ALTER TABLE flow_actions ADD COLUMN prev_hash TEXT;
+ALTER TABLE flow_actions ADD COLUMN hash TEXT;
+
+CREATE INDEX idx_flow_actions_chain
+ON flow_actions(chain_seq);
+
+CREATE TRIGGER flow_actions_chain_set_once
+BEFORE UPDATE OF chain_seq, prev_hash, hash ON flow_actions
+WHEN OLD.hash IS NOT NULL
+BEGIN
+    SELECT RAISE(FAIL, 'flow_actions chain links cannot be changed');
+END;
@@ -13,21 +13,31 @@ pub type DbPool = Pool<ConnectionManager<SqliteConnection>> ;
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
 
 use crate::errors::app_error::AppError;
+use crate::services::entity_service::EntityService;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
+/// Adds `entity_aliases.normalized`.
+const ALIAS_NORMALIZATION_MIGRATION: &str = "20261018100000";
+
 pub fn run_migrations(conn: &mut SqliteConnection) -> Result<(), AppError> {
     // SQLite needs this for foreign keys to actually work
     diesel::sql_query("PRAGMA foreign_keys = ON;")
         .execute(conn)?;
 
     // Run all pending migrations
-    conn.run_pending_migrations(MIGRATIONS)
-        .map(|_| ())
+    let applied = conn.run_pending_migrations(MIGRATIONS)
         .map_err(|e| {
             log::error!("Database migrations failed: {:?}", e);
             AppError::Internal("Database migrations failed".to_string())
-        })
+        })?;
+
+    // Aliases that predate the normalized column are filled in once, with
+    // the same normalisation lookups use.
+    if applied.iter().any(|v| v.to_string() == ALIAS_NORMALIZATION_MIGRATION) {
+        EntityService::normalize_aliases(conn)?;
+    }
+    Ok(())
 }
 
 
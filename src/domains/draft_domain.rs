@@ -1,15 +1,18 @@
 use crate::domains::ledger_domain::LedgerDomain;
 use crate::errors::app_error::AppError;
-use crate::models::drafts::{Draft, NewDraft};
-use crate::services::draft_service::DraftListItem;
+use crate::models::drafts::{Draft, DraftComment, DraftRevision, DraftRevisionSummary, DraftTransition, NewDraft};
+use crate::services::draft_service::{
+    CommentAnchor, CommentThread, DraftActor, DraftDiff, DraftWithComments,
+};
 use crate::services::ledger_service::{self, LedgerService};
 use crate::services::member_content_service::MemberContent;
+use crate::services::publish_service::SitePublisher;
 use crate::{db::DbPool, services::draft_service::DraftService};
 
 use crate::{
     routes::drafts_api::{self, DraftQuery},
     schema::drafts,
-    types::{DocType, DraftStatus, JsonField},
+    types::{DocType, DraftAction, DraftStatus, JsonField},
 };
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
@@ -78,4 +81,140 @@ impl DraftDomain {
 
         Ok(draft)
     }
+
+    pub fn get_draft(&self, host: i32, draft_id: i32) -> Result<Draft, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::get_draft(&mut conn, host, draft_id)
+    }
+
+    pub fn update_draft(
+        &self,
+        draft: &Draft,
+        updated: &mut NewDraft,
+        actor: &DraftActor,
+    ) -> Result<Draft, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::save_draft(&mut conn, draft, updated, actor)
+    }
+
+    pub fn transition_draft(
+        &self,
+        host: i32,
+        draft_id: i32,
+        action: DraftAction,
+        actor: &DraftActor,
+        notes: Option<String>,
+    ) -> Result<Draft, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::transition(&mut conn, host, draft_id, action, actor, notes)
+    }
+
+    pub fn approve_drafts(&self, host: i32, ids: &[i32], actor: &DraftActor) -> Result<Vec<Draft>, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::approve_all(&mut conn, host, ids, actor)
+    }
+
+    pub fn get_draft_transitions(&self, host: i32, draft_id: i32) -> Result<Vec<DraftTransition>, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::transitions(&mut conn, host, draft_id)
+    }
+
+    pub fn delete_draft(&self, host: i32, draft_id: i32, actor: &DraftActor) -> Result<(), AppError> {
+        let mut conn = self.conn()?;
+        DraftService::delete(&mut conn, host, draft_id, actor)
+    }
+
+    pub fn get_draft_revisions(&self, host: i32, draft_id: i32) -> Result<Vec<DraftRevisionSummary>, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::revisions(&mut conn, host, draft_id)
+    }
+
+    pub fn get_draft_revision(&self, host: i32, draft_id: i32, revision: i32) -> Result<DraftRevision, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::revision(&mut conn, host, draft_id, revision)
+    }
+
+    pub fn diff_draft_revisions(&self, host: i32, draft_id: i32, from: i32, to: i32) -> Result<DraftDiff, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::diff(&mut conn, host, draft_id, from, to)
+    }
+
+    pub fn restore_draft_revision(
+        &self,
+        host: i32,
+        draft_id: i32,
+        revision: i32,
+        actor: &DraftActor,
+    ) -> Result<Draft, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::restore(&mut conn, host, draft_id, revision, actor)
+    }
+
+    pub fn get_draft_with_comments(&self, host: i32, draft_id: i32) -> Result<DraftWithComments, AppError> {
+        let mut conn = self.conn()?;
+        let draft = DraftService::get_draft(&mut conn, host, draft_id)?;
+        let comments = DraftService::comment_threads(&mut conn, host, draft_id)?;
+        Ok(DraftWithComments { draft, comments })
+    }
+
+    pub fn get_draft_comments(&self, host: i32, draft_id: i32) -> Result<Vec<CommentThread>, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::comment_threads(&mut conn, host, draft_id)
+    }
+
+    pub fn add_draft_comment(
+        &self,
+        host: i32,
+        draft_id: i32,
+        actor: &DraftActor,
+        body: &str,
+        parent_id: Option<i32>,
+        anchor: CommentAnchor,
+    ) -> Result<DraftComment, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::add_comment(&mut conn, host, draft_id, actor, body, parent_id, anchor)
+    }
+
+    pub fn resolve_draft_comment(
+        &self,
+        host: i32,
+        draft_id: i32,
+        comment_id: i32,
+        actor: &DraftActor,
+        resolved: bool,
+    ) -> Result<DraftComment, AppError> {
+        let mut conn = self.conn()?;
+        DraftService::resolve_comment(&mut conn, host, draft_id, comment_id, actor, resolved)
+    }
+
+    /// Deploys through the host's site repository when one is configured,
+    /// otherwise only moves the draft to deployed.
+    pub fn deploy_draft(
+        &self,
+        host: i32,
+        draft_id: i32,
+        actor: &DraftActor,
+        publisher: Option<&SitePublisher>,
+        render: impl Fn(&Draft) -> String,
+    ) -> Result<Draft, AppError> {
+        let mut conn = self.conn()?;
+        match publisher {
+            Some(publisher) => DraftService::publish(&mut conn, host, draft_id, actor, publisher, render),
+            None => DraftService::transition(&mut conn, host, draft_id, DraftAction::Deploy, actor, None),
+        }
+    }
+
+    pub fn unpublish_draft(
+        &self,
+        host: i32,
+        draft_id: i32,
+        actor: &DraftActor,
+        publisher: Option<&SitePublisher>,
+    ) -> Result<Draft, AppError> {
+        let mut conn = self.conn()?;
+        match publisher {
+            Some(publisher) => DraftService::unpublish(&mut conn, host, draft_id, actor, publisher),
+            None => DraftService::transition(&mut conn, host, draft_id, DraftAction::Unpublish, actor, None),
+        }
+    }
 }
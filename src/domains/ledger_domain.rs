@@ -1,20 +1,46 @@
-use std::collections::HashMap;
-
+use chrono::NaiveDateTime;
+use diesel::Connection;
 use diesel::dsl::json;
 use serde::Serialize;
 use serde_json::{Value, json};
 use uuid::Uuid;
 
-use crate::db::DbPool;
+use crate::db::{DbConn, DbPool};
 use crate::errors::app_error::AppError;
 
-use crate::models::entities::{Entity, NewEntity};
+use crate::models::commitments::{Commitment, NewCommitment, ProjectCommitments};
+use crate::models::entity_goals::{EntityGoal, NewEntityGoal};
+use crate::models::entity_types::{EntityType, NewEntityType};
+use crate::models::entities::{Entity, EntityAlias, EntityChanges, EntityUser, NewEntity};
 use crate::models::flow_events::{FlowEvent, NewFlowEvent};
+use crate::models::flow_templates::{FlowTemplate, NewFlowTemplate};
+use crate::models::ledger_chain::LedgerCheckpoint;
+use crate::models::ledger_views::{self, ActivitySeries, EntityBalance, VitalSigns};
+use crate::models::resource_types::{NewResourceType, ResourceType};
 
 //use crate::models::{Entity, FlowEvent, NewEntity, NewFlowEvent};
-use crate::services::ledger_service::{EntityRef, LedgerEventRow, LedgerService};
-use crate::types::{Audience, ConfigHash, JsonField};
-use crate::types::flow_query::FlowQuery;
+use crate::services::balance_service::{BalanceService, EntityStatement};
+use crate::services::commitment_service::{CommitmentService, CommitmentView, EntityCommitments};
+use crate::services::entity_service::{
+    ANONYMOUS, DuplicateCandidate, EntityMerge, EntityPerson, EntityService, UserLookup,
+};
+use crate::services::ledger_service::{
+    EntityRef, FlowActionView, FlowCorrection, LedgerEventRow, LedgerEvents, LedgerService,
+};
+use crate::services::entity_type_service::EntityTypeService;
+use crate::services::export_service::{ExportFormat, ExportKind, ExportService};
+use crate::services::geo_service::GeoService;
+use crate::services::goal_service::{GoalService, GoalView};
+use crate::services::integrity_service::{ChainReport, IntegrityService};
+use crate::services::graph_service::{FlowGraph, GraphService};
+use crate::services::import_service::{
+    ENTITY_FIELDS, FLOW_FIELDS, ImportMapping, ImportReport, ImportService,
+};
+use crate::services::resource_service::ResourceService;
+use crate::services::template_service::TemplateService;
+use crate::types::{Audience, ConfigHash, EntityRole, FlowActionType, JsonField, Visibility};
+use crate::types::flow_query::{FlowCursor, FlowQuery, FlowViewer, TimeBucket};
+use crate::types::geo::{GeoArea, GeoPoint};
 
 #[derive(Serialize)]
 pub struct EntityFlows {
@@ -22,6 +48,7 @@ pub struct EntityFlows {
     pub inflows: Vec<LedgerEventRow>,
     pub outflows: Vec<LedgerEventRow>,
     pub entities: Vec<EntityRef>,
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Clone)]
@@ -40,9 +67,12 @@ impl LedgerDomain {
 
     // ENTITY CRUD
 
+    /// Validates the type and details against the host's entity schema.
     pub fn create_entity(&self, new: NewEntity) -> Result<Entity, AppError> {
         let mut conn = self.conn()?;
-        LedgerService::create_entity(&mut conn, new).map_err(|e| AppError::User(e.to_string()))
+        let entity_type = EntityTypeService::validate_entity(&mut conn, new.host_id, &new.entity_type, &new.details.0)?;
+        LedgerService::create_entity(&mut conn, NewEntity { entity_type, ..new })
+            .map_err(|e| AppError::User(e.to_string()))
     }
 
     pub fn save_all_entities(&self, events: Vec<NewEntity>) -> Result<String, AppError> {
@@ -62,14 +92,72 @@ impl LedgerDomain {
         LedgerService::get_effort_contexts(&mut conn, host, audience)
     }
 
-    pub fn get_entity(&self, id: &str) -> Result<Entity, AppError> {
+    /// Hidden entities are reported as not found.
+    pub fn get_entity(&self, id: &str, viewer: &FlowViewer) -> Result<Entity, AppError> {
+        let mut conn = self.conn()?;
+        let entity = LedgerService::get_entity(&mut conn, id).map_err(|e| AppError::User(e.to_string()))?;
+        Self::require_visible(&mut conn, viewer, &entity.id)?;
+        Ok(entity)
+    }
+
+    /// Reports an entity the viewer may not see as not found.
+    fn require_visible(conn: &mut DbConn, viewer: &FlowViewer, id: &str) -> Result<(), AppError> {
+        let id = id.to_string();
+        if !EntityService::visible_ids(conn, viewer, std::slice::from_ref(&id))?.contains(&id) {
+            return Err(AppError::NotFound(format!("Entity {}", id)));
+        }
+        Ok(())
+    }
+
+    pub fn get_all_entities(
+        &self,
+        host_id: i32,
+        include_archived: bool,
+        viewer: &FlowViewer,
+    ) -> Result<Vec<Entity>, AppError> {
+        let mut conn = self.conn()?;
+        let entities = LedgerService::get_entities(&mut conn, host_id, include_archived)
+            .map_err(|e| AppError::User(e.to_string()))?;
+        let ids: Vec<String> = entities.iter().map(|e| e.id.clone()).collect();
+        let visible = EntityService::visible_ids(&mut conn, viewer, &ids)?;
+        Ok(entities.into_iter().filter(|e| visible.contains(&e.id)).collect())
+    }
+
+    /// Applies `changes` if the user is a host admin or an owner of the entity.
+    pub fn update_entity(
+        &self,
+        host: i32,
+        id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+        changes: EntityChanges,
+    ) -> Result<Entity, AppError> {
         let mut conn = self.conn()?;
-        LedgerService::get_entity(&mut conn, id).map_err(|e| AppError::User(e.to_string()))
+        let entity = EntityService::get_on_host(&mut conn, host, id)?;
+        EntityService::require_manager(&mut conn, &entity, user_id, is_host_admin)?;
+
+        let mut changes = changes;
+        if changes.entity_type.is_some() || changes.details.is_some() {
+            let entity_type = changes.entity_type.as_deref().unwrap_or(&entity.entity_type);
+            let details = changes.details.as_ref().unwrap_or(&entity.details);
+            let key = EntityTypeService::validate_entity(&mut conn, host, entity_type, &details.0)?;
+            changes.entity_type = changes.entity_type.map(|_| key);
+        }
+        EntityService::update_entity(&mut conn, &entity, changes)
     }
 
-    pub fn get_all_entities(&self, host_id: i32) -> Result<Vec<Entity>, AppError> {
+    /// Deletes an entity that no flow references; archive it otherwise.
+    pub fn delete_entity(
+        &self,
+        host: i32,
+        id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+    ) -> Result<(), AppError> {
         let mut conn = self.conn()?;
-        LedgerService::get_entities(&mut conn, host_id).map_err(|e| AppError::User(e.to_string()))
+        let entity = EntityService::get_on_host(&mut conn, host, id)?;
+        EntityService::require_manager(&mut conn, &entity, user_id, is_host_admin)?;
+        EntityService::delete_entity(&mut conn, &entity)
     }
 
     pub fn get_user_entity_id(&self, host: i32, user: i32) -> Result<String, AppError> {
@@ -85,77 +173,565 @@ impl LedgerDomain {
         LedgerService::create_flow_event(&mut conn, new).map_err(|e| AppError::User(e.to_string()))
     }
 
+    /// Records a flow that waits for the receiving entity to confirm it.
+    pub fn propose_flow(&self, new: NewFlowEvent) -> Result<FlowEvent, AppError> {
+        let mut conn = self.conn()?;
+        LedgerService::propose_flow(&mut conn, new)
+    }
+
+    /// Proposes a flow on behalf of `new.from_entity`, which the user must
+    /// be linked to unless they are a host admin. The visibility and
+    /// location sent with it are applied in the same transaction.
+    pub fn submit_flow(
+        &self,
+        user_id: i32,
+        is_host_admin: bool,
+        new: NewFlowEvent,
+        visibility: Option<Visibility>,
+        location: Option<GeoPoint>,
+    ) -> Result<FlowEvent, AppError> {
+        let mut conn = self.conn()?;
+        conn.transaction(|conn| Self::submit_flow_in(conn, user_id, is_host_admin, new, visibility, location))
+    }
+
+    /// Submits every row as `submit_flow` would, all or nothing.
+    pub fn submit_flows(
+        &self,
+        user_id: i32,
+        is_host_admin: bool,
+        rows: Vec<(NewFlowEvent, Option<Visibility>, Option<GeoPoint>)>,
+    ) -> Result<Vec<FlowEvent>, AppError> {
+        let mut conn = self.conn()?;
+        conn.transaction(|conn| {
+            rows.into_iter()
+                .map(|(new, visibility, location)| {
+                    Self::submit_flow_in(conn, user_id, is_host_admin, new, visibility, location)
+                })
+                .collect()
+        })
+    }
+
+    fn submit_flow_in(
+        conn: &mut DbConn,
+        user_id: i32,
+        is_host_admin: bool,
+        new: NewFlowEvent,
+        visibility: Option<Visibility>,
+        location: Option<GeoPoint>,
+    ) -> Result<FlowEvent, AppError> {
+        EntityService::require_recorder(conn, &new.from_entity, user_id, is_host_admin)?;
+        ResourceService::validate_flow(conn, new.host_id, &new.resource_type, &new.quantity_unit)?;
+        let host = new.host_id;
+        let flow = LedgerService::propose_flow(conn, new)?;
+        let flow = match visibility {
+            Some(visibility) => {
+                LedgerService::set_flow_visibility(conn, host, &flow.id, user_id, is_host_admin, visibility)?;
+                FlowEvent { visibility: visibility.value().to_string(), ..flow }
+            }
+            None => flow,
+        };
+        match location {
+            Some(point) => GeoService::set_flow_location(conn, host, &flow.id, user_id, is_host_admin, point),
+            None => Ok(flow),
+        }
+    }
+
+    pub fn set_flow_visibility(
+        &self,
+        host: i32,
+        flow_id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+        visibility: Visibility,
+    ) -> Result<(), AppError> {
+        let mut conn = self.conn()?;
+        LedgerService::set_flow_visibility(&mut conn, host, flow_id, user_id, is_host_admin, visibility)
+    }
+
+    // COMMITMENTS
+
+    pub fn create_commitment(
+        &self,
+        user_id: i32,
+        is_host_admin: bool,
+        new: NewCommitment,
+    ) -> Result<Commitment, AppError> {
+        let mut conn = self.conn()?;
+        CommitmentService::create(&mut conn, user_id, is_host_admin, new)
+    }
+
+    pub fn fulfil_commitment(
+        &self,
+        host: i32,
+        commitment_id: &str,
+        flow_id: &str,
+        quantity: Option<f32>,
+        user_id: i32,
+        is_host_admin: bool,
+    ) -> Result<CommitmentView, AppError> {
+        let mut conn = self.conn()?;
+        CommitmentService::fulfil(&mut conn, host, commitment_id, flow_id, quantity, user_id, is_host_admin)
+    }
+
+    pub fn cancel_commitment(
+        &self,
+        host: i32,
+        commitment_id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+    ) -> Result<Commitment, AppError> {
+        let mut conn = self.conn()?;
+        CommitmentService::cancel(&mut conn, host, commitment_id, user_id, is_host_admin)
+    }
+
+    /// Commitments of an entity the viewer can see, leaving out those made
+    /// with counterparties hidden from them.
+    pub fn get_entity_commitments(
+        &self,
+        host: i32,
+        entity_id: &str,
+        include_closed: bool,
+        viewer: &FlowViewer,
+    ) -> Result<EntityCommitments, AppError> {
+        let mut conn = self.conn()?;
+        let entity = EntityService::get_on_host(&mut conn, host, entity_id)?;
+        Self::require_visible(&mut conn, viewer, &entity.id)?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let mut report = CommitmentService::for_entity(&mut conn, host, &entity.id, include_closed, now)?;
+        let counterparties: Vec<String> = report
+            .owes
+            .iter()
+            .map(|v| v.commitment.to_entity.clone())
+            .chain(report.owed.iter().map(|v| v.commitment.from_entity.clone()))
+            .collect();
+        let visible = EntityService::visible_ids(&mut conn, viewer, &counterparties)?;
+        report.owes.retain(|v| visible.contains(&v.commitment.to_entity));
+        report.owed.retain(|v| visible.contains(&v.commitment.from_entity));
+        Ok(report)
+    }
+
+    /// Totals for the projects the viewer can see.
+    pub fn get_project_commitments(
+        &self,
+        host: i32,
+        viewer: &FlowViewer,
+    ) -> Result<Vec<ProjectCommitments>, AppError> {
+        let mut conn = self.conn()?;
+        let mut report =
+            CommitmentService::outstanding_by_project(&mut conn, host, chrono::Utc::now().naive_utc())?;
+        let ids: Vec<String> = report.iter().map(|p| p.project_id.clone()).collect();
+        let visible = EntityService::visible_ids(&mut conn, viewer, &ids)?;
+        report.retain(|p| visible.contains(&p.project_id));
+        Ok(report)
+    }
+
+    // LOCATIONS
+
+    pub fn set_entity_location(
+        &self,
+        host: i32,
+        id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+        geometry: Option<Value>,
+    ) -> Result<Entity, AppError> {
+        let mut conn = self.conn()?;
+        let entity = EntityService::get_on_host(&mut conn, host, id)?;
+        EntityService::require_manager(&mut conn, &entity, user_id, is_host_admin)?;
+        GeoService::set_entity_location(&mut conn, &entity, geometry)
+    }
+
+    pub fn set_flow_location(
+        &self,
+        host: i32,
+        flow_id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+        point: GeoPoint,
+    ) -> Result<FlowEvent, AppError> {
+        let mut conn = self.conn()?;
+        GeoService::set_flow_location(&mut conn, host, flow_id, user_id, is_host_admin, point)
+    }
+
+    pub fn get_entity_features(
+        &self,
+        host: i32,
+        viewer: &FlowViewer,
+        area: Option<&GeoArea>,
+    ) -> Result<Value, AppError> {
+        let mut conn = self.conn()?;
+        GeoService::entity_features(&mut conn, host, viewer, area)
+    }
+
+    pub fn get_flow_features(&self, flow_query: &FlowQuery) -> Result<Value, AppError> {
+        let mut conn = self.conn()?;
+        let (since, until) = vital_signs_window(flow_query);
+        let windowed = flow_query.clone().since(since).until(until);
+        GeoService::flow_features(&mut conn, &windowed)
+    }
+
+    // GOALS
+
+    pub fn get_goals(
+        &self,
+        host: i32,
+        entity_id: Option<&str>,
+        viewer: &FlowViewer,
+    ) -> Result<Vec<GoalView>, AppError> {
+        let mut conn = self.conn()?;
+        GoalService::list(&mut conn, host, entity_id, viewer, chrono::Utc::now().naive_utc())
+    }
+
+    pub fn create_goal(
+        &self,
+        user_id: i32,
+        is_host_admin: bool,
+        new: NewEntityGoal,
+    ) -> Result<EntityGoal, AppError> {
+        let mut conn = self.conn()?;
+        GoalService::create(&mut conn, user_id, is_host_admin, new)
+    }
+
+    pub fn update_goal(
+        &self,
+        goal_id: i32,
+        user_id: i32,
+        is_host_admin: bool,
+        new: NewEntityGoal,
+    ) -> Result<EntityGoal, AppError> {
+        let mut conn = self.conn()?;
+        GoalService::update(&mut conn, goal_id, user_id, is_host_admin, new)
+    }
+
+    pub fn delete_goal(
+        &self,
+        host: i32,
+        goal_id: i32,
+        user_id: i32,
+        is_host_admin: bool,
+    ) -> Result<(), AppError> {
+        let mut conn = self.conn()?;
+        GoalService::delete(&mut conn, host, goal_id, user_id, is_host_admin)
+    }
+
+    // FLOW TEMPLATES
+
+    pub fn create_flow_template(
+        &self,
+        user_id: i32,
+        is_host_admin: bool,
+        new: NewFlowTemplate,
+    ) -> Result<FlowTemplate, AppError> {
+        let mut conn = self.conn()?;
+        TemplateService::create(&mut conn, user_id, is_host_admin, new)
+    }
+
+    /// Templates of an entity the viewer can see, leaving out those whose
+    /// other party is hidden from them.
+    pub fn get_flow_templates(
+        &self,
+        host: i32,
+        entity_id: &str,
+        viewer: &FlowViewer,
+    ) -> Result<Vec<FlowTemplate>, AppError> {
+        let mut conn = self.conn()?;
+        let entity = EntityService::get_on_host(&mut conn, host, entity_id)?;
+        Self::require_visible(&mut conn, viewer, &entity.id)?;
+
+        let templates = TemplateService::get_for_entity(&mut conn, host, &entity.id)?;
+        let parties: Vec<String> = templates
+            .iter()
+            .flat_map(|t| [t.from_entity.clone(), t.to_entity.clone()])
+            .collect();
+        let visible = EntityService::visible_ids(&mut conn, viewer, &parties)?;
+        Ok(templates
+            .into_iter()
+            .filter(|t| visible.contains(&t.from_entity) && visible.contains(&t.to_entity))
+            .collect())
+    }
+
+    pub fn set_flow_template_paused(
+        &self,
+        host: i32,
+        template_id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+        paused: bool,
+    ) -> Result<FlowTemplate, AppError> {
+        let mut conn = self.conn()?;
+        let now = chrono::Utc::now().naive_utc();
+        TemplateService::set_paused(&mut conn, host, template_id, user_id, is_host_admin, paused, now)
+    }
+
+    /// Proposes every template flow due by `now`; see `TemplateService::run_due`.
+    pub fn run_flow_templates(&self, now: NaiveDateTime) -> Result<usize, AppError> {
+        let mut conn = self.conn()?;
+        TemplateService::run_due(&mut conn, now)
+    }
+
+    /// Runs due templates every `period` for as long as the server is up.
+    pub async fn schedule_flow_templates(self, period: std::time::Duration) {
+        let mut ticks = actix_web::rt::time::interval(period);
+        loop {
+            ticks.tick().await;
+            let domain = self.clone();
+            let now = chrono::Utc::now().naive_utc();
+            match actix_web::web::block(move || domain.run_flow_templates(now)).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(count)) => log::info!("Proposed {} flows from templates", count),
+                Ok(Err(e)) => log::error!("Flow templates failed: {}", e),
+                Err(e) => log::error!("Flow templates failed: {}", e),
+            }
+        }
+    }
+
+    // INTEGRITY
+
+    pub fn verify_ledger(&self, host: i32, secret: &str) -> Result<ChainReport, AppError> {
+        let mut conn = self.conn()?;
+        IntegrityService::verify(&mut conn, host, secret)
+    }
+
+    pub fn get_ledger_checkpoints(&self, host: i32) -> Result<Vec<LedgerCheckpoint>, AppError> {
+        let mut conn = self.conn()?;
+        IntegrityService::checkpoints(&mut conn, host)
+    }
+
+    /// Signs the chain head now, or returns the latest checkpoint if
+    /// nothing was added since.
+    pub fn create_ledger_checkpoint(&self, host: i32, secret: &str) -> Result<LedgerCheckpoint, AppError> {
+        let mut conn = self.conn()?;
+        match IntegrityService::checkpoint(&mut conn, host, secret, chrono::Utc::now().naive_utc())? {
+            Some(checkpoint) => Ok(checkpoint),
+            None => IntegrityService::checkpoints(&mut conn, host)?
+                .pop()
+                .ok_or_else(|| AppError::BadRequest("The ledger is empty".into())),
+        }
+    }
+
+    /// Checkpoints every host's chain every `period` while the server is up.
+    pub async fn schedule_ledger_checkpoints(self, secret: String, period: std::time::Duration) {
+        let mut ticks = actix_web::rt::time::interval(period);
+        loop {
+            ticks.tick().await;
+            let domain = self.clone();
+            let secret = secret.clone();
+            let now = chrono::Utc::now().naive_utc();
+            let run = move || {
+                let mut conn = domain.conn()?;
+                IntegrityService::checkpoint_all(&mut conn, &secret, now)
+            };
+            match actix_web::web::block(run).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(count)) => log::info!("Signed {} ledger checkpoints", count),
+                Ok(Err(e)) => log::error!("Ledger checkpoints failed: {}", e),
+                Err(e) => log::error!("Ledger checkpoints failed: {}", e),
+            }
+        }
+    }
+
+    /// Confirms, disputes or rejects a proposed flow.
+    pub fn decide_flow(
+        &self,
+        host: i32,
+        flow_id: &str,
+        user_id: i32,
+        action: FlowActionType,
+        reason: Option<&str>,
+    ) -> Result<FlowActionView, AppError> {
+        let mut conn = self.conn()?;
+        LedgerService::decide_flow(&mut conn, host, flow_id, user_id, action, reason)
+    }
+
+    /// Cancels a mistaken flow with a compensating entry.
+    pub fn reverse_flow(
+        &self,
+        host: i32,
+        flow_id: &str,
+        user_id: i32,
+        reason: Option<&str>,
+    ) -> Result<FlowCorrection, AppError> {
+        let mut conn = self.conn()?;
+        let actor = LedgerService::get_user_entity_id(&mut conn, host, user_id)?;
+        LedgerService::reverse_flow(&mut conn, host, flow_id, &actor, reason)
+    }
+
+    /// Reverses a flow and records its corrected replacement.
+    pub fn amend_flow(
+        &self,
+        host: i32,
+        flow_id: &str,
+        user_id: i32,
+        replacement: NewFlowEvent,
+        reason: Option<&str>,
+    ) -> Result<FlowCorrection, AppError> {
+        let mut conn = self.conn()?;
+        ResourceService::validate_flow(&mut conn, host, &replacement.resource_type, &replacement.quantity_unit)?;
+        let actor = LedgerService::get_user_entity_id(&mut conn, host, user_id)?;
+        let replacement = NewFlowEvent {
+            created_by: actor.clone(),
+            ..replacement
+        };
+        LedgerService::amend_flow(&mut conn, host, flow_id, &actor, replacement, reason)
+    }
+
     pub fn get_flow_events(
         &self,
         host: i32,
-    ) -> Result<(Vec<LedgerEventRow>, Vec<EntityRef>, Vec<String>), AppError> {
+    ) -> Result<LedgerEvents, AppError> {
         let mut conn = self.conn()?;
         LedgerService::get_flow_events(&mut conn, FlowQuery::new(host).both())
             .map_err(|e| AppError::User(e.to_string()))
     }
 
+    /// One page of an entity's flows, split into inflows and outflows.
+    /// The page (and its cursor) covers both directions together.
     pub fn get_flows_for_entity(
         &self,
-        host: i32,
         entity_id: &str,
+        flow_query: FlowQuery,
     ) -> Result<EntityFlows, AppError> {
 
         let entity_id = Uuid::parse_str(entity_id)
             .map_err(|e| AppError::User(format!("Bad UUID: {}", e)))?;
 
         let mut conn = self.conn()?;
-        let (inflows, inflow_entities, _) =
-            LedgerService::get_flow_events(&mut conn, FlowQuery::new(host)
-                .to()
-                .entity(entity_id)
-            )?;
-        let (outflows, outflow_entities, _) =
-            LedgerService::get_flow_events(&mut conn, FlowQuery::new(host)
-            .from()
-            .entity(entity_id)
-            )?;
+        let limit = flow_query.limit;
+        let (rows, entities, _, _) =
+            LedgerService::get_flow_events(&mut conn, flow_query.entity(entity_id).both())?;
+        let next_cursor = next_cursor(&rows, limit);
 
-        // Merge entities, deduplicating by id
-        let mut entity_map: HashMap<String, EntityRef> = HashMap::new();
-
-        for e in inflow_entities
-            .into_iter()
-            .chain(outflow_entities.into_iter())
-        {
-            entity_map.entry(e.id.clone()).or_insert(e);
-        }
-
-        let entities: Vec<EntityRef> = entity_map.into_values().collect();
+        let id = entity_id.to_string();
+        let (inflows, outflows): (Vec<LedgerEventRow>, Vec<LedgerEventRow>) =
+            rows.into_iter().partition(|row| row.to_entity == id);
 
         Ok(EntityFlows {
-            entity_id: entity_id.to_string(),
+            entity_id: id,
             inflows,
             outflows,
             entities,
+            next_cursor,
         })
     }
 
     pub fn get_ledger_summary(&self, flow_query: FlowQuery) -> Result<Value, AppError> {
         let mut conn = self.conn()?;
+        let limit = flow_query.limit;
         let events = LedgerService::get_flow_events(&mut conn, flow_query)?;
         Ok(json!(
             { "total_events": events.0.len(), 
+            "next_cursor": next_cursor(&events.0, limit),
             "ledger": events.0, 
             "entities": events.1 ,
-            "resources": events.2 
+            "resources": events.2,
+            "resource_registry": events.3
+        }))
+    }
+
+    // VITAL SIGNS
+
+    /// Vital signs for the query's host over its [since, until] window.
+    /// Defaults to the 30 days ending now when the window is open.
+    pub fn get_vital_signs(&self, flow_query: &FlowQuery) -> Result<VitalSigns, AppError> {
+        let mut conn = self.conn()?;
+        let (since, until) = vital_signs_window(flow_query);
+        ledger_views::get_vital_signs(&mut conn, flow_query.host, since, until).map_err(AppError::Db)
+    }
+
+    /// Anonymised totals for the public site: no individual flows or names,
+    /// but computed from every confirmed flow like the vital signs.
+    pub fn get_public_summary(&self, flow_query: &FlowQuery) -> Result<Value, AppError> {
+        let mut conn = self.conn()?;
+        let (since, until) = vital_signs_window(flow_query);
+        let signs = ledger_views::get_vital_signs(&mut conn, flow_query.host, since, until)?;
+        let totals = ledger_views::get_resource_totals(&mut conn, flow_query.host, since, until)?;
+        Ok(json!({
+            "vital_signs": signs,
+            "totals": totals,
         }))
     }
 
+    /// Bucketed activity for the vital-signs window.
+    pub fn get_activity_series(
+        &self,
+        flow_query: &FlowQuery,
+        bucket: TimeBucket,
+    ) -> Result<ActivitySeries, AppError> {
+        let mut conn = self.conn()?;
+        let (since, until) = vital_signs_window(flow_query);
+        ledger_views::get_activity_series(&mut conn, flow_query.host, since, until, bucket)
+            .map_err(AppError::Db)
+    }
+
+    /// Flow graph for the vital-signs window, optionally centred on
+    /// `flow_query.entity` and limited to `depth` hops around it.
+    pub fn get_flow_graph(&self, flow_query: &FlowQuery, depth: u32) -> Result<FlowGraph, AppError> {
+        let mut conn = self.conn()?;
+        let (since, until) = vital_signs_window(flow_query);
+        let center = flow_query.entity.map(|id| id.to_string());
+        let mut graph =
+            GraphService::get_flow_graph(&mut conn, flow_query, since, until, center.as_deref(), depth)?;
+
+        let ids: Vec<String> = graph.nodes.iter().map(|n| n.id.clone()).collect();
+        let visible = EntityService::visible_ids(&mut conn, &flow_query.viewer, &ids)?;
+        for node in graph.nodes.iter_mut().filter(|n| !visible.contains(&n.id)) {
+            node.name = ANONYMOUS.to_string();
+        }
+        Ok(graph)
+    }
+
+    /// Balances as of the window's end over the flows the viewer may see.
+    /// Entities hidden from the viewer are left out.
+    pub fn get_entity_balances(&self, flow_query: &FlowQuery) -> Result<Vec<EntityBalance>, AppError> {
+        let mut conn = self.conn()?;
+        let (_, until) = vital_signs_window(flow_query);
+        let as_of = FlowQuery {
+            entity: None,
+            since: None,
+            until: Some(until),
+            limit: None,
+            offset: None,
+            cursor: None,
+            ..flow_query.clone()
+        };
+        let mut balances = ledger_views::get_entity_balances(&mut conn, &as_of)?;
+
+        let mut ids: Vec<String> = balances.iter().map(|b| b.entity_id.clone()).collect();
+        ids.dedup();
+        let visible = EntityService::visible_ids(&mut conn, &flow_query.viewer, &ids)?;
+        balances.retain(|b| visible.contains(&b.entity_id));
+        Ok(balances)
+    }
+
+    // BALANCES
+
+    pub fn get_entity_statement(&self, flow_query: &FlowQuery) -> Result<EntityStatement, AppError> {
+        let mut conn = self.conn()?;
+        let mut statement = BalanceService::get_entity_statement(&mut conn, flow_query)?;
+
+        let mut ids: Vec<String> = statement.lines.iter().map(|l| l.counterparty.id.clone()).collect();
+        ids.push(statement.entity.id.clone());
+        let visible = EntityService::visible_ids(&mut conn, &flow_query.viewer, &ids)?;
+        if !visible.contains(&statement.entity.id) {
+            return Err(AppError::NotFound(format!("Entity {}", statement.entity.id)));
+        }
+        for line in statement.lines.iter_mut().filter(|l| !visible.contains(&l.counterparty.id)) {
+            line.counterparty.name = ANONYMOUS.to_string();
+        }
+        Ok(statement)
+    }
+
     pub fn resolve_or_create_entity(&self, input: i32, host: i32) -> Result<String, AppError> {
         let mut conn = self.conn()?;
-        let id = LedgerService::get_user_entity_id(&mut conn, host, input).unwrap();
-        Ok(id)
+        LedgerService::get_user_entity_id(&mut conn, host, input)
+            .map_err(|e| AppError::User(e.to_string()))
     }
 
 
     pub fn find_or_create_entity(&self, input: &str, host: i32) -> Result<String, AppError> {
         let mut conn = self.conn()?;
-        match  LedgerService::find_entity_by_name(&mut conn, input, host) {
+        match  EntityService::find_by_name_or_alias(&mut conn, input, host) {
             Ok(entity) => Ok(entity.id),
             Err(_) => {
                 let new_entity = NewEntity {
@@ -180,13 +756,405 @@ impl LedgerDomain {
     //     Ok(id)
     // }
 
+    /// Resolves an id, name or alias. Ids of merged duplicates resolve to
+    /// the entity they were merged into.
     pub fn resolve_entity(&self, input: &str, host: i32) -> Result<String, AppError> {
         let mut conn = self.conn()?;
-        if uuid::Uuid::parse_str(input).is_ok() {
-            return Ok(input.to_string());
+        EntityService::resolve(&mut conn, input, host)
+    }
+
+    // EXPORT
+
+    /// Renders the export page after position `after`, or `None` once the
+    /// rows run out. Returns the chunk and the position of its last row.
+    /// `first` is true until an earlier page has written something.
+    pub fn export_page(
+        &self,
+        kind: ExportKind,
+        format: ExportFormat,
+        flow_query: &FlowQuery,
+        after: Option<&str>,
+        first: bool,
+    ) -> Result<Option<(String, String)>, AppError> {
+        let mut conn = self.conn()?;
+        let page = match kind {
+            ExportKind::Flows => {
+                let cursor = after.and_then(FlowCursor::decode);
+                let flows = ExportService::flows_page(&mut conn, flow_query, cursor)?;
+                let Some(last) = flows.last() else {
+                    return Ok(None);
+                };
+                let next = FlowCursor { timestamp: last.timestamp, id: last.id.clone() }.encode();
+                let registry = ResourceService::get_registry(&mut conn, flow_query.host)?;
+                (ExportService::render_flows(format, &flows, &registry, first)?, next)
+            }
+            ExportKind::Entities => {
+                let mut entities = ExportService::entities_page(&mut conn, flow_query, after)?;
+                let Some(last) = entities.last() else {
+                    return Ok(None);
+                };
+                let next = last.id.clone();
+                let ids: Vec<String> = entities.iter().map(|e| e.id.clone()).collect();
+                let visible = EntityService::visible_ids(&mut conn, &flow_query.viewer, &ids)?;
+                entities.retain(|e| visible.contains(&e.id));
+                (ExportService::render_entities(format, &entities, first)?, next)
+            }
+        };
+        Ok(Some(page))
+    }
+
+    // CSV IMPORT
+
+    /// Validates every row, then saves the batch in one transaction only
+    /// when nothing failed and this is not a dry run.
+    pub fn import_entities_csv(
+        &self,
+        host: i32,
+        created_by: &str,
+        data: &[u8],
+        mapping: &ImportMapping,
+        dry_run: bool,
+    ) -> Result<ImportReport<NewEntity>, AppError> {
+        let rows = ImportService::read_rows(data, mapping, ENTITY_FIELDS, &["name", "entity_type"])?;
+        let total_rows = rows.len();
+        let mut conn = self.conn()?;
+        let mut errors = Vec::new();
+        let mut entities: Vec<NewEntity> = Vec::new();
+
+        for row in &rows {
+            let (name, entity_type) = match (row.require("name"), row.require("entity_type")) {
+                (Ok(name), Ok(entity_type)) => (name, entity_type),
+                (name, entity_type) => {
+                    errors.extend(name.err());
+                    errors.extend(entity_type.err());
+                    continue;
+                }
+            };
+            let duplicate = entities.iter().any(|e| e.name.eq_ignore_ascii_case(name));
+            if duplicate || EntityService::resolve(&mut conn, name, host).is_ok() {
+                errors.push(row.error("name", format!("Entity '{}' already exists", name)));
+                continue;
+            }
+            // Imports carry no details, so only the type is checked.
+            let entity_type = match EntityTypeService::resolve_type(&mut conn, host, entity_type) {
+                Ok(key) => key,
+                Err(e) => {
+                    errors.push(row.error("entity_type", e.to_string()));
+                    continue;
+                }
+            };
+            entities.push(NewEntity {
+                id: Uuid::new_v4().to_string(),
+                name: name.to_string(),
+                entity_type,
+                host_id: host,
+                created_by: created_by.to_string(),
+                created_at: chrono::Utc::now().naive_utc(),
+                details: JsonField(json!({ "imported": "CSV import" })),
+            });
+        }
+
+        let committed = !dry_run && errors.is_empty() && !entities.is_empty();
+        if committed {
+            LedgerService::save_all_entities(&mut conn, entities.clone())?;
         }
-        let entity = LedgerService::find_entity_by_name(&mut conn, input, host)?;
 
-        Ok(entity.id)
+        Ok(ImportReport {
+            dry_run,
+            committed,
+            total_rows,
+            valid_rows: entities.len(),
+            errors,
+            rows: entities,
+        })
     }
+
+    /// Resolves entity names through aliases and checks resource types and
+    /// units against the host registry. All-or-nothing, like the entity import.
+    pub fn import_flows_csv(
+        &self,
+        host: i32,
+        created_by: &str,
+        data: &[u8],
+        mapping: &ImportMapping,
+        dry_run: bool,
+    ) -> Result<ImportReport<NewFlowEvent>, AppError> {
+        let rows = ImportService::read_rows(
+            data,
+            mapping,
+            FLOW_FIELDS,
+            &["from", "to", "resource_type", "quantity", "date"],
+        )?;
+        let total_rows = rows.len();
+        let mut conn = self.conn()?;
+
+        // Validation and the insert share one transaction, so the registry
+        // and entities checked are the ones the rows are saved against.
+        let (flows, errors, committed) = conn.transaction(|conn| {
+            let registry = ResourceService::get_registry(conn, host)?;
+            let mut errors = Vec::new();
+            let mut flows: Vec<NewFlowEvent> = Vec::new();
+
+            for row in &rows {
+                let before = errors.len();
+                let mut entity = |field: &str| match row.require(field) {
+                    Ok(input) => EntityService::resolve(conn, input, host)
+                        .map_err(|_| errors.push(row.error(field, format!("Unknown entity '{}'", input))))
+                        .ok(),
+                    Err(e) => {
+                        errors.push(e);
+                        None
+                    }
+                };
+                let from_entity = entity("from");
+                let to_entity = entity("to");
+
+                let quantity_value = match row.require("quantity") {
+                    Ok(q) => ImportService::parse_quantity(q).or_else(|| {
+                        errors.push(row.error("quantity", format!("'{}' is not a positive number", q)));
+                        None
+                    }),
+                    Err(e) => {
+                        errors.push(e);
+                        None
+                    }
+                };
+
+                let timestamp = match row.require("date") {
+                    Ok(date) => ImportService::parse_timestamp(date, row.get("time")).or_else(|| {
+                        errors.push(row.error("date", format!("Invalid date '{}'", date)));
+                        None
+                    }),
+                    Err(e) => {
+                        errors.push(e);
+                        None
+                    }
+                };
+
+                let resource = match row.require("resource_type") {
+                    Ok(resource_type) => {
+                        let unit = row
+                            .get("quantity_unit")
+                            .or_else(|| {
+                                registry
+                                    .iter()
+                                    .find(|r| r.key == resource_type)
+                                    .map(|r| r.default_unit.as_str())
+                            })
+                            .unwrap_or_default();
+                        match ResourceService::check_flow(&registry, resource_type, unit) {
+                            Ok(()) => Some((resource_type, unit)),
+                            Err(e) => {
+                                errors.push(row.error("resource_type", e.to_string()));
+                                None
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        errors.push(e);
+                        None
+                    }
+                };
+
+                if errors.len() > before {
+                    continue;
+                }
+                let (Some(from_entity), Some(to_entity), Some(quantity_value), Some(timestamp), Some((resource_type, unit))) =
+                    (from_entity, to_entity, quantity_value, timestamp, resource)
+                else {
+                    continue;
+                };
+                flows.push(NewFlowEvent {
+                    id: Uuid::new_v4().to_string(),
+                    timestamp,
+                    recorded_at: chrono::Utc::now().naive_utc(),
+                    from_entity,
+                    to_entity,
+                    host_id: host,
+                    resource_type: resource_type.to_string(),
+                    quantity_value,
+                    quantity_unit: unit.to_string(),
+                    notes: row.get("notes").map(str::to_string),
+                    details: JsonField(json!({ "imported": "CSV import" })),
+                    created_by: created_by.to_string(),
+                });
+            }
+
+            let committed = !dry_run && errors.is_empty() && !flows.is_empty();
+            if committed {
+                LedgerService::save_all_flow_events(conn, flows.clone())?;
+            }
+            Ok::<_, AppError>((flows, errors, committed))
+        })?;
+
+        Ok(ImportReport {
+            dry_run,
+            committed,
+            total_rows,
+            valid_rows: flows.len(),
+            errors,
+            rows: flows,
+        })
+    }
+
+    // RESOURCE REGISTRY
+
+    pub fn get_resource_types(&self, host: i32) -> Result<Vec<ResourceType>, AppError> {
+        let mut conn = self.conn()?;
+        ResourceService::get_registry(&mut conn, host)
+    }
+
+    pub fn create_resource_type(&self, new: NewResourceType) -> Result<ResourceType, AppError> {
+        let mut conn = self.conn()?;
+        ResourceService::create(&mut conn, new)
+    }
+
+    pub fn update_resource_type(&self, id: i32, new: NewResourceType) -> Result<ResourceType, AppError> {
+        let mut conn = self.conn()?;
+        ResourceService::update(&mut conn, id, new)
+    }
+
+    pub fn delete_resource_type(&self, host: i32, id: i32) -> Result<(), AppError> {
+        let mut conn = self.conn()?;
+        ResourceService::delete(&mut conn, host, id)
+    }
+
+    // ENTITY TYPE SCHEMA
+
+    pub fn get_entity_types(&self, host: i32) -> Result<Vec<EntityType>, AppError> {
+        let mut conn = self.conn()?;
+        EntityTypeService::get_registry(&mut conn, host)
+    }
+
+    pub fn create_entity_type(&self, new: NewEntityType) -> Result<EntityType, AppError> {
+        let mut conn = self.conn()?;
+        EntityTypeService::create(&mut conn, new)
+    }
+
+    pub fn update_entity_type(&self, id: i32, new: NewEntityType) -> Result<EntityType, AppError> {
+        let mut conn = self.conn()?;
+        EntityTypeService::update(&mut conn, id, new)
+    }
+
+    pub fn delete_entity_type(&self, host: i32, id: i32) -> Result<(), AppError> {
+        let mut conn = self.conn()?;
+        EntityTypeService::delete(&mut conn, host, id)
+    }
+
+    // ENTITY PEOPLE
+
+    /// Hidden entities are reported as not found.
+    pub fn get_entity_people(
+        &self,
+        host: i32,
+        entity_id: &str,
+        viewer: &FlowViewer,
+    ) -> Result<Vec<EntityPerson>, AppError> {
+        let mut conn = self.conn()?;
+        let entity = EntityService::get_on_host(&mut conn, host, entity_id)?;
+        Self::require_visible(&mut conn, viewer, &entity.id)?;
+        EntityService::list_people(&mut conn, &entity)
+    }
+
+    pub fn invite_to_entity(
+        &self,
+        host: i32,
+        entity_id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+        invitee: UserLookup<'_>,
+        role: EntityRole,
+    ) -> Result<EntityUser, AppError> {
+        let mut conn = self.conn()?;
+        let entity = EntityService::get_on_host(&mut conn, host, entity_id)?;
+        EntityService::invite(&mut conn, &entity, user_id, is_host_admin, invitee, role)
+    }
+
+    pub fn respond_to_entity_invite(
+        &self,
+        host: i32,
+        entity_id: &str,
+        user_id: i32,
+        accept: bool,
+    ) -> Result<EntityUser, AppError> {
+        let mut conn = self.conn()?;
+        let entity = EntityService::get_on_host(&mut conn, host, entity_id)?;
+        EntityService::respond_to_invite(&mut conn, &entity, user_id, accept)
+    }
+
+    pub fn set_entity_role(
+        &self,
+        host: i32,
+        entity_id: &str,
+        user_id: i32,
+        is_host_admin: bool,
+        member: i32,
+        role: EntityRole,
+    ) -> Result<EntityUser, AppError> {
+        let mut conn = self.conn()?;
+        let entity = EntityService::get_on_host(&mut conn, host, entity_id)?;
+        EntityService::set_role(&mut conn, &entity, user_id, is_host_admin, member, role)
+    }
+
+    // ALIASES AND MERGES
+
+    pub fn add_entity_alias(
+        &self,
+        host: i32,
+        entity_id: &str,
+        alias: &str,
+        user_id: i32,
+    ) -> Result<EntityAlias, AppError> {
+        let mut conn = self.conn()?;
+        let entity = LedgerService::get_entity(&mut conn, entity_id)?;
+        if entity.host_id != host {
+            return Err(AppError::NotFound(format!("Entity {}", entity_id)));
+        }
+        let actor = LedgerService::get_user_entity_id(&mut conn, host, user_id)?;
+        EntityService::add_alias(&mut conn, &entity, alias, &actor)
+    }
+
+    pub fn merge_entities(
+        &self,
+        host: i32,
+        canonical_id: &str,
+        duplicate_id: &str,
+        user_id: i32,
+    ) -> Result<EntityMerge, AppError> {
+        let mut conn = self.conn()?;
+        let actor = LedgerService::get_user_entity_id(&mut conn, host, user_id)?;
+        EntityService::merge_entities(&mut conn, host, canonical_id, duplicate_id, &actor)
+    }
+
+    pub fn find_duplicate_entities(&self, host: i32) -> Result<Vec<DuplicateCandidate>, AppError> {
+        let mut conn = self.conn()?;
+        EntityService::find_duplicates(&mut conn, host)
+    }
+}
+
+const DEFAULT_WINDOW_DAYS: i64 = 30;
+
+/// Cursor for the page after `rows`, when the page came back full.
+fn next_cursor(rows: &[LedgerEventRow], limit: Option<i64>) -> Option<String> {
+    let last = rows.last()?;
+    if limit? > rows.len() as i64 {
+        return None;
+    }
+    Some(
+        FlowCursor {
+            timestamp: last.timestamp,
+            id: last.id.clone(),
+        }
+        .encode(),
+    )
+}
+
+fn vital_signs_window(flow_query: &FlowQuery) -> (NaiveDateTime, NaiveDateTime) {
+    let until = flow_query
+        .until
+        .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+    let since = flow_query
+        .since
+        .unwrap_or_else(|| until - chrono::Duration::days(DEFAULT_WINDOW_DAYS));
+    (since, until)
 }
@@ -16,6 +16,21 @@ pub struct ErrorResponse {
     // request_id: Option<String>,
 }
 
+/// One field's problem, for `AppError::Validation`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// `ErrorResponse` plus every field that failed validation.
+#[derive(Serialize)]
+pub struct ValidationErrorResponse {
+    #[serde(flatten)]
+    pub error: ErrorResponse,
+    pub errors: Vec<FieldError>,
+}
+
 /// Centralized app error type
 #[derive(Debug)]
 pub enum AppError {
@@ -28,6 +43,7 @@ pub enum AppError {
     BcryptError(BcryptError),
     NotFound(String), 
     BadRequest(String),
+    Validation(Vec<FieldError>),
     Unauthorized,
 
 }
@@ -43,6 +59,10 @@ impl fmt::Display for AppError {
             AppError::BcryptError(e) => write!(f, "Bcrypt error: {}", e),
             AppError::NotFound(e) => write!(f, "Not found: {}", e), // ← display message
             AppError::BadRequest(e) => write!(f, "Not found: {}", e), // ← display message
+            AppError::Validation(errors) => {
+                let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+                write!(f, "Validation failed: {}", messages.join("; "))
+            }
             AppError::Unauthorized => write!(f, "Unauthorized"), // ← display message
 
         }
@@ -92,6 +112,18 @@ impl ResponseError for AppError {
                 log::error!("BadRequest: {}", e);
                 HttpResponse::InternalServerError().json(resp)
             }
+            AppError::Validation(errors) => {
+                let resp = ValidationErrorResponse {
+                    error: ErrorResponse {
+                        code: 422,
+                        error_type: "ValidationError",
+                        message: self.to_string(),
+                    },
+                    errors: errors.clone(),
+                };
+                log::warn!("{}", self);
+                HttpResponse::UnprocessableEntity().json(resp)
+            }
             AppError::Db(e) => {
                 let error_id = uuid::Uuid::new_v4();
                 log::error!("DB Error [{}]: {:?}", error_id, e);
@@ -140,6 +140,20 @@ async fn main() -> std::io::Result<()> {
     let member_domain = MemberDomain::new(pool.clone());
     let draft_domain = DraftDomain::new(pool.clone());
 
+    actix_web::rt::spawn(
+        ledger_domain
+            .clone()
+            .schedule_flow_templates(services::template_service::TEMPLATE_SCHEDULER_PERIOD),
+    );
+    actix_web::rt::spawn(
+        ledger_domain
+            .clone()
+            .schedule_ledger_checkpoints(
+                settings.ledger.checkpoint_secret.clone(),
+                services::integrity_service::CHECKPOINT_PERIOD,
+            ),
+    );
+
 
     //let admin_middleware = AdminMiddleware::new();
     // {
@@ -0,0 +1,205 @@
+use crate::{
+    schema::{commitment_fulfilments, commitments},
+    types::JsonField,
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Double, Integer, Text, Timestamp};
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = commitments)]
+pub struct Commitment {
+    pub id: String,
+    pub host_id: i32,
+    pub from_entity: String,
+    pub to_entity: String,
+    pub resource_type: String,
+    pub quantity_value: f32,
+    pub quantity_unit: String,
+    pub due_date: NaiveDateTime,
+    pub notes: Option<String>,
+    pub details: JsonField,
+    pub status: String,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = commitments)]
+pub struct NewCommitment {
+    pub id: String,
+    pub host_id: i32,
+    pub from_entity: String,
+    pub to_entity: String,
+    pub resource_type: String,
+    pub quantity_value: f32,
+    pub quantity_unit: String,
+    pub due_date: NaiveDateTime,
+    pub notes: Option<String>,
+    pub details: JsonField,
+    pub created_by: String,
+}
+
+/// The share of a commitment one flow covers, in the commitment's unit.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = commitment_fulfilments)]
+pub struct CommitmentFulfilment {
+    pub commitment_id: String,
+    pub flow_id: String,
+    pub quantity_value: f32,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+}
+
+pub fn create_commitment(
+    conn: &mut SqliteConnection,
+    new: &NewCommitment,
+) -> QueryResult<Commitment> {
+    diesel::insert_into(commitments::table)
+        .values(new)
+        .execute(conn)?;
+
+    get_commitment(conn, &new.id)
+}
+
+pub fn get_commitment(conn: &mut SqliteConnection, commitment_id: &str) -> QueryResult<Commitment> {
+    commitments::table
+        .find(commitment_id)
+        .select(Commitment::as_select())
+        .first(conn)
+}
+
+/// Commitments the entity owes or is owed, soonest due first.
+pub fn get_commitments_for_entity(
+    conn: &mut SqliteConnection,
+    host: i32,
+    entity_id: &str,
+) -> QueryResult<Vec<Commitment>> {
+    commitments::table
+        .filter(commitments::host_id.eq(host))
+        .filter(
+            commitments::from_entity
+                .eq(entity_id)
+                .or(commitments::to_entity.eq(entity_id)),
+        )
+        .order(commitments::due_date.asc())
+        .select(Commitment::as_select())
+        .load(conn)
+}
+
+pub fn set_status(
+    conn: &mut SqliteConnection,
+    commitment_id: &str,
+    status: &str,
+) -> QueryResult<usize> {
+    diesel::update(commitments::table.find(commitment_id))
+        .set(commitments::status.eq(status))
+        .execute(conn)
+}
+
+pub fn create_fulfilment(
+    conn: &mut SqliteConnection,
+    fulfilment: &CommitmentFulfilment,
+) -> QueryResult<usize> {
+    diesel::insert_into(commitment_fulfilments::table)
+        .values(fulfilment)
+        .execute(conn)
+}
+
+/// Removes every share drawn from a flow and returns the commitments
+/// they belonged to.
+pub fn delete_flow_fulfilments(conn: &mut SqliteConnection, flow_id: &str) -> QueryResult<Vec<String>> {
+    let commitment_ids = commitment_fulfilments::table
+        .filter(commitment_fulfilments::flow_id.eq(flow_id))
+        .select(commitment_fulfilments::commitment_id)
+        .load(conn)?;
+    diesel::delete(commitment_fulfilments::table.filter(commitment_fulfilments::flow_id.eq(flow_id)))
+        .execute(conn)?;
+    Ok(commitment_ids)
+}
+
+pub fn get_fulfilments(
+    conn: &mut SqliteConnection,
+    commitment_ids: &[String],
+) -> QueryResult<Vec<CommitmentFulfilment>> {
+    commitment_fulfilments::table
+        .filter(commitment_fulfilments::commitment_id.eq_any(commitment_ids))
+        .order(commitment_fulfilments::created_at.asc())
+        .select(CommitmentFulfilment::as_select())
+        .load(conn)
+}
+
+/// Everything already drawn from a flow, with the unit of the commitment
+/// each share was recorded in.
+pub fn get_flow_allocations(
+    conn: &mut SqliteConnection,
+    flow_id: &str,
+) -> QueryResult<Vec<(f32, String)>> {
+    commitment_fulfilments::table
+        .inner_join(commitments::table)
+        .filter(commitment_fulfilments::flow_id.eq(flow_id))
+        .select((commitment_fulfilments::quantity_value, commitments::quantity_unit))
+        .load(conn)
+}
+
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectCommitments {
+    #[diesel(sql_type = Text)]
+    pub project_id: String,
+    #[diesel(sql_type = Text)]
+    pub project_name: String,
+    #[diesel(sql_type = Text)]
+    pub resource_type: String,
+    #[diesel(sql_type = Text)]
+    pub unit: String,
+    #[diesel(sql_type = Integer)]
+    pub open_count: i32,
+    #[diesel(sql_type = Integer)]
+    pub overdue_count: i32,
+    #[diesel(sql_type = Double)]
+    pub committed: f64,
+    #[diesel(sql_type = Double)]
+    pub fulfilled: f64,
+    #[diesel(sql_type = Double)]
+    pub outstanding: f64,
+}
+
+/// Open commitments to each project on the host, per resource type and
+/// unit. Overdue means due before `now` and not yet fulfilled.
+pub fn get_outstanding_by_project(
+    conn: &mut SqliteConnection,
+    host: i32,
+    now: NaiveDateTime,
+) -> QueryResult<Vec<ProjectCommitments>> {
+    diesel::sql_query(
+        r#"
+        SELECT
+            c.to_entity AS project_id,
+            e.name AS project_name,
+            c.resource_type,
+            c.quantity_unit AS unit,
+            COUNT(*) AS open_count,
+            SUM(CASE WHEN c.due_date < ? THEN 1 ELSE 0 END) AS overdue_count,
+            SUM(c.quantity_value) AS committed,
+            SUM(IFNULL(f.fulfilled, 0.0)) AS fulfilled,
+            SUM(c.quantity_value - IFNULL(f.fulfilled, 0.0)) AS outstanding
+        FROM commitments c
+        JOIN entities e ON e.id = c.to_entity
+        LEFT JOIN (
+            SELECT commitment_id, SUM(quantity_value) AS fulfilled
+            FROM commitment_fulfilments
+            GROUP BY commitment_id
+        ) f ON f.commitment_id = c.id
+        WHERE c.host_id = ?
+          AND c.status = 'open'
+          AND e.entity_type IN ('project', 'team', 'organization')
+        GROUP BY c.to_entity, c.resource_type, c.quantity_unit
+        ORDER BY e.name, c.resource_type
+        "#,
+    )
+    .bind::<Timestamp, _>(now)
+    .bind::<Integer, _>(host)
+    .load::<ProjectCommitments>(conn)
+}
@@ -1,7 +1,7 @@
 
 use crate::{
     routes::drafts_api::{self, DraftQuery},
-    schema::drafts,
+    schema::{draft_comments, draft_revisions, draft_transitions, drafts},
     types::{DocType, DraftStatus, JsonField},
 };
 use diesel::prelude::*;
@@ -28,6 +28,9 @@ pub struct Draft {
     pub review_notes: Option<String>,
     pub details: Option<String>,
     pub host_id: i32,
+    pub published_path: Option<String>,
+    pub published_commit: Option<String>,
+    pub published_at: Option<chrono::NaiveDateTime>,
 }
 
 
@@ -43,7 +46,7 @@ impl Draft {
 }
 
 
-#[derive(AsChangeset, Insertable, Deserialize, Serialize)]
+#[derive(AsChangeset, Insertable, Deserialize, Serialize, Clone)]
 #[diesel(table_name = drafts)]
 pub struct NewDraft {
     pub doc_type: String,
@@ -208,83 +211,335 @@ pub fn delete_draft(conn: &mut SqliteConnection, in_draft_id: i32) -> QueryResul
 use chrono::NaiveDate;
 
 
-// Change status to submitted
-pub fn submit_draft(conn: &mut SqliteConnection, draft_id: i32) -> QueryResult<Draft> {
-    diesel::update(drafts::table.find(draft_id))
-        .set((
-            drafts::status.eq("submitted"),
-            drafts::submitted_at.eq(chrono::Utc::now().naive_utc()),
-        ))
-        .execute(conn)?;
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = draft_transitions)]
+pub struct DraftTransition {
+    pub id: i32,
+    pub draft_id: i32,
+    pub host_id: i32,
+    pub action: String,
+    pub from_status: DraftStatus,
+    pub to_status: DraftStatus,
+    pub actor_id: i32,
+    pub notes: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub commit_id: Option<String>,
+}
 
-    drafts::table.find(draft_id).first(conn)
+#[derive(Insertable)]
+#[diesel(table_name = draft_transitions)]
+pub struct NewDraftTransition {
+    pub draft_id: i32,
+    pub host_id: i32,
+    pub action: String,
+    pub from_status: DraftStatus,
+    pub to_status: DraftStatus,
+    pub actor_id: i32,
+    pub notes: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub commit_id: Option<String>,
 }
 
-// Request changes from the author
-pub fn request_changes(
+/// Moves a draft from `from_status` to `to_status` and records the
+/// transition. Submitting stamps `submitted_at`; review steps stamp the
+/// reviewer. Returns `NotFound` if the draft is no longer in `from_status`.
+pub fn transition_draft(
     conn: &mut SqliteConnection,
-    draft_id: i32,
-    reviewer_id: i32,
-    notes: &str,
+    new: &NewDraftTransition,
+) -> QueryResult<Draft> {
+    conn.transaction(|conn| {
+        let target = drafts::table
+            .find(new.draft_id)
+            .filter(drafts::status.eq(new.from_status));
+        let updated = if new.to_status == DraftStatus::Submitted {
+            diesel::update(target)
+                .set((
+                    drafts::status.eq(new.to_status),
+                    drafts::submitted_at.eq(Some(new.created_at)),
+                ))
+                .execute(conn)?
+        } else {
+            diesel::update(target)
+                .set((
+                    drafts::status.eq(new.to_status),
+                    drafts::reviewed_by.eq(Some(new.actor_id)),
+                    drafts::reviewed_at.eq(Some(new.created_at)),
+                    drafts::review_notes.eq(&new.notes),
+                ))
+                .execute(conn)?
+        };
+        if updated == 0 {
+            return Err(diesel::result::Error::NotFound);
+        }
+
+        diesel::insert_into(draft_transitions::table)
+            .values(new)
+            .execute(conn)?;
+
+        drafts::table.find(new.draft_id).first(conn)
+    })
+}
+
+/// Records where a draft is published and the commit that did it, or
+/// clears both with `None`.
+pub fn set_draft_publication(
+    conn: &mut SqliteConnection,
+    in_draft_id: i32,
+    publication: Option<(&str, &str, chrono::NaiveDateTime)>,
 ) -> QueryResult<Draft> {
-    diesel::update(drafts::table.find(draft_id))
+    diesel::update(drafts::table.find(in_draft_id))
         .set((
-            drafts::status.eq("changes_requested"),
-            drafts::reviewed_by.eq(Some(reviewer_id)),
-            drafts::review_notes.eq(Some(notes.to_string())),
-            drafts::reviewed_at.eq(Some(chrono::Utc::now().naive_utc())),
+            drafts::published_path.eq(publication.map(|(path, _, _)| path)),
+            drafts::published_commit.eq(publication.map(|(_, commit, _)| commit)),
+            drafts::published_at.eq(publication.map(|(_, _, at)| at)),
         ))
         .execute(conn)?;
 
-    drafts::table.find(draft_id).first(conn)
+    drafts::table.find(in_draft_id).first(conn)
+}
+
+/// Whether another of the host's drafts is published to `path`.
+pub fn published_path_taken(
+    conn: &mut SqliteConnection,
+    host: i32,
+    path: &str,
+    in_draft_id: i32,
+) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        drafts::table
+            .filter(drafts::host_id.eq(host))
+            .filter(drafts::published_path.eq(path))
+            .filter(drafts::id.ne(in_draft_id)),
+    ))
+    .get_result(conn)
 }
 
-// Approve a draft
-pub fn approve_draft(
+/// A draft's transitions, oldest first.
+pub fn get_draft_transitions(
     conn: &mut SqliteConnection,
+    in_draft_id: i32,
+) -> QueryResult<Vec<DraftTransition>> {
+    draft_transitions::table
+        .filter(draft_transitions::draft_id.eq(in_draft_id))
+        .order(draft_transitions::id.asc())
+        .select(DraftTransition::as_select())
+        .load(conn)
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = draft_revisions)]
+pub struct DraftRevision {
+    pub id: i32,
+    pub draft_id: i32,
+    pub host_id: i32,
+    pub revision: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub tags: Option<String>,
+    pub author: Option<String>,
+    pub meta: Option<JsonField>,
+    pub body_md: String,
+    pub restored_from: Option<i32>,
+    pub created_by: i32,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// A revision without its content, for listing.
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = draft_revisions)]
+pub struct DraftRevisionSummary {
+    pub revision: i32,
+    pub title: String,
+    pub restored_from: Option<i32>,
+    pub created_by: i32,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = draft_revisions)]
+struct NewDraftRevision<'a> {
     draft_id: i32,
-    reviewer_id: i32,
+    host_id: i32,
+    revision: i32,
+    title: &'a str,
+    description: Option<&'a str>,
+    tags: Option<&'a str>,
+    author: Option<&'a str>,
+    meta: Option<&'a JsonField>,
+    body_md: &'a str,
+    restored_from: Option<i32>,
+    created_by: i32,
+    created_at: chrono::NaiveDateTime,
+}
+
+/// Snapshots the draft's current content as its next revision.
+pub fn create_draft_revision(
+    conn: &mut SqliteConnection,
+    draft: &Draft,
+    created_by: i32,
+    restored_from: Option<i32>,
+) -> QueryResult<DraftRevision> {
+    conn.transaction(|conn| {
+        let latest: Option<i32> = draft_revisions::table
+            .filter(draft_revisions::draft_id.eq(draft.id))
+            .select(diesel::dsl::max(draft_revisions::revision))
+            .first(conn)?;
+        let new = NewDraftRevision {
+            draft_id: draft.id,
+            host_id: draft.host_id,
+            revision: latest.unwrap_or(0) + 1,
+            title: &draft.title,
+            description: draft.description.as_deref(),
+            tags: draft.tags.as_deref(),
+            author: draft.author.as_deref(),
+            meta: draft.meta.as_ref(),
+            body_md: &draft.body_md,
+            restored_from,
+            created_by,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+        diesel::insert_into(draft_revisions::table)
+            .values(&new)
+            .execute(conn)?;
+
+        get_draft_revision(conn, draft.id, new.revision)
+    })
+}
+
+/// A draft's revisions, newest first.
+pub fn get_draft_revisions(
+    conn: &mut SqliteConnection,
+    in_draft_id: i32,
+) -> QueryResult<Vec<DraftRevisionSummary>> {
+    draft_revisions::table
+        .filter(draft_revisions::draft_id.eq(in_draft_id))
+        .order(draft_revisions::revision.desc())
+        .select(DraftRevisionSummary::as_select())
+        .load(conn)
+}
+
+pub fn get_draft_revision(
+    conn: &mut SqliteConnection,
+    in_draft_id: i32,
+    revision: i32,
+) -> QueryResult<DraftRevision> {
+    draft_revisions::table
+        .filter(draft_revisions::draft_id.eq(in_draft_id))
+        .filter(draft_revisions::revision.eq(revision))
+        .select(DraftRevision::as_select())
+        .first(conn)
+}
+
+pub fn get_latest_draft_revision(
+    conn: &mut SqliteConnection,
+    in_draft_id: i32,
+) -> QueryResult<Option<DraftRevision>> {
+    draft_revisions::table
+        .filter(draft_revisions::draft_id.eq(in_draft_id))
+        .order(draft_revisions::revision.desc())
+        .select(DraftRevision::as_select())
+        .first(conn)
+        .optional()
+}
+
+/// Puts a revision's content back on the draft.
+pub fn restore_draft_content(
+    conn: &mut SqliteConnection,
+    revision: &DraftRevision,
 ) -> QueryResult<Draft> {
-    diesel::update(drafts::table.find(draft_id))
+    diesel::update(drafts::table.find(revision.draft_id))
         .set((
-            drafts::status.eq("approved"),
-            drafts::reviewed_by.eq(Some(reviewer_id)),
-            drafts::reviewed_at.eq(Some(chrono::Utc::now().naive_utc())),
+            drafts::title.eq(&revision.title),
+            drafts::description.eq(&revision.description),
+            drafts::tags.eq(&revision.tags),
+            drafts::author.eq(&revision.author),
+            drafts::meta.eq(&revision.meta),
+            drafts::body_md.eq(&revision.body_md),
         ))
         .execute(conn)?;
 
-    drafts::table.find(draft_id).first(conn)
+    drafts::table.find(revision.draft_id).first(conn)
 }
 
-pub fn approve_drafts(
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = draft_comments)]
+pub struct DraftComment {
+    pub id: i32,
+    pub draft_id: i32,
+    pub host_id: i32,
+    pub parent_id: Option<i32>,
+    pub revision: Option<i32>,
+    pub line_start: Option<i32>,
+    pub line_end: Option<i32>,
+    pub field: Option<String>,
+    pub body: String,
+    pub author_id: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub resolved_by: Option<i32>,
+    pub resolved_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = draft_comments)]
+pub struct NewDraftComment {
+    pub draft_id: i32,
+    pub host_id: i32,
+    pub parent_id: Option<i32>,
+    pub revision: Option<i32>,
+    pub line_start: Option<i32>,
+    pub line_end: Option<i32>,
+    pub field: Option<String>,
+    pub body: String,
+    pub author_id: i32,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+pub fn create_draft_comment(
     conn: &mut SqliteConnection,
-    ids: &[i32],
-    in_reviewed_by: i32,
-) -> QueryResult<usize> {
-    use crate::schema::drafts::dsl::*;
+    new: &NewDraftComment,
+) -> QueryResult<DraftComment> {
+    diesel::insert_into(draft_comments::table)
+        .values(new)
+        .execute(conn)?;
 
-    diesel::update(drafts.filter(id.eq_any(ids)))
-        .set((
-            status.eq("approved"),
-            reviewed_by.eq(Some(in_reviewed_by)),
-            reviewed_at.eq(Some(chrono::Utc::now().naive_utc())),
-        ))
-        .execute(conn)
+    draft_comments::table
+        .filter(draft_comments::draft_id.eq(new.draft_id))
+        .order(draft_comments::id.desc())
+        .select(DraftComment::as_select())
+        .first(conn)
+}
+
+pub fn get_draft_comment(conn: &mut SqliteConnection, comment_id: i32) -> QueryResult<DraftComment> {
+    draft_comments::table
+        .find(comment_id)
+        .select(DraftComment::as_select())
+        .first(conn)
 }
 
-// Deploy a draft
-pub fn deploy_draft(
+/// A draft's comments and replies, oldest first.
+pub fn get_draft_comments(
     conn: &mut SqliteConnection,
-    draft_id: i32,
-    reviewer_id: i32,
-) -> QueryResult<Draft> {
-    diesel::update(drafts::table.find(draft_id))
+    in_draft_id: i32,
+) -> QueryResult<Vec<DraftComment>> {
+    draft_comments::table
+        .filter(draft_comments::draft_id.eq(in_draft_id))
+        .order(draft_comments::id.asc())
+        .select(DraftComment::as_select())
+        .load(conn)
+}
+
+/// Marks a comment resolved by `resolved`, or unresolved with `None`.
+pub fn set_draft_comment_resolved(
+    conn: &mut SqliteConnection,
+    comment_id: i32,
+    resolved: Option<(i32, chrono::NaiveDateTime)>,
+) -> QueryResult<DraftComment> {
+    diesel::update(draft_comments::table.find(comment_id))
         .set((
-            drafts::status.eq(DraftStatus::Deployed),
-            drafts::reviewed_by.eq(Some(reviewer_id)),
-            drafts::reviewed_at.eq(Some(chrono::Utc::now().naive_utc())),
+            draft_comments::resolved_by.eq(resolved.map(|(by, _)| by)),
+            draft_comments::resolved_at.eq(resolved.map(|(_, at)| at)),
         ))
         .execute(conn)?;
 
-    drafts::table.find(draft_id).first(conn)
+    get_draft_comment(conn, comment_id)
 }
@@ -14,10 +14,15 @@ pub struct Entity {
     pub created_by: String,
     pub created_at: NaiveDateTime,
     pub details: JsonField,
-    
+    pub archived_at: Option<NaiveDateTime>,
+    pub visibility: String,
+    /// GeoJSON Point or Polygon.
+    pub geometry: Option<JsonField>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
 }
 
-#[derive(Insertable, AsChangeset, Serialize, Deserialize)]
+#[derive(Insertable, AsChangeset, Serialize, Deserialize, Clone)]
 #[diesel(table_name = entities)]
 pub struct NewEntity {
     pub id: String,
@@ -30,7 +35,21 @@ pub struct NewEntity {
     
 }
 
-#[derive(Debug, Clone, Selectable, Queryable, Identifiable)]
+/// Partial entity update; `None` fields are left unchanged.
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = entities)]
+pub struct EntityChanges {
+    pub name: Option<String>,
+    pub entity_type: Option<String>,
+    pub details: Option<JsonField>,
+    pub archived_at: Option<Option<NaiveDateTime>>,
+    pub visibility: Option<String>,
+    pub geometry: Option<Option<JsonField>>,
+    pub latitude: Option<Option<f64>>,
+    pub longitude: Option<Option<f64>>,
+}
+
+#[derive(Debug, Clone, Selectable, Queryable, Identifiable, Serialize)]
 #[diesel(table_name = entity_aliases)]
 pub struct EntityAlias {
     pub id: String,
@@ -38,6 +57,7 @@ pub struct EntityAlias {
     pub alias: String,
     pub created_by: String,
     pub created_at: NaiveDateTime,
+    pub normalized: String,
 }
 
 #[derive(Insertable, AsChangeset )]
@@ -47,10 +67,11 @@ pub struct NewEntityAlias<'a> {
     pub entity_id: &'a str,
     pub alias: &'a str,
     pub created_by: &'a str,
+    pub normalized: &'a str,
 }
 
 
-#[derive(Debug, Clone, Selectable, Queryable, Identifiable)]
+#[derive(Debug, Clone, Selectable, Queryable, Identifiable, Serialize)]
 #[diesel(table_name = entity_users)]
 pub struct EntityUser {
     pub id: i32,
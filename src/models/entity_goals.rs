@@ -0,0 +1,88 @@
+use crate::schema::entity_goals;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = entity_goals)]
+pub struct EntityGoal {
+    pub id: i32,
+    pub host_id: i32,
+    pub entity_id: String,
+    pub label: String,
+    pub resource_type: String,
+    pub target_value: f32,
+    pub unit: String,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Serialize, Deserialize, Clone)]
+#[diesel(table_name = entity_goals)]
+pub struct NewEntityGoal {
+    pub host_id: i32,
+    pub entity_id: String,
+    pub label: String,
+    pub resource_type: String,
+    pub target_value: f32,
+    pub unit: String,
+    pub period_start: NaiveDateTime,
+    pub period_end: NaiveDateTime,
+    pub created_by: String,
+}
+
+pub fn create_goal(conn: &mut SqliteConnection, new: &NewEntityGoal) -> QueryResult<EntityGoal> {
+    diesel::insert_into(entity_goals::table)
+        .values(new)
+        .execute(conn)?;
+
+    entity_goals::table
+        .filter(entity_goals::entity_id.eq(&new.entity_id))
+        .order(entity_goals::id.desc())
+        .select(EntityGoal::as_select())
+        .first(conn)
+}
+
+pub fn get_goal(conn: &mut SqliteConnection, goal_id: i32) -> QueryResult<EntityGoal> {
+    entity_goals::table
+        .find(goal_id)
+        .select(EntityGoal::as_select())
+        .first(conn)
+}
+
+/// The host's goals, optionally for one entity, latest period first.
+pub fn get_goals(
+    conn: &mut SqliteConnection,
+    host: i32,
+    entity_id: Option<&str>,
+) -> QueryResult<Vec<EntityGoal>> {
+    let mut query = entity_goals::table
+        .filter(entity_goals::host_id.eq(host))
+        .into_boxed();
+    if let Some(entity_id) = entity_id {
+        query = query.filter(entity_goals::entity_id.eq(entity_id.to_string()));
+    }
+    query
+        .order((entity_goals::period_end.desc(), entity_goals::id.asc()))
+        .select(EntityGoal::as_select())
+        .load(conn)
+}
+
+pub fn update_goal(
+    conn: &mut SqliteConnection,
+    goal_id: i32,
+    updated: &NewEntityGoal,
+) -> QueryResult<EntityGoal> {
+    diesel::update(entity_goals::table.find(goal_id))
+        .set(updated)
+        .execute(conn)?;
+
+    get_goal(conn, goal_id)
+}
+
+pub fn delete_goal(conn: &mut SqliteConnection, goal_id: i32) -> QueryResult<usize> {
+    diesel::delete(entity_goals::table.find(goal_id)).execute(conn)
+}
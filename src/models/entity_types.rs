@@ -0,0 +1,96 @@
+use crate::{
+    schema::entity_types,
+    types::{FieldSchema, JsonField},
+};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = entity_types)]
+pub struct EntityType {
+    pub id: i32,
+    pub host_id: i32,
+    pub key: String,
+    pub label: String,
+    /// A JSON array of `FieldSchema`.
+    pub fields: JsonField,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Serialize, Deserialize)]
+#[diesel(table_name = entity_types)]
+pub struct NewEntityType {
+    pub host_id: i32,
+    pub key: String,
+    pub label: String,
+    pub fields: JsonField,
+}
+
+impl EntityType {
+    /// The detail fields; a malformed list reads as none.
+    pub fn field_list(&self) -> Vec<FieldSchema> {
+        serde_json::from_value(self.fields.0.clone()).unwrap_or_default()
+    }
+}
+
+pub fn create_entity_type(
+    conn: &mut SqliteConnection,
+    new: &NewEntityType,
+) -> QueryResult<EntityType> {
+    diesel::insert_into(entity_types::table)
+        .values(new)
+        .execute(conn)?;
+
+    entity_types::table
+        .filter(entity_types::host_id.eq(new.host_id))
+        .filter(entity_types::key.eq(&new.key))
+        .select(EntityType::as_select())
+        .first(conn)
+}
+
+pub fn get_entity_types(
+    conn: &mut SqliteConnection,
+    host: i32,
+) -> QueryResult<Vec<EntityType>> {
+    entity_types::table
+        .filter(entity_types::host_id.eq(host))
+        .order(entity_types::key.asc())
+        .select(EntityType::as_select())
+        .load(conn)
+}
+
+pub fn get_entity_type(
+    conn: &mut SqliteConnection,
+    host: i32,
+    type_key: &str,
+) -> QueryResult<EntityType> {
+    entity_types::table
+        .filter(entity_types::host_id.eq(host))
+        .filter(entity_types::key.eq(type_key))
+        .select(EntityType::as_select())
+        .first(conn)
+}
+
+pub fn update_entity_type(
+    conn: &mut SqliteConnection,
+    type_id: i32,
+    updated: &NewEntityType,
+) -> QueryResult<EntityType> {
+    diesel::update(entity_types::table.find(type_id))
+        .set(updated)
+        .execute(conn)?;
+
+    entity_types::table
+        .find(type_id)
+        .select(EntityType::as_select())
+        .first(conn)
+}
+
+pub fn delete_entity_type(
+    conn: &mut SqliteConnection,
+    type_id: i32,
+) -> QueryResult<usize> {
+    diesel::delete(entity_types::table.find(type_id)).execute(conn)
+}
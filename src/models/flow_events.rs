@@ -1,4 +1,4 @@
-use crate::{schema::flow_actions, schema::flow_events, types::JsonField};
+use crate::{models::ledger_chain, schema::flow_actions, schema::flow_events, types::JsonField};
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
@@ -19,9 +19,16 @@ pub struct FlowEvent {
     pub notes: Option<String>,
     pub details: JsonField,
     pub created_by: String,
+    pub visibility: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Position in the host's hash chain; `None` until linked.
+    pub chain_seq: Option<i32>,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
 }
 
-#[derive(Insertable, Serialize, Deserialize)]
+#[derive(Insertable, Serialize, Deserialize, Clone)]
 #[diesel(table_name = flow_events)]
 pub struct NewFlowEvent {
     pub id: String,
@@ -47,6 +54,11 @@ pub struct FlowAction {
     pub actor_entity: String,
     pub timestamp: NaiveDateTime,
     pub details: String,
+    /// Position in the host's hash chain, shared with flows; `None`
+    /// until linked.
+    pub chain_seq: Option<i32>,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
 }
 
 #[derive(Insertable, AsChangeset, Serialize, Deserialize)]
@@ -66,6 +78,7 @@ pub fn create_flow_event(
     diesel::insert_into(flow_events::table)
         .values(new)
         .execute(conn)?;
+    ledger_chain::link_pending(conn, new.host_id)?;
 
     flow_events::table.find(&new.id).first(conn)
 }
@@ -117,6 +130,11 @@ pub fn create_flow_action(
             details.eq(new.details),
         ))
         .execute(conn)?;
+    let host: i32 = flow_events::table
+        .find(new.flow_id)
+        .select(flow_events::host_id)
+        .first(conn)?;
+    ledger_chain::link_pending(conn, host)?;
 
     flow_actions
         .filter(id.eq(action_id))
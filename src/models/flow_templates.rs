@@ -0,0 +1,129 @@
+use crate::{schema::flow_templates, types::JsonField};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = flow_templates)]
+pub struct FlowTemplate {
+    pub id: String,
+    pub host_id: i32,
+    pub from_entity: String,
+    pub to_entity: String,
+    pub resource_type: String,
+    pub quantity_value: f32,
+    pub quantity_unit: String,
+    pub notes: Option<String>,
+    pub details: JsonField,
+    pub recurrence: String,
+    pub every: i32,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: Option<NaiveDateTime>,
+    pub next_run: NaiveDateTime,
+    pub run_count: i32,
+    pub last_run_at: Option<NaiveDateTime>,
+    pub paused: bool,
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = flow_templates)]
+pub struct NewFlowTemplate {
+    pub id: String,
+    pub host_id: i32,
+    pub from_entity: String,
+    pub to_entity: String,
+    pub resource_type: String,
+    pub quantity_value: f32,
+    pub quantity_unit: String,
+    pub notes: Option<String>,
+    pub details: JsonField,
+    pub recurrence: String,
+    pub every: i32,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: Option<NaiveDateTime>,
+    pub next_run: NaiveDateTime,
+    pub created_by: String,
+}
+
+pub fn create_flow_template(
+    conn: &mut SqliteConnection,
+    new: &NewFlowTemplate,
+) -> QueryResult<FlowTemplate> {
+    diesel::insert_into(flow_templates::table)
+        .values(new)
+        .execute(conn)?;
+
+    get_flow_template(conn, &new.id)
+}
+
+pub fn get_flow_template(
+    conn: &mut SqliteConnection,
+    template_id: &str,
+) -> QueryResult<FlowTemplate> {
+    flow_templates::table
+        .find(template_id)
+        .select(FlowTemplate::as_select())
+        .first(conn)
+}
+
+/// Templates where the entity gives or receives.
+pub fn get_templates_for_entity(
+    conn: &mut SqliteConnection,
+    entity_id: &str,
+) -> QueryResult<Vec<FlowTemplate>> {
+    flow_templates::table
+        .filter(
+            flow_templates::from_entity
+                .eq(entity_id)
+                .or(flow_templates::to_entity.eq(entity_id)),
+        )
+        .order(flow_templates::created_at.asc())
+        .select(FlowTemplate::as_select())
+        .load(conn)
+}
+
+/// Unpaused templates with an occurrence at or before `now`, on any host.
+pub fn get_due_templates(
+    conn: &mut SqliteConnection,
+    now: NaiveDateTime,
+) -> QueryResult<Vec<FlowTemplate>> {
+    flow_templates::table
+        .filter(flow_templates::paused.eq(false))
+        .filter(flow_templates::next_run.le(now))
+        .order(flow_templates::next_run.asc())
+        .select(FlowTemplate::as_select())
+        .load(conn)
+}
+
+pub fn set_paused(
+    conn: &mut SqliteConnection,
+    template_id: &str,
+    paused: bool,
+) -> QueryResult<usize> {
+    diesel::update(flow_templates::table.find(template_id))
+        .set(flow_templates::paused.eq(paused))
+        .execute(conn)
+}
+
+/// Moves the template on to its next occurrence. Templates that have run
+/// past `ends_at` are paused rather than deleted so they stay listed.
+pub fn advance(
+    conn: &mut SqliteConnection,
+    template_id: &str,
+    next_run: NaiveDateTime,
+    run_count: i32,
+    last_run_at: Option<NaiveDateTime>,
+    finished: bool,
+) -> QueryResult<usize> {
+    diesel::update(flow_templates::table.find(template_id))
+        .set((
+            flow_templates::next_run.eq(next_run),
+            flow_templates::run_count.eq(run_count),
+            flow_templates::last_run_at.eq(last_run_at),
+            flow_templates::paused.eq(finished),
+        ))
+        .execute(conn)
+}
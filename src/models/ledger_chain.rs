@@ -0,0 +1,259 @@
+use crate::{
+    models::flow_events::{FlowAction, FlowEvent},
+    schema::{flow_actions, flow_events, ledger_checkpoints},
+};
+use chrono::NaiveDateTime;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+/// `prev_hash` of the first link in every host's chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const HASH_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = ledger_checkpoints)]
+pub struct LedgerCheckpoint {
+    pub id: i32,
+    pub host_id: i32,
+    pub chain_seq: i32,
+    pub head_hash: String,
+    pub signature: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = ledger_checkpoints)]
+pub struct NewLedgerCheckpoint {
+    pub host_id: i32,
+    pub chain_seq: i32,
+    pub head_hash: String,
+    pub signature: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// SHA-256 over a flow's recorded content, its chain position and the
+/// previous link. Visibility and location can change later, so they are
+/// left out.
+pub fn link_hash(prev_hash: &str, chain_seq: i32, flow: &FlowEvent) -> String {
+    let content = json!([
+        prev_hash,
+        chain_seq,
+        flow.id,
+        flow.timestamp.format(HASH_TIME_FORMAT).to_string(),
+        flow.recorded_at.format(HASH_TIME_FORMAT).to_string(),
+        flow.host_id,
+        flow.from_entity,
+        flow.to_entity,
+        flow.resource_type,
+        flow.quantity_value,
+        flow.quantity_unit,
+        flow.notes,
+        flow.details.0,
+        flow.created_by,
+    ]);
+    let mut hasher = Sha256::new();
+    hasher.update(content.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// SHA-256 over an action on a flow, its chain position and the
+/// previous link.
+pub fn action_link_hash(prev_hash: &str, chain_seq: i32, action: &FlowAction) -> String {
+    let content = json!([
+        prev_hash,
+        chain_seq,
+        action.id,
+        action.flow_id,
+        action.action_type,
+        action.actor_entity,
+        action.timestamp.format(HASH_TIME_FORMAT).to_string(),
+        action.details,
+    ]);
+    let mut hasher = Sha256::new();
+    hasher.update(content.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// A link in a host's chain: a flow, or an action taken on one.
+#[derive(Debug, Clone)]
+pub enum ChainEntry {
+    Flow(FlowEvent),
+    Action(FlowAction),
+}
+
+impl ChainEntry {
+    pub fn chain_seq(&self) -> Option<i32> {
+        match self {
+            ChainEntry::Flow(flow) => flow.chain_seq,
+            ChainEntry::Action(action) => action.chain_seq,
+        }
+    }
+
+    pub fn prev_hash(&self) -> Option<&str> {
+        match self {
+            ChainEntry::Flow(flow) => flow.prev_hash.as_deref(),
+            ChainEntry::Action(action) => action.prev_hash.as_deref(),
+        }
+    }
+
+    pub fn hash(&self) -> Option<&str> {
+        match self {
+            ChainEntry::Flow(flow) => flow.hash.as_deref(),
+            ChainEntry::Action(action) => action.hash.as_deref(),
+        }
+    }
+
+    pub fn flow_id(&self) -> &str {
+        match self {
+            ChainEntry::Flow(flow) => &flow.id,
+            ChainEntry::Action(action) => &action.flow_id,
+        }
+    }
+}
+
+/// Actions on the host's flows.
+fn host_actions(host: i32) -> flow_actions::BoxedQuery<'static, diesel::sqlite::Sqlite> {
+    flow_actions::table
+        .filter(
+            flow_actions::flow_id.eq_any(
+                flow_events::table
+                    .filter(flow_events::host_id.eq(host))
+                    .select(flow_events::id),
+            ),
+        )
+        .into_boxed()
+}
+
+/// Links the host's unlinked flows, then its unlinked actions, onto its
+/// chain in insertion order. Called after every insert, so normally
+/// there is just the new one.
+pub fn link_pending(conn: &mut SqliteConnection, host: i32) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let flow_head: Option<(Option<i32>, Option<String>)> = flow_events::table
+            .filter(flow_events::host_id.eq(host))
+            .filter(flow_events::hash.is_not_null())
+            .order(flow_events::chain_seq.desc())
+            .select((flow_events::chain_seq, flow_events::hash))
+            .first(conn)
+            .optional()?;
+        let action_head: Option<(Option<i32>, Option<String>)> = host_actions(host)
+            .filter(flow_actions::hash.is_not_null())
+            .order(flow_actions::chain_seq.desc())
+            .select((flow_actions::chain_seq, flow_actions::hash))
+            .first(conn)
+            .optional()?;
+        let (mut seq, mut prev) = match flow_head.into_iter().chain(action_head).max() {
+            Some((Some(seq), Some(hash))) => (seq, hash),
+            _ => (0, GENESIS_HASH.to_string()),
+        };
+
+        let pending: Vec<FlowEvent> = flow_events::table
+            .filter(flow_events::host_id.eq(host))
+            .filter(flow_events::hash.is_null())
+            .order(sql::<BigInt>("flow_events.rowid"))
+            .select(FlowEvent::as_select())
+            .load(conn)?;
+        for flow in &pending {
+            seq += 1;
+            let hash = link_hash(&prev, seq, flow);
+            diesel::update(flow_events::table.find(&flow.id))
+                .set((
+                    flow_events::chain_seq.eq(seq),
+                    flow_events::prev_hash.eq(&prev),
+                    flow_events::hash.eq(&hash),
+                ))
+                .execute(conn)?;
+            prev = hash;
+        }
+
+        let pending_actions: Vec<FlowAction> = host_actions(host)
+            .filter(flow_actions::hash.is_null())
+            .order(sql::<BigInt>("flow_actions.rowid"))
+            .select(FlowAction::as_select())
+            .load(conn)?;
+        for action in &pending_actions {
+            seq += 1;
+            let hash = action_link_hash(&prev, seq, action);
+            diesel::update(flow_actions::table.find(&action.id))
+                .set((
+                    flow_actions::chain_seq.eq(seq),
+                    flow_actions::prev_hash.eq(&prev),
+                    flow_actions::hash.eq(&hash),
+                ))
+                .execute(conn)?;
+            prev = hash;
+        }
+        Ok(pending.len() + pending_actions.len())
+    })
+}
+
+/// The host's linked flows and actions in chain order.
+pub fn get_chain(conn: &mut SqliteConnection, host: i32) -> QueryResult<Vec<ChainEntry>> {
+    let flows: Vec<FlowEvent> = flow_events::table
+        .filter(flow_events::host_id.eq(host))
+        .filter(flow_events::hash.is_not_null())
+        .select(FlowEvent::as_select())
+        .load(conn)?;
+    let actions: Vec<FlowAction> = host_actions(host)
+        .filter(flow_actions::hash.is_not_null())
+        .select(FlowAction::as_select())
+        .load(conn)?;
+    let mut chain: Vec<ChainEntry> = flows
+        .into_iter()
+        .map(ChainEntry::Flow)
+        .chain(actions.into_iter().map(ChainEntry::Action))
+        .collect();
+    chain.sort_by_key(|entry| entry.chain_seq());
+    Ok(chain)
+}
+
+pub fn count_unlinked(conn: &mut SqliteConnection, host: i32) -> QueryResult<i64> {
+    let flows: i64 = flow_events::table
+        .filter(flow_events::host_id.eq(host))
+        .filter(flow_events::hash.is_null())
+        .count()
+        .get_result(conn)?;
+    let actions: i64 = host_actions(host)
+        .filter(flow_actions::hash.is_null())
+        .count()
+        .get_result(conn)?;
+    Ok(flows + actions)
+}
+
+/// Hosts with anything on their ledger.
+pub fn get_ledger_hosts(conn: &mut SqliteConnection) -> QueryResult<Vec<i32>> {
+    flow_events::table
+        .select(flow_events::host_id)
+        .distinct()
+        .load(conn)
+}
+
+pub fn create_checkpoint(
+    conn: &mut SqliteConnection,
+    new: &NewLedgerCheckpoint,
+) -> QueryResult<LedgerCheckpoint> {
+    diesel::insert_into(ledger_checkpoints::table)
+        .values(new)
+        .execute(conn)?;
+
+    ledger_checkpoints::table
+        .filter(ledger_checkpoints::host_id.eq(new.host_id))
+        .order(ledger_checkpoints::id.desc())
+        .select(LedgerCheckpoint::as_select())
+        .first(conn)
+}
+
+/// The host's checkpoints, oldest first.
+pub fn get_checkpoints(conn: &mut SqliteConnection, host: i32) -> QueryResult<Vec<LedgerCheckpoint>> {
+    ledger_checkpoints::table
+        .filter(ledger_checkpoints::host_id.eq(host))
+        .order(ledger_checkpoints::id.asc())
+        .select(LedgerCheckpoint::as_select())
+        .load(conn)
+}
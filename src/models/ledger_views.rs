@@ -1,72 +1,708 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+use diesel::dsl::sql;
 use diesel::prelude::*;
+use diesel::sql_types::{Bool, Double, Integer, Nullable, Text, Timestamp};
 use diesel::sqlite::SqliteConnection;
 use serde::{Deserialize, Serialize};
 
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+use crate::schema::flow_events;
+use crate::types::flow_query::{FLOW_LATITUDE, FLOW_LONGITUDE, FlowQuery, TimeBucket};
+
+// Every query here is scoped to a single host and an explicit
+// [since, until] window. Nothing should aggregate across hosts.
+
+/// Excludes reversed flows and the compensating flows that reversed them,
+/// so a correction never shows up as fresh activity.
+const NOT_REVERSED: &str = "flow_events.id NOT IN (SELECT flow_id FROM flow_actions WHERE action_type = 'reversal')
+          AND json_extract(flow_events.details, '$.reverses') IS NULL";
+
+/// Looks up each flow's resource type in the host registry as `r`.
+const REGISTRY: &str = "LEFT JOIN resource_types r
+          ON r.host_id = flow_events.host_id AND r.key = flow_events.resource_type";
+
+/// Labor and money come from the registry flags; hosts without a registry
+/// fall back to the original `labor_time` and `Dollars` keys.
+const IS_LABOR: &str = "(r.is_labor = 1 OR (r.id IS NULL AND flow_events.resource_type = 'labor_time'))";
+const IS_MONETARY: &str = "(r.is_monetary = 1 OR (r.id IS NULL AND flow_events.resource_type = 'Dollars'))";
+
+/// Quantity converted into the resource type's default unit.
+const NORMALIZED_QTY: &str =
+    "flow_events.quantity_value * IFNULL(json_extract(r.units, '$.\"' || flow_events.quantity_unit || '\"'), 1.0)";
+
+/// `NORMALIZED_QTY` and the default unit as registry subqueries, for
+/// queries built on `FlowQuery::apply` that cannot add the `r` join.
+const REGISTRY_UNIT: &str = "IFNULL((SELECT r.default_unit FROM resource_types r
+          WHERE r.host_id = flow_events.host_id AND r.key = flow_events.resource_type), flow_events.quantity_unit)";
+const REGISTRY_QTY: &str = "flow_events.quantity_value * IFNULL((SELECT json_extract(r.units, '$.\"' || flow_events.quantity_unit || '\"')
+          FROM resource_types r
+          WHERE r.host_id = flow_events.host_id AND r.key = flow_events.resource_type), 1.0)";
+
+/// Keeps only confirmed flows: the latest approval action must be a
+/// confirmation, or there must be none (flows recorded without a proposal).
+pub(crate) const CONFIRMED: &str = "IFNULL((
+            SELECT a.action_type FROM flow_actions a
+            WHERE a.flow_id = flow_events.id
+              AND a.action_type IN ('proposal', 'confirmation', 'dispute', 'rejection')
+            ORDER BY a.timestamp DESC, a.rowid DESC
+            LIMIT 1
+          ), 'confirmation') = 'confirmation'";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VitalSigns {
+    pub host_id: i32,
+    pub since: NaiveDateTime,
+    pub until: NaiveDateTime,
     pub dollars_available: f64,
-    pub active_contributors_30d: i32,
-    pub total_hours_30d: f64,
-    pub active_projects_30d: i32,
+    pub active_contributors: i32,
+    pub total_hours: f64,
+    pub active_projects: i32,
 }
 
-// API function to get the current vital signs
-pub fn get_vital_signs(conn: &mut SqliteConnection) -> QueryResult<VitalSigns> {
-    diesel::sql_query("SELECT * FROM v_regen_vital_signs")
-        .get_result::<VitalSigns>(conn)
+// API function to get the vital signs for a host and window
+pub fn get_vital_signs(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> QueryResult<VitalSigns> {
+    let dollars = get_dollars_available(conn, host, until)?;
+    let contributors = get_active_contributors(conn, host, since, until)?;
+    let hours = get_total_hours(conn, host, since, until)?;
+    let projects = get_active_projects(conn, host, since, until)?;
+
+    Ok(VitalSigns {
+        host_id: host,
+        since,
+        until,
+        dollars_available: dollars.dollars_available,
+        active_contributors: contributors.active_contributors,
+        total_hours: hours.total_hours,
+        active_projects: projects.active_projects,
+    })
 }
 
-#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EntityBalance {
     pub entity_id: String,
     pub resource_type: String,
+    /// The resource type's default unit, or the flows' own unit when the
+    /// host has no registry entry for it.
+    pub unit: String,
     pub balance: f64,
 }
 
-pub fn get_entity_balances(conn: &mut SqliteConnection) -> QueryResult<Vec<EntityBalance>> {
-    diesel::sql_query("SELECT * FROM v_entity_balances")
-        .load::<EntityBalance>(conn)
+/// A confirmed, unreversed flow with its quantity in the default unit.
+#[derive(Queryable, Debug, Clone)]
+struct NormalizedFlow {
+    from_entity: String,
+    to_entity: String,
+    resource_type: String,
+    unit: String,
+    quantity: f64,
 }
 
+/// Confirmed, unreversed flows the query's viewer may see, converted to
+/// each resource type's default unit.
+fn get_normalized_flows(
+    conn: &mut SqliteConnection,
+    flow_query: &FlowQuery,
+) -> QueryResult<Vec<NormalizedFlow>> {
+    flow_query
+        .apply(flow_events::table.into_boxed())
+        .filter(sql::<Bool>(NOT_REVERSED))
+        .filter(sql::<Bool>(CONFIRMED))
+        .select((
+            flow_events::from_entity,
+            flow_events::to_entity,
+            flow_events::resource_type,
+            sql::<Text>(REGISTRY_UNIT),
+            sql::<Double>(REGISTRY_QTY),
+        ))
+        .load(conn)
+}
 
-#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+/// Net balance per entity and resource type over the flows matching the
+/// query, so the viewer's visibility and the query's window both apply.
+pub fn get_entity_balances(
+    conn: &mut SqliteConnection,
+    flow_query: &FlowQuery,
+) -> QueryResult<Vec<EntityBalance>> {
+    let mut balances: BTreeMap<(String, String, String), f64> = BTreeMap::new();
+    for flow in get_normalized_flows(conn, flow_query)? {
+        let key = |entity: String| (entity, flow.resource_type.clone(), flow.unit.clone());
+        *balances.entry(key(flow.to_entity.clone())).or_default() += flow.quantity;
+        *balances.entry(key(flow.from_entity.clone())).or_default() -= flow.quantity;
+    }
+    Ok(balances
+        .into_iter()
+        .map(|((entity_id, resource_type, unit), balance)| EntityBalance {
+            entity_id,
+            resource_type,
+            unit,
+            balance,
+        })
+        .collect())
+}
+
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
 pub struct DollarsAvailable {
+    #[diesel(sql_type = Double)]
     pub dollars_available: f64,
 }
 
-pub fn get_dollars_available(conn: &mut SqliteConnection) -> QueryResult<DollarsAvailable> {
-    diesel::sql_query("SELECT * FROM v_dollars_available")
-        .get_result::<DollarsAvailable>(conn)
+/// Sum of positive dollar balances held by the host's entities, as of `until`.
+pub fn get_dollars_available(
+    conn: &mut SqliteConnection,
+    host: i32,
+    until: NaiveDateTime,
+) -> QueryResult<DollarsAvailable> {
+    diesel::sql_query(format!(
+        r#"
+        SELECT IFNULL(SUM(balance), 0.0) AS dollars_available
+        FROM (
+            SELECT entity_id, SUM(amount) AS balance
+            FROM (
+                SELECT to_entity AS entity_id, {NORMALIZED_QTY} AS amount
+                FROM flow_events {REGISTRY}
+                WHERE flow_events.host_id = ? AND {IS_MONETARY} AND timestamp <= ? AND {CONFIRMED}
+                UNION ALL
+                SELECT from_entity AS entity_id, -{NORMALIZED_QTY} AS amount
+                FROM flow_events {REGISTRY}
+                WHERE flow_events.host_id = ? AND {IS_MONETARY} AND timestamp <= ? AND {CONFIRMED}
+            )
+            GROUP BY entity_id
+        )
+        WHERE balance > 0
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(until)
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(until)
+    .get_result::<DollarsAvailable>(conn)
 }
 
-
-#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
 pub struct ActiveContributors {
+    #[diesel(sql_type = Integer)]
     pub active_contributors: i32,
 }
 
-#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
 pub struct TotalHours {
+    #[diesel(sql_type = Double)]
     pub total_hours: f64,
 }
 
-#[derive(Queryable, Serialize, Deserialize, Debug, Clone)]
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
 pub struct ActiveProjects {
+    #[diesel(sql_type = Integer)]
     pub active_projects: i32,
 }
 
+pub fn get_active_contributors(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> QueryResult<ActiveContributors> {
+    diesel::sql_query(format!(
+        r#"
+        SELECT COUNT(DISTINCT from_entity) AS active_contributors
+        FROM flow_events {REGISTRY}
+        WHERE flow_events.host_id = ?
+          AND {IS_LABOR}
+          AND timestamp >= ? AND timestamp <= ?
+          AND {NOT_REVERSED}
+          AND {CONFIRMED}
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(since)
+    .bind::<Timestamp, _>(until)
+    .get_result::<ActiveContributors>(conn)
+}
+
+pub fn get_total_hours(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> QueryResult<TotalHours> {
+    diesel::sql_query(format!(
+        r#"
+        SELECT IFNULL(SUM({NORMALIZED_QTY}), 0.0) AS total_hours
+        FROM flow_events {REGISTRY}
+        WHERE flow_events.host_id = ?
+          AND {IS_LABOR}
+          AND timestamp >= ? AND timestamp <= ?
+          AND {NOT_REVERSED}
+          AND {CONFIRMED}
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(since)
+    .bind::<Timestamp, _>(until)
+    .get_result::<TotalHours>(conn)
+}
+
+/// Entity types `get_active_projects` counts as projects; goals can only
+/// be set on these.
+pub const PROJECT_ENTITY_TYPES: [&str; 3] = ["project", "team", "organization"];
+
+pub fn get_active_projects(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> QueryResult<ActiveProjects> {
+    diesel::sql_query(format!(
+        r#"
+        SELECT COUNT(DISTINCT e.id) AS active_projects
+        FROM entities e
+        JOIN (
+            SELECT to_entity
+            FROM flow_events
+            WHERE host_id = ?
+              AND timestamp >= ? AND timestamp <= ?
+              AND {NOT_REVERSED}
+              AND {CONFIRMED}
+        ) f ON f.to_entity = e.id
+        WHERE e.entity_type IN ('project', 'team', 'organization')
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(since)
+    .bind::<Timestamp, _>(until)
+    .get_result::<ActiveProjects>(conn)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FlowEdge {
+    pub source: String,
+    pub target: String,
+    pub resource_type: String,
+    pub unit: String,
+    pub quantity: f64,
+    pub flow_count: i32,
+}
+
+/// Confirmed flows matching the query summed per (from, to, resource
+/// type), in each resource type's default unit. Flows the query's viewer
+/// cannot see are left out, as in the flow list.
+pub fn get_flow_edges(
+    conn: &mut SqliteConnection,
+    flow_query: &FlowQuery,
+) -> QueryResult<Vec<FlowEdge>> {
+    let mut edges: BTreeMap<(String, String, String, String), (f64, i32)> = BTreeMap::new();
+    for flow in get_normalized_flows(conn, flow_query)? {
+        let key = (flow.from_entity, flow.to_entity, flow.resource_type, flow.unit);
+        let edge = edges.entry(key).or_default();
+        edge.0 += flow.quantity;
+        edge.1 += 1;
+    }
+    let mut edges: Vec<FlowEdge> = edges
+        .into_iter()
+        .map(|((source, target, resource_type, unit), (quantity, flow_count))| FlowEdge {
+            source,
+            target,
+            resource_type,
+            unit,
+            quantity,
+            flow_count,
+        })
+        .collect();
+    edges.sort_by(|a, b| b.quantity.total_cmp(&a.quantity));
+    Ok(edges)
+}
+
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+pub struct ResourceTotal {
+    #[diesel(sql_type = Text)]
+    pub resource_type: String,
+    #[diesel(sql_type = Text)]
+    pub unit: String,
+    #[diesel(sql_type = Double)]
+    pub quantity: f64,
+    #[diesel(sql_type = Integer)]
+    pub flow_count: i32,
+}
+
+/// Confirmed totals per resource type in the window. Counts every flow
+/// regardless of visibility; nothing identifies who was involved.
+pub fn get_resource_totals(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> QueryResult<Vec<ResourceTotal>> {
+    diesel::sql_query(format!(
+        r#"
+        SELECT flow_events.resource_type AS resource_type,
+               IFNULL(r.default_unit, flow_events.quantity_unit) AS unit,
+               SUM({NORMALIZED_QTY}) AS quantity,
+               COUNT(*) AS flow_count
+        FROM flow_events {REGISTRY}
+        WHERE flow_events.host_id = ?
+          AND timestamp >= ? AND timestamp <= ?
+          AND {NOT_REVERSED}
+          AND {CONFIRMED}
+        GROUP BY flow_events.resource_type, unit
+        ORDER BY flow_events.resource_type
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(since)
+    .bind::<Timestamp, _>(until)
+    .load::<ResourceTotal>(conn)
+}
+
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+pub struct GoalProgress {
+    #[diesel(sql_type = Integer)]
+    pub goal_id: i32,
+    #[diesel(sql_type = Double)]
+    pub achieved: f64,
+    #[diesel(sql_type = Integer)]
+    pub flow_count: i32,
+}
+
+/// Progress on every goal on the host: confirmed flows into the goal's
+/// entity for its resource type within its period, in the goal's unit.
+pub fn get_goal_progress(conn: &mut SqliteConnection, host: i32) -> QueryResult<Vec<GoalProgress>> {
+    diesel::sql_query(format!(
+        r#"
+        SELECT g.id AS goal_id,
+               IFNULL(SUM({NORMALIZED_QTY}), 0.0)
+                 / IFNULL((
+                     SELECT json_extract(rg.units, '$."' || g.unit || '"')
+                     FROM resource_types rg
+                     WHERE rg.host_id = g.host_id AND rg.key = g.resource_type
+                   ), 1.0) AS achieved,
+               COUNT(flow_events.id) AS flow_count
+        FROM entity_goals g
+        LEFT JOIN flow_events
+          ON flow_events.host_id = g.host_id
+         AND flow_events.to_entity = g.entity_id
+         AND flow_events.resource_type = g.resource_type
+         AND flow_events.timestamp >= g.period_start
+         AND flow_events.timestamp <= g.period_end
+         AND {NOT_REVERSED}
+         AND {CONFIRMED}
+        {REGISTRY}
+        WHERE g.host_id = ?
+        GROUP BY g.id
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .load::<GoalProgress>(conn)
+}
+
+/// One counted flow with where it is mapped.
+#[derive(Queryable, Debug, Clone)]
+pub struct LocatedFlow {
+    pub resource_type: String,
+    pub quantity_value: f32,
+    pub quantity_unit: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// The receiving entity, when the flow is mapped at its location
+    /// rather than its own.
+    pub placed_at: Option<String>,
+}
+
+/// Confirmed, unreversed flows matching the query that have a location,
+/// their own or their receiving entity's.
+pub fn get_located_flows(
+    conn: &mut SqliteConnection,
+    flow_query: &FlowQuery,
+) -> QueryResult<Vec<LocatedFlow>> {
+    flow_query
+        .apply(flow_events::table.into_boxed())
+        .filter(sql::<Bool>(NOT_REVERSED))
+        .filter(sql::<Bool>(CONFIRMED))
+        .filter(sql::<Bool>(&format!("{FLOW_LATITUDE} IS NOT NULL AND {FLOW_LONGITUDE} IS NOT NULL")))
+        .select((
+            flow_events::resource_type,
+            flow_events::quantity_value,
+            flow_events::quantity_unit,
+            sql::<Double>(FLOW_LATITUDE),
+            sql::<Double>(FLOW_LONGITUDE),
+            sql::<Nullable<Text>>(
+                "CASE WHEN flow_events.latitude IS NULL OR flow_events.longitude IS NULL \
+                 THEN flow_events.to_entity END",
+            ),
+        ))
+        .load(conn)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ActivitySeries {
+    pub host_id: i32,
+    pub since: NaiveDateTime,
+    pub until: NaiveDateTime,
+    pub bucket: TimeBucket,
+    /// Every bucket in the range, including ones with no activity.
+    pub buckets: Vec<String>,
+    pub by_resource: Vec<ResourcePoint>,
+    pub by_entity_type: Vec<EntityTypePoint>,
+    /// One point per bucket; quiet buckets report zero.
+    pub contributors: Vec<ContributorPoint>,
+}
+
+pub fn get_activity_series(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+    bucket: TimeBucket,
+) -> QueryResult<ActivitySeries> {
+    let buckets = bucket.labels(since, until);
+    let active = get_contributor_series(conn, host, since, until, bucket)?;
+    let contributors = buckets
+        .iter()
+        .map(|b| ContributorPoint {
+            bucket: b.clone(),
+            active_contributors: active
+                .iter()
+                .find(|p| &p.bucket == b)
+                .map_or(0, |p| p.active_contributors),
+        })
+        .collect();
 
-pub fn get_active_contributors(conn: &mut SqliteConnection) -> QueryResult<ActiveContributors> {
-    diesel::sql_query("SELECT * FROM v_active_contributors_30d")
-        .get_result::<ActiveContributors>(conn)
+    Ok(ActivitySeries {
+        host_id: host,
+        since,
+        until,
+        bucket,
+        by_resource: get_resource_series(conn, host, since, until, bucket)?,
+        by_entity_type: get_entity_type_series(conn, host, since, until, bucket)?,
+        buckets,
+        contributors,
+    })
 }
 
-pub fn get_total_hours(conn: &mut SqliteConnection) -> QueryResult<TotalHours> {
-    diesel::sql_query("SELECT * FROM v_total_hours_30d")
-        .get_result::<TotalHours>(conn)
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+pub struct ResourcePoint {
+    #[diesel(sql_type = Text)]
+    pub bucket: String,
+    #[diesel(sql_type = Text)]
+    pub resource_type: String,
+    #[diesel(sql_type = Text)]
+    pub unit: String,
+    #[diesel(sql_type = Double)]
+    pub quantity: f64,
+}
+
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+pub struct EntityTypePoint {
+    #[diesel(sql_type = Text)]
+    pub bucket: String,
+    /// Type of the receiving entity.
+    #[diesel(sql_type = Text)]
+    pub entity_type: String,
+    #[diesel(sql_type = Text)]
+    pub resource_type: String,
+    #[diesel(sql_type = Text)]
+    pub unit: String,
+    #[diesel(sql_type = Double)]
+    pub quantity: f64,
 }
 
-pub fn get_active_projects(conn: &mut SqliteConnection) -> QueryResult<ActiveProjects> {
-    diesel::sql_query("SELECT * FROM v_active_projects_30d")
-        .get_result::<ActiveProjects>(conn)
-}
\ No newline at end of file
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+pub struct ContributorPoint {
+    #[diesel(sql_type = Text)]
+    pub bucket: String,
+    #[diesel(sql_type = Integer)]
+    pub active_contributors: i32,
+}
+
+/// Quantity per bucket and resource type, in the type's default unit.
+pub fn get_resource_series(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+    bucket: TimeBucket,
+) -> QueryResult<Vec<ResourcePoint>> {
+    let bucket = bucket.sql();
+    diesel::sql_query(format!(
+        r#"
+        SELECT {bucket} AS bucket,
+               flow_events.resource_type AS resource_type,
+               IFNULL(r.default_unit, flow_events.quantity_unit) AS unit,
+               SUM({NORMALIZED_QTY}) AS quantity
+        FROM flow_events {REGISTRY}
+        WHERE flow_events.host_id = ?
+          AND timestamp >= ? AND timestamp <= ?
+          AND {NOT_REVERSED}
+          AND {CONFIRMED}
+        GROUP BY bucket, flow_events.resource_type, unit
+        ORDER BY bucket, flow_events.resource_type
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(since)
+    .bind::<Timestamp, _>(until)
+    .load::<ResourcePoint>(conn)
+}
+
+/// Quantity per bucket, receiving entity type and resource type.
+pub fn get_entity_type_series(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+    bucket: TimeBucket,
+) -> QueryResult<Vec<EntityTypePoint>> {
+    let bucket = bucket.sql();
+    diesel::sql_query(format!(
+        r#"
+        SELECT {bucket} AS bucket,
+               e.entity_type AS entity_type,
+               flow_events.resource_type AS resource_type,
+               IFNULL(r.default_unit, flow_events.quantity_unit) AS unit,
+               SUM({NORMALIZED_QTY}) AS quantity
+        FROM flow_events {REGISTRY}
+        JOIN entities e ON e.id = flow_events.to_entity
+        WHERE flow_events.host_id = ?
+          AND timestamp >= ? AND timestamp <= ?
+          AND {NOT_REVERSED}
+          AND {CONFIRMED}
+        GROUP BY bucket, e.entity_type, flow_events.resource_type, unit
+        ORDER BY bucket, e.entity_type, flow_events.resource_type
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(since)
+    .bind::<Timestamp, _>(until)
+    .load::<EntityTypePoint>(conn)
+}
+
+/// Distinct labor contributors per bucket. Empty buckets are omitted.
+pub fn get_contributor_series(
+    conn: &mut SqliteConnection,
+    host: i32,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+    bucket: TimeBucket,
+) -> QueryResult<Vec<ContributorPoint>> {
+    let bucket = bucket.sql();
+    diesel::sql_query(format!(
+        r#"
+        SELECT {bucket} AS bucket, COUNT(DISTINCT from_entity) AS active_contributors
+        FROM flow_events {REGISTRY}
+        WHERE flow_events.host_id = ?
+          AND {IS_LABOR}
+          AND timestamp >= ? AND timestamp <= ?
+          AND {NOT_REVERSED}
+          AND {CONFIRMED}
+        GROUP BY bucket
+        ORDER BY bucket
+        "#
+    ))
+    .bind::<Integer, _>(host)
+    .bind::<Timestamp, _>(since)
+    .bind::<Timestamp, _>(until)
+    .load::<ContributorPoint>(conn)
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::{NewFlowEvent, create_flow_event};
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::JsonField;
+    use chrono::Duration;
+
+    fn flow(
+        conn: &mut SqliteConnection,
+        host: i32,
+        from: &str,
+        to: &str,
+        resource: &str,
+        qty: f32,
+        at: NaiveDateTime,
+    ) {
+        let new = NewFlowEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: at,
+            recorded_at: at,
+            from_entity: from.to_string(),
+            to_entity: to.to_string(),
+            host_id: host,
+            resource_type: resource.to_string(),
+            quantity_value: qty,
+            quantity_unit: "hours".to_string(),
+            notes: None,
+            details: JsonField::default(),
+            created_by: "test".to_string(),
+        };
+        create_flow_event(conn, &new).unwrap();
+    }
+
+    #[test]
+    fn vital_signs_are_scoped_to_host_and_window() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        // host 1 and host 3 both have activity
+        let skagit_person = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let skagit_project = create_test_entity(&mut conn, 1, "Garden", "project").id;
+        let revillage_person = create_test_entity(&mut conn, 3, "Sam", "Person").id;
+        let revillage_project = create_test_entity(&mut conn, 3, "Kitchen", "project").id;
+
+        flow(&mut conn, 1, &skagit_person, &skagit_project, "labor_time", 3.0, now);
+        flow(&mut conn, 1, &skagit_person, &skagit_project, "Dollars", 50.0, now);
+        flow(&mut conn, 3, &revillage_person, &revillage_project, "labor_time", 7.0, now);
+        // outside the window
+        flow(&mut conn, 1, &skagit_person, &skagit_project, "labor_time", 11.0, now - Duration::days(90));
+
+        let signs = get_vital_signs(&mut conn, 1, now - Duration::days(30), now).unwrap();
+
+        assert_eq!(signs.total_hours, 3.0);
+        assert_eq!(signs.active_contributors, 1);
+        assert_eq!(signs.active_projects, 1);
+        assert_eq!(signs.dollars_available, 50.0);
+
+        let balances = get_entity_balances(&mut conn, &FlowQuery::new(3).until(now)).unwrap();
+        assert!(balances.iter().all(|b| b.entity_id == revillage_person || b.entity_id == revillage_project));
+    }
+
+    #[test]
+    fn activity_series_fills_every_bucket() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        // Wednesday and the following Monday land in consecutive weeks
+        let wednesday = NaiveDateTime::parse_from_str("2026-03-04 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let monday = wednesday + Duration::days(5);
+
+        let person = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let project = create_test_entity(&mut conn, 1, "Garden", "project").id;
+        flow(&mut conn, 1, &person, &project, "labor_time", 2.0, wednesday);
+        flow(&mut conn, 1, &person, &project, "labor_time", 4.0, monday);
+
+        let series = get_activity_series(
+            &mut conn,
+            1,
+            wednesday - Duration::days(7),
+            monday,
+            TimeBucket::Week,
+        )
+        .unwrap();
+
+        assert_eq!(series.buckets, vec!["2026-02-23", "2026-03-02", "2026-03-09"]);
+        let active: Vec<i32> = series.contributors.iter().map(|p| p.active_contributors).collect();
+        assert_eq!(active, vec![0, 1, 1]);
+        let hours: Vec<(&str, f64)> = series
+            .by_resource
+            .iter()
+            .map(|p| (p.bucket.as_str(), p.quantity))
+            .collect();
+        assert_eq!(hours, vec![("2026-03-02", 2.0), ("2026-03-09", 4.0)]);
+        assert_eq!(series.by_entity_type[0].entity_type, "project");
+    }
+}
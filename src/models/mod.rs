@@ -18,5 +18,11 @@ pub mod question_summary;
 
 pub mod entities;
 pub mod flow_events;
+pub mod resource_types;
+pub mod entity_types;
+pub mod flow_templates;
+pub mod commitments;
+pub mod entity_goals;
+pub mod ledger_chain;
 
-//pub mod ledger_views;
\ No newline at end of file
+pub mod ledger_views;
\ No newline at end of file
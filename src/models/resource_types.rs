@@ -0,0 +1,110 @@
+use crate::{schema::resource_types, types::JsonField};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = resource_types)]
+pub struct ResourceType {
+    pub id: i32,
+    pub host_id: i32,
+    pub key: String,
+    pub label: String,
+    pub default_unit: String,
+    /// `{ "<unit>": <factor into default_unit> }`
+    pub units: JsonField,
+    pub is_monetary: bool,
+    pub is_labor: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Serialize, Deserialize)]
+#[diesel(table_name = resource_types)]
+pub struct NewResourceType {
+    pub host_id: i32,
+    pub key: String,
+    pub label: String,
+    pub default_unit: String,
+    pub units: JsonField,
+    pub is_monetary: bool,
+    pub is_labor: bool,
+}
+
+impl ResourceType {
+    /// Factor converting `unit` into the default unit, if the unit is allowed.
+    pub fn factor(&self, unit: &str) -> Option<f64> {
+        if unit == self.default_unit {
+            return Some(1.0);
+        }
+        self.units.0.get(unit)?.as_f64()
+    }
+
+    pub fn allowed_units(&self) -> Vec<String> {
+        let mut units = vec![self.default_unit.clone()];
+        if let Some(map) = self.units.0.as_object() {
+            units.extend(map.keys().filter(|u| **u != self.default_unit).cloned());
+        }
+        units
+    }
+}
+
+pub fn create_resource_type(
+    conn: &mut SqliteConnection,
+    new: &NewResourceType,
+) -> QueryResult<ResourceType> {
+    diesel::insert_into(resource_types::table)
+        .values(new)
+        .execute(conn)?;
+
+    resource_types::table
+        .filter(resource_types::host_id.eq(new.host_id))
+        .filter(resource_types::key.eq(&new.key))
+        .select(ResourceType::as_select())
+        .first(conn)
+}
+
+pub fn get_resource_types(
+    conn: &mut SqliteConnection,
+    host: i32,
+) -> QueryResult<Vec<ResourceType>> {
+    resource_types::table
+        .filter(resource_types::host_id.eq(host))
+        .order(resource_types::key.asc())
+        .select(ResourceType::as_select())
+        .load(conn)
+}
+
+pub fn get_resource_type(
+    conn: &mut SqliteConnection,
+    host: i32,
+    resource_key: &str,
+) -> QueryResult<ResourceType> {
+    resource_types::table
+        .filter(resource_types::host_id.eq(host))
+        .filter(resource_types::key.eq(resource_key))
+        .select(ResourceType::as_select())
+        .first(conn)
+}
+
+pub fn update_resource_type(
+    conn: &mut SqliteConnection,
+    resource_id: i32,
+    updated: &NewResourceType,
+) -> QueryResult<ResourceType> {
+    diesel::update(resource_types::table.find(resource_id))
+        .set(updated)
+        .execute(conn)?;
+
+    resource_types::table
+        .find(resource_id)
+        .select(ResourceType::as_select())
+        .first(conn)
+}
+
+pub fn delete_resource_type(
+    conn: &mut SqliteConnection,
+    resource_id: i32,
+) -> QueryResult<usize> {
+    diesel::delete(resource_types::table.find(resource_id)).execute(conn)
+}
@@ -4,11 +4,12 @@ use crate::domains::ledger_domain::LedgerDomain;
 use crate::errors::app_error::AppError;
 use crate::errors::auth_error::AuthError;
 use crate::middleware::host::{HostContext};
-use crate::middleware::host_utils::require_host_id;
 use crate::models::drafts::*;
-use crate::types::{DocType, DraftStatus, FrontendSchema, JsonField, MemberRole, load_frontend_schema};
+use crate::types::{DocType, DraftAction, DraftStatus, FrontendSchema, JsonField, MemberRole, load_frontend_schema};
 use crate::validator::{AuthContext, require_role_for_host};
-use actix_web::{HttpRequest, HttpResponse, Responder, Scope, web};
+use crate::services::draft_service::{CommentAnchor, DraftActor, DraftFields, DraftService};
+use crate::services::publish_service::SitePublisher;
+use actix_web::{HttpResponse, Responder, ResponseError, Scope, web};
 
 use crate::routes::register;
 use crate::types::method::Method;
@@ -34,6 +35,14 @@ async fn get_doc_schema() -> Result<HttpResponse, AppError> {
     Ok(HttpResponse::Ok().json(&*FRONTEND_SCHEMA))
 }
 
+/// Saving checks field types and options; saving as submitted also
+/// checks required fields.
+fn validate_new_draft(new: &NewDraft, saved: Option<&DraftFields>) -> Result<(), AppError> {
+    let submitting = new.status.as_deref() == Some(DraftStatus::Submitted.value());
+    let fields = DraftFields::from_new(new)?;
+    DraftService::validate(&FRONTEND_SCHEMA, &fields, saved, submitting)
+}
+
 // Create or save draft
 //#[post("")]
 pub async fn create_draft_api(
@@ -48,8 +57,26 @@ pub async fn create_draft_api(
     let mut new = new.into_inner();
     new.submitted_by = Some(auth_context.user_id);
     new.host_id = host.0.id;
-    
-    match domain.create_draft(&new) {
+
+    if let Err(e) = validate_new_draft(&new, None) {
+        return e.error_response();
+    }
+
+    // every draft starts as a draft; submitting is a transition
+    let submit = new.status.as_deref() == Some(DraftStatus::Submitted.value());
+    new.status = Some(DraftStatus::Draft.value().to_string());
+    new.reviewed_by = None;
+    new.reviewed_at = None;
+    new.review_notes = None;
+
+    let created = domain.create_draft(&new).and_then(|draft| {
+        if !submit {
+            return Ok(draft);
+        }
+        let actor = draft_actor(&auth_context, host.0.id);
+        domain.transition_draft(host.0.id, draft.id, DraftAction::Submit, &actor, None)
+    });
+    match created {
         Ok(draft) => HttpResponse::Ok().json(draft),
         Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
         
@@ -153,143 +180,308 @@ pub async fn get_drafts_api(
 
 // Get single draft
 //#[get("/{id}")]
-pub async fn get_draft_api(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
-    let mut conn = data.db_pool.get().unwrap();
-    match get_draft(&mut conn, id.into_inner()) {
-        Ok(draft) => HttpResponse::Ok().json(draft),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
+pub async fn get_draft_api(
+    id: web::Path<i32>,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let draft = domain.get_draft_with_comments(host.0.id, id.into_inner())?;
+    Ok(HttpResponse::Ok().json(draft))
 }
 
 // Update draft
 //#[post("/{id}")]
 pub async fn update_draft_api(
-    data: web::Data<AppState>,
     id: web::Path<i32>,
     auth_context: AuthContext,
     updated: web::Json<NewDraft>,
     host: HostContext,
-
-) -> impl Responder {
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
     log::debug!("Updating Draft");
-    let mut conn = data.db_pool.get().unwrap();
     let mut updated = updated.into_inner();
-    updated.submitted_by = Some(auth_context.user_id);
-    updated.host_id = host.0.id;
+    let actor = draft_actor(&auth_context, host.0.id);
 
     log::info!("Updating draft ID: {} {}", id, updated.title);
 
-    match update_draft(&mut conn, id.into_inner(), &updated) {
-        Ok(draft) => HttpResponse::Ok().json(draft),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    let draft = domain.get_draft(host.0.id, id.into_inner())?;
+    validate_new_draft(&updated, Some(&DraftFields::from_draft(&draft)))?;
+    let submit = updated.status.as_deref() == Some(DraftStatus::Submitted.value());
+
+    let mut saved = domain.update_draft(&draft, &mut updated, &actor)?;
+    if submit {
+        saved = domain.transition_draft(host.0.id, saved.id, DraftAction::Submit, &actor, None)?;
     }
+    Ok(HttpResponse::Ok().json(saved))
 }
 
 // Delete draft
 //#[delete("/{id}")]
-pub async fn delete_draft_api(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
-    let mut conn = data.db_pool.get().unwrap();
-    match delete_draft(&mut conn, id.into_inner()) {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
+pub async fn delete_draft_api(
+    id: web::Path<i32>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let actor = draft_actor(&auth_context, host.0.id);
+    domain.delete_draft(host.0.id, id.into_inner(), &actor)?;
+    Ok(HttpResponse::Ok().finish())
 }
 
 // Submit draft
 //#[post("/{id}/submit")]
 pub async fn submit_draft_api(
-    data: web::Data<AppState>, 
     id: web::Path<i32>,
-    
-) -> impl Responder {
-    let mut conn = data.db_pool.get().unwrap();
-    
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
     log::info!("Submitting draft ID: {}", id);
-    match submit_draft(&mut conn, id.into_inner()) {
-        Ok( draft) => {       
-            HttpResponse::Ok().json(draft)
-        },
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
+    let draft = domain.get_draft(host.0.id, id.into_inner())?;
+    DraftService::validate(&FRONTEND_SCHEMA, &DraftFields::from_draft(&draft), None, true)?;
+
+    let actor = draft_actor(&auth_context, host.0.id);
+    let draft = domain.transition_draft(host.0.id, draft.id, DraftAction::Submit, &actor, None)?;
+    Ok(HttpResponse::Ok().json(draft))
 }
 
 // Request changes
 //#[post("/{id}/request_changes")]
 pub async fn request_changes_api(
-    data: web::Data<AppState>,
     id: web::Path<i32>,
-    info: web::Json<(i32, String)>, // (reviewer_id, notes)
-) -> impl Responder {
-    let (reviewer_id, notes) = info.into_inner();
-    let mut conn = data.db_pool.get().unwrap();
-    match request_changes(&mut conn, id.into_inner(), reviewer_id, &notes) {
-        Ok(draft) => HttpResponse::Ok().json(draft),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
+    body: web::Json<ReviewNotes>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let notes = body.into_inner().notes;
+    review_draft(&domain, &auth_context, &host, id.into_inner(), DraftAction::RequestChanges, notes)
+}
+
+/// Notes left with a review step; they also start a comment thread.
+#[derive(Deserialize, Default)]
+pub struct ReviewNotes {
+    pub notes: Option<String>,
 }
 
-// curl 'https://dev.revillagesociety.org/api/drafts/4/approve' \
-//   -X 'POST' \
-//   -H 'accept: */*' \
-//   -H 'accept-language: en-US,en;q=0.9' \
-//   -H 'content-length: 0' \
-//   -b 'revillage_session=tXa%2FcDO9U5fGpWh1vAdcUO0mWbDbHWWC5oUCQ%2F71KGg9Xi0K8hRi2wbW3PE%3D' \
-//   -H 'origin: https://dev.revillagesociety.org' \
-//   -H 'priority: u=1, i' \
-//   -H 'referer: https://dev.revillagesociety.org/recipes/admin/' \
-//   -H 'sec-ch-ua: "Google Chrome";v="143", "Chromium";v="143", "Not A(Brand";v="24"' \
-//   -H 'sec-ch-ua-mobile: ?0' \
-//   -H 'sec-ch-ua-platform: "macOS"' \
-//   -H 'sec-fetch-dest: empty' \
-//   -H 'sec-fetch-mode: cors' \
-//   -H 'sec-fetch-site: same-origin' \
-//   -H 'user-agent: Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/143.0.0.0 Safari/537.36'
+//#[post("/{id}/reject")]
+pub async fn reject_draft_api(
+    id: web::Path<i32>,
+    body: Option<web::Json<ReviewNotes>>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let notes = body.and_then(|b| b.into_inner().notes);
+    review_draft(&domain, &auth_context, &host, id.into_inner(), DraftAction::Reject, notes)
+}
 
 // Approve draft
 //#[post("/{id}/approve")]
 pub async fn approve_draft_api(
-    data: web::Data<AppState>,
     id: web::Path<i32>,
-    admin_context: AuthContext,
-    req: HttpRequest,
-    //reviewer_id: web::Json<i32>,
-) -> Result<HttpResponse, AuthError> {
-    let incoming_host_id = require_host_id(&req).await.unwrap(); // safe because fallback exists
-
-    require_role_for_host(
-        &admin_context,
-        incoming_host_id,
-        &[MemberRole::Admin, MemberRole::Reviewer],
-    )?;
-    let mut conn = data.db_pool.get().unwrap();
-    let reviewer_id = admin_context.user_id; // TODO: get from AuthenticatedUser
-    match approve_draft(&mut conn, id.into_inner(), reviewer_id) {
-        Ok(draft) => Ok(HttpResponse::Ok().json(draft)),
-        Err(_) => Err(AuthError::Forbidden("Approval Failed")),
-    }
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    review_draft(&domain, &auth_context, &host, id.into_inner(), DraftAction::Approve, None)
 }
 
-// Approve draft
+// Deploy an approved draft
 //#[post("/{id}/deploy")]
 pub async fn deploy_draft_api(
     data: web::Data<AppState>,
     id: web::Path<i32>,
-    admin_context: AuthContext,
-    req: HttpRequest,
-    //reviewer_id: web::Json<i32>,
-) -> Result<HttpResponse, AuthError> {
-    let incoming_host_id = require_host_id(&req).await.unwrap(); // safe because fallback exists
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth_context, host.0.id, &[MemberRole::Admin, MemberRole::Reviewer])?;
+    let publisher = SitePublisher::for_host(&data.settings.publish, &host.0.slug);
+    let actor = draft_actor(&auth_context, host.0.id);
+    let (host_id, draft_id) = (host.0.id, id.into_inner());
+    // git runs here, so keep it off the request threads; the page is
+    // rendered from the draft as read under the site lock
+    let draft = web::block(move || domain.deploy_draft(host_id, draft_id, &actor, publisher.as_ref(), draft_markdown))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(draft))
+}
 
-    require_role_for_host(
-        &admin_context,
-        incoming_host_id,
-        &[MemberRole::Admin, MemberRole::Reviewer],
+// Take a deployed draft off the site
+//#[post("/{id}/unpublish")]
+pub async fn unpublish_draft_api(
+    data: web::Data<AppState>,
+    id: web::Path<i32>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth_context, host.0.id, &[MemberRole::Admin, MemberRole::Reviewer])?;
+    let publisher = SitePublisher::for_host(&data.settings.publish, &host.0.slug);
+    let actor = draft_actor(&auth_context, host.0.id);
+    let (host_id, draft_id) = (host.0.id, id.into_inner());
+    let draft = web::block(move || domain.unpublish_draft(host_id, draft_id, &actor, publisher.as_ref()))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(draft))
+}
+
+// Who moved the draft through review, and when
+//#[get("/{id}/history")]
+pub async fn get_draft_history_api(
+    id: web::Path<i32>,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let transitions = domain.get_draft_transitions(host.0.id, id.into_inner())?;
+    Ok(HttpResponse::Ok().json(transitions))
+}
+
+//#[get("/{id}/revisions")]
+pub async fn get_draft_revisions_api(
+    id: web::Path<i32>,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let revisions = domain.get_draft_revisions(host.0.id, id.into_inner())?;
+    Ok(HttpResponse::Ok().json(revisions))
+}
+
+//#[get("/{id}/revisions/{revision}")]
+pub async fn get_draft_revision_api(
+    path: web::Path<(i32, i32)>,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let (id, revision) = path.into_inner();
+    Ok(HttpResponse::Ok().json(domain.get_draft_revision(host.0.id, id, revision)?))
+}
+
+#[derive(Deserialize)]
+pub struct RevisionDiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+//#[get("/{id}/diff?from=1&to=2")]
+pub async fn diff_draft_revisions_api(
+    id: web::Path<i32>,
+    query: web::Query<RevisionDiffQuery>,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let diff = domain.diff_draft_revisions(host.0.id, id.into_inner(), query.from, query.to)?;
+    Ok(HttpResponse::Ok().json(diff))
+}
+
+//#[post("/{id}/revisions/{revision}/restore")]
+pub async fn restore_draft_revision_api(
+    path: web::Path<(i32, i32)>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let (id, revision) = path.into_inner();
+    let actor = draft_actor(&auth_context, host.0.id);
+    let draft = domain.restore_draft_revision(host.0.id, id, revision, &actor)?;
+    Ok(HttpResponse::Ok().json(draft))
+}
+
+//#[get("/{id}/comments")]
+pub async fn get_draft_comments_api(
+    id: web::Path<i32>,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let threads = domain.get_draft_comments(host.0.id, id.into_inner())?;
+    Ok(HttpResponse::Ok().json(threads))
+}
+
+#[derive(Deserialize)]
+pub struct NewCommentPayload {
+    pub body: String,
+    pub parent_id: Option<i32>,
+    #[serde(flatten)]
+    pub anchor: CommentAnchor,
+}
+
+//#[post("/{id}/comments")]
+pub async fn add_draft_comment_api(
+    id: web::Path<i32>,
+    payload: web::Json<NewCommentPayload>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    let payload = payload.into_inner();
+    let actor = draft_actor(&auth_context, host.0.id);
+    let comment = domain.add_draft_comment(
+        host.0.id,
+        id.into_inner(),
+        &actor,
+        &payload.body,
+        payload.parent_id,
+        payload.anchor,
     )?;
-    let mut conn = data.db_pool.get().unwrap();
-    let reviewer_id = admin_context.user_id; // TODO: get from AuthenticatedUser
-    match deploy_draft(&mut conn, id.into_inner(), reviewer_id) {
-        Ok(draft) => Ok(HttpResponse::Ok().json(draft)),
-        Err(_) => Err(AuthError::Forbidden("Approval Failed")),
+    Ok(HttpResponse::Ok().json(comment))
+}
+
+//#[post("/{id}/comments/{comment_id}/resolve")]
+pub async fn resolve_draft_comment_api(
+    path: web::Path<(i32, i32)>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    set_comment_resolved(path.into_inner(), &auth_context, &host, &domain, true)
+}
+
+//#[post("/{id}/comments/{comment_id}/unresolve")]
+pub async fn unresolve_draft_comment_api(
+    path: web::Path<(i32, i32)>,
+    auth_context: AuthContext,
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
+    set_comment_resolved(path.into_inner(), &auth_context, &host, &domain, false)
+}
+
+fn set_comment_resolved(
+    (id, comment_id): (i32, i32),
+    auth_context: &AuthContext,
+    host: &HostContext,
+    domain: &DraftDomain,
+    resolved: bool,
+) -> Result<HttpResponse, AppError> {
+    let actor = draft_actor(auth_context, host.0.id);
+    let comment = domain.resolve_draft_comment(host.0.id, id, comment_id, &actor, resolved)?;
+    Ok(HttpResponse::Ok().json(comment))
+}
+
+fn review_draft(
+    domain: &DraftDomain,
+    auth_context: &AuthContext,
+    host: &HostContext,
+    draft_id: i32,
+    action: DraftAction,
+    notes: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(auth_context, host.0.id, &[MemberRole::Admin, MemberRole::Reviewer])?;
+    let actor = draft_actor(auth_context, host.0.id);
+    let draft = domain.transition_draft(host.0.id, draft_id, action, &actor, notes)?;
+    Ok(HttpResponse::Ok().json(draft))
+}
+
+fn draft_actor(auth_context: &AuthContext, host_id: i32) -> DraftActor {
+    DraftActor {
+        user_id: auth_context.user_id,
+        is_reviewer: require_role_for_host(
+            auth_context,
+            host_id,
+            &[MemberRole::Admin, MemberRole::Reviewer],
+        )
+        .is_ok(),
     }
 }
 
@@ -470,17 +662,18 @@ fn _generate_frontmatter_old(draft: &Draft) -> String {
     frontmatter
 }
 
+/// The file the static site gets: frontmatter, then the body.
+fn draft_markdown(draft: &Draft) -> String {
+    format!("{}{}", generate_frontmatter(draft), draft.body_md)
+}
+
 //#[get("/{id}/md")]
 pub async fn get_draft_md_api(data: web::Data<AppState>, id: web::Path<i32>) -> impl Responder {
     let mut conn = data.db_pool.get().unwrap();
     match get_draft(&mut conn, id.into_inner()) {
-        Ok(draft) => {
-            let frontmatter = generate_frontmatter(&draft);
-            let md_content = format!("{}{}", frontmatter, draft.body_md);
-            HttpResponse::Ok()
-                .content_type("text/markdown")
-                .body(md_content)
-        }
+        Ok(draft) => HttpResponse::Ok()
+            .content_type("text/markdown")
+            .body(draft_markdown(&draft)),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
@@ -491,24 +684,18 @@ pub struct BulkIds {
 
 //#[post("/bulk/approve")]
 pub async fn bulk_approve(
-    data: web::Data<crate::app_state::AppState>,
-    admin_context: AuthContext,
+    auth_context: AuthContext,
     ids: web::Json<BulkIds>,
-    req: HttpRequest,
-) -> Result<HttpResponse, AuthError> {
-    let incoming_host_id = require_host_id(&req).await.unwrap(); // safe because fallback exists
-
+    host: HostContext,
+    domain: web::Data<DraftDomain>,
+) -> Result<HttpResponse, AppError> {
     require_role_for_host(
-        &admin_context,
-        incoming_host_id,
+        &auth_context,
+        host.0.id,
         &[MemberRole::Admin, MemberRole::Reviewer],
     )?;
-    let ids = ids.into_inner().ids;
-    let mut conn = data.db_pool.get().unwrap();
-    match approve_drafts(&mut conn, &ids, admin_context.user_id) {
-        Ok(_) => (),
-        Err(_) => return Err(AuthError::Forbidden("Failed to approve drafts")),
-    };
+    let actor = draft_actor(&auth_context, host.0.id);
+    domain.approve_drafts(host.0.id, &ids.into_inner().ids, &actor)?;
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -614,6 +801,88 @@ pub fn admin_scope(parent_path: Vec<&str>) -> Scope {
             approve_draft_api,
             MemberRole::Reviewer,
         ))
+        .service(register(
+            "draft_reject",
+            Method::POST,
+            &full_path,
+            "{id}/reject",
+            reject_draft_api,
+            MemberRole::Reviewer,
+        ))
+        .service(register(
+            "draft_history",
+            Method::GET,
+            &full_path,
+            "{id}/history",
+            get_draft_history_api,
+            MemberRole::Member,
+        ))
+        // Revisions
+        .service(register(
+            "draft_revisions",
+            Method::GET,
+            &full_path,
+            "{id}/revisions",
+            get_draft_revisions_api,
+            MemberRole::Member,
+        ))
+        .service(register(
+            "draft_revision",
+            Method::GET,
+            &full_path,
+            "{id}/revisions/{revision}",
+            get_draft_revision_api,
+            MemberRole::Member,
+        ))
+        .service(register(
+            "draft_revision_diff",
+            Method::GET,
+            &full_path,
+            "{id}/diff",
+            diff_draft_revisions_api,
+            MemberRole::Member,
+        ))
+        .service(register(
+            "draft_revision_restore",
+            Method::POST,
+            &full_path,
+            "{id}/revisions/{revision}/restore",
+            restore_draft_revision_api,
+            MemberRole::Member,
+        ))
+        // Review comments
+        .service(register(
+            "draft_comments",
+            Method::GET,
+            &full_path,
+            "{id}/comments",
+            get_draft_comments_api,
+            MemberRole::Member,
+        ))
+        .service(register(
+            "draft_comment_add",
+            Method::POST,
+            &full_path,
+            "{id}/comments",
+            add_draft_comment_api,
+            MemberRole::Member,
+        ))
+        .service(register(
+            "draft_comment_resolve",
+            Method::POST,
+            &full_path,
+            "{id}/comments/{comment_id}/resolve",
+            resolve_draft_comment_api,
+            MemberRole::Member,
+        ))
+        .service(register(
+            "draft_comment_unresolve",
+            Method::POST,
+            &full_path,
+            "{id}/comments/{comment_id}/unresolve",
+            unresolve_draft_comment_api,
+            MemberRole::Member,
+        ))
         .service(register(
             "draft_deploy",
             Method::POST,
@@ -622,6 +891,14 @@ pub fn admin_scope(parent_path: Vec<&str>) -> Scope {
             deploy_draft_api,
             MemberRole::Reviewer,
         ))
+        .service(register(
+            "draft_unpublish",
+            Method::POST,
+            &full_path,
+            "{id}/unpublish",
+            unpublish_draft_api,
+            MemberRole::Reviewer,
+        ))
         // Markdown Export
         .service(register(
             "draft_markdown",
@@ -1,17 +1,31 @@
+use crate::app_state::AppState;
 use crate::domains::ledger_domain::LedgerDomain;
+use crate::errors::app_error::AppError;
+use crate::errors::auth_error::AuthError;
 use crate::middleware::host::{HostContext};
 
 
-use crate::models::entities::NewEntity;
+use crate::models::commitments::NewCommitment;
+use crate::models::entities::{EntityChanges, NewEntity};
+use crate::models::entity_goals::NewEntityGoal;
+use crate::models::entity_types::NewEntityType;
 use crate::models::flow_events::NewFlowEvent;
+use crate::models::flow_templates::NewFlowTemplate;
+use crate::models::resource_types::NewResourceType;
 use crate::routes::register;
-use crate::types::JsonField;
-use crate::types::flow_query::{FlowDirection, FlowQuery};
+use crate::services::entity_service::UserLookup;
+use crate::services::export_service::{ExportFormat, ExportKind, ExportService};
+use crate::services::import_service::ImportMapping;
+use crate::types::{EntityRole, FlowActionType, JsonField, MemberRole, Recurrence, Visibility};
+use crate::types::flow_query::{FlowCursor, FlowDirection, FlowQuery, FlowSort, FlowViewer, TimeBucket};
+use crate::types::geo::{GeoArea, GeoPoint};
 //use crate::services::hosts::HostDomain;
 use crate::types::method::Method;
-use crate::validator::AuthContext;
+use crate::validator::{AuthContext, require_role_for_host};
+use actix_multipart::Multipart;
 use actix_web::{HttpResponse, Responder, Scope, web};
-use chrono::NaiveDateTime;
+use futures_util::StreamExt as _;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use serde::Deserialize;
 use serde_json::json;
 use uuid::Uuid;
@@ -26,6 +40,12 @@ pub struct NewFlowPayload {
     pub notes: Option<String>,
     pub timestamp: Option<NaiveDateTime>,
     pub details: JsonField,
+    /// Defaults to `members`.
+    #[serde(default)]
+    pub visibility: Option<Visibility>,
+    /// Where it happened; unlocated flows map to the receiving entity.
+    #[serde(default)]
+    pub location: Option<GeoPoint>,
 }
 
 
@@ -61,16 +81,25 @@ async fn create_entity(
 
     match domain.create_entity(new_entity) {
         Ok(entity) => HttpResponse::Ok().json(entity),
+        Err(AppError::BadRequest(e)) => HttpResponse::BadRequest().body(e),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct EntityListParams {
+    pub include_archived: Option<bool>,
+}
+
 async fn get_entities(
     domain: web::Data<LedgerDomain>,
     //host: web::Data<crate::middleware::host::HostContext>,
-    host: HostContext
+    host: HostContext,
+    query: web::Query<EntityListParams>,
+    auth: Option<AuthContext>,
 ) -> impl Responder {
-    match domain.get_all_entities(host.0.id) {
+    let viewer = viewer_for(auth.as_ref(), host.0.id);
+    match domain.get_all_entities(host.0.id, query.include_archived.unwrap_or(false), &viewer) {
         Ok(entities) => HttpResponse::Ok().json(entities),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
@@ -79,14 +108,365 @@ async fn get_entities(
 async fn get_entity(
     domain: web::Data<LedgerDomain>,
     path: web::Path<String>,
+    host: HostContext,
+    auth: Option<AuthContext>,
 ) -> impl Responder {
     let entity_id = path.into_inner();
-    match domain.get_entity(&entity_id) {
+    let viewer = viewer_for(auth.as_ref(), host.0.id);
+    match domain.get_entity(&entity_id, &viewer) {
         Ok(entity) => HttpResponse::Ok().json(entity),
+        Err(AppError::NotFound(e)) => HttpResponse::NotFound().body(e),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
+/// PATCH body; PUT uses `NewEntityPayload` and replaces all three fields.
+#[derive(Debug, Deserialize)]
+pub struct EntityPatchPayload {
+    pub name: Option<String>,
+    pub entity_type: Option<String>,
+    pub details: Option<JsonField>,
+    /// `true` archives (hides from pickers), `false` restores.
+    pub archived: Option<bool>,
+    pub visibility: Option<Visibility>,
+}
+
+async fn replace_entity(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<NewEntityPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let payload = payload.into_inner();
+    let changes = EntityChanges {
+        name: Some(payload.name),
+        entity_type: Some(payload.entity_type),
+        details: Some(payload.details),
+        archived_at: None,
+        visibility: None,
+        ..Default::default()
+    };
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let entity = domain.update_entity(host.0.id, &path.into_inner(), auth.user_id, is_admin, changes)?;
+    Ok(HttpResponse::Ok().json(entity))
+}
+
+async fn patch_entity(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<EntityPatchPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let payload = payload.into_inner();
+    let changes = EntityChanges {
+        name: payload.name,
+        entity_type: payload.entity_type,
+        details: payload.details,
+        archived_at: payload
+            .archived
+            .map(|archived| archived.then(|| chrono::Utc::now().naive_utc())),
+        visibility: payload.visibility.map(|v| v.value().to_string()),
+        ..Default::default()
+    };
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let entity = domain.update_entity(host.0.id, &path.into_inner(), auth.user_id, is_admin, changes)?;
+    Ok(HttpResponse::Ok().json(entity))
+}
+
+async fn delete_entity(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    domain.delete_entity(host.0.id, &path.into_inner(), auth.user_id, is_admin)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// -----------------------------
+// RESOURCE REGISTRY ROUTES
+// -----------------------------
+#[derive(Debug, Deserialize)]
+pub struct ResourceTypePayload {
+    pub key: String,
+    pub label: String,
+    pub default_unit: String,
+    #[serde(default)]
+    pub units: JsonField,
+    #[serde(default)]
+    pub is_monetary: bool,
+    #[serde(default)]
+    pub is_labor: bool,
+}
+
+impl ResourceTypePayload {
+    fn into_new(self, host: i32) -> NewResourceType {
+        NewResourceType {
+            host_id: host,
+            key: self.key,
+            label: self.label,
+            default_unit: self.default_unit,
+            units: if self.units.0.is_null() {
+                JsonField(serde_json::json!({}))
+            } else {
+                self.units
+            },
+            is_monetary: self.is_monetary,
+            is_labor: self.is_labor,
+        }
+    }
+}
+
+async fn get_resource_types(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let registry = domain.get_resource_types(host.0.id)?;
+    Ok(HttpResponse::Ok().json(registry))
+}
+
+async fn create_resource_type(
+    domain: web::Data<LedgerDomain>,
+    payload: web::Json<ResourceTypePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let resource = domain.create_resource_type(payload.into_inner().into_new(host.0.id))?;
+    Ok(HttpResponse::Ok().json(resource))
+}
+
+async fn update_resource_type(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<i32>,
+    payload: web::Json<ResourceTypePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let resource = domain.update_resource_type(path.into_inner(), payload.into_inner().into_new(host.0.id))?;
+    Ok(HttpResponse::Ok().json(resource))
+}
+
+async fn delete_resource_type(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<i32>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    domain.delete_resource_type(host.0.id, path.into_inner())?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// -----------------------------
+// ENTITY TYPE SCHEMA ROUTES
+// -----------------------------
+#[derive(Debug, Deserialize)]
+pub struct EntityTypePayload {
+    pub key: String,
+    pub label: String,
+    /// Detail fields in the doc_schema.json field format.
+    #[serde(default)]
+    pub fields: JsonField,
+}
+
+impl EntityTypePayload {
+    fn into_new(self, host: i32) -> NewEntityType {
+        NewEntityType {
+            host_id: host,
+            key: self.key,
+            label: self.label,
+            fields: if self.fields.0.is_null() {
+                JsonField(serde_json::json!([]))
+            } else {
+                self.fields
+            },
+        }
+    }
+}
+
+async fn get_entity_types(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let registry = domain.get_entity_types(host.0.id)?;
+    Ok(HttpResponse::Ok().json(registry))
+}
+
+async fn create_entity_type(
+    domain: web::Data<LedgerDomain>,
+    payload: web::Json<EntityTypePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let entity_type = domain.create_entity_type(payload.into_inner().into_new(host.0.id))?;
+    Ok(HttpResponse::Ok().json(entity_type))
+}
+
+async fn update_entity_type(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<i32>,
+    payload: web::Json<EntityTypePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let entity_type = domain.update_entity_type(path.into_inner(), payload.into_inner().into_new(host.0.id))?;
+    Ok(HttpResponse::Ok().json(entity_type))
+}
+
+async fn delete_entity_type(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<i32>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    domain.delete_entity_type(host.0.id, path.into_inner())?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// -----------------------------
+// ENTITY PEOPLE ROUTES
+// -----------------------------
+async fn get_entity_people(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let viewer = viewer_for(Some(&auth), host.0.id);
+    let people = domain.get_entity_people(host.0.id, &path.into_inner(), &viewer)?;
+    Ok(HttpResponse::Ok().json(people))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvitePayload {
+    pub user_id: Option<i32>,
+    pub email: Option<String>,
+    pub role: EntityRole,
+}
+
+async fn invite_to_entity(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<InvitePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let invitee = match (payload.user_id, payload.email.as_deref()) {
+        (Some(id), _) => UserLookup::Id(id),
+        (None, Some(email)) => UserLookup::Email(email),
+        (None, None) => return Err(AppError::BadRequest("Give a user_id or email".into())),
+    };
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let link = domain.invite_to_entity(
+        host.0.id,
+        &path.into_inner(),
+        auth.user_id,
+        is_admin,
+        invitee,
+        payload.role,
+    )?;
+    Ok(HttpResponse::Ok().json(link))
+}
+
+async fn accept_entity_invite(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let link = domain.respond_to_entity_invite(host.0.id, &path.into_inner(), auth.user_id, true)?;
+    Ok(HttpResponse::Ok().json(link))
+}
+
+async fn decline_entity_invite(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let link = domain.respond_to_entity_invite(host.0.id, &path.into_inner(), auth.user_id, false)?;
+    Ok(HttpResponse::Ok().json(link))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityRolePayload {
+    pub role: EntityRole,
+}
+
+async fn set_entity_role(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<(String, i32)>,
+    payload: web::Json<EntityRolePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let (entity_id, member) = path.into_inner();
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let link = domain.set_entity_role(host.0.id, &entity_id, auth.user_id, is_admin, member, payload.role)?;
+    Ok(HttpResponse::Ok().json(link))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityAliasPayload {
+    pub alias: String,
+}
+
+async fn add_entity_alias(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<EntityAliasPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let alias = domain.add_entity_alias(host.0.id, &path.into_inner(), &payload.alias, auth.user_id)?;
+    Ok(HttpResponse::Ok().json(alias))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeEntityPayload {
+    /// The duplicate folded into the entity in the path.
+    pub duplicate_id: String,
+}
+
+async fn merge_entity(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<MergeEntityPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let merge = domain.merge_entities(host.0.id, &path.into_inner(), &payload.duplicate_id, auth.user_id)?;
+    Ok(HttpResponse::Ok().json(merge))
+}
+
+async fn get_duplicate_entities(
+    domain: web::Data<LedgerDomain>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let candidates = domain.find_duplicate_entities(host.0.id)?;
+    Ok(HttpResponse::Ok().json(candidates))
+}
 
 #[derive(Debug, Deserialize)]
 struct NewBulkEntityPayload {
@@ -129,21 +509,21 @@ async fn submit_bulk_flows(
     payload: web::Json<NewBulkFlowPayload>,
     auth: AuthContext,
     host: HostContext
-)-> impl Responder {
-
-   
-    let entity_user_id = domain.resolve_or_create_entity(auth.user_id, host.0.id).unwrap();
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
 
+    let entity_user_id = domain.resolve_or_create_entity(auth.user_id, host.0.id)?;
 
-    let events: Vec<NewFlowEvent> = payload.rows.iter().map(|row| {
-        NewFlowEvent {
+    let mut rows = Vec::with_capacity(payload.rows.len());
+    for row in &payload.rows {
+        let new_flow = NewFlowEvent {
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: row
                 .timestamp
                 .unwrap_or_else(|| chrono::Utc::now().naive_utc()),
-            from_entity: row.from_entity.clone(),
-            to_entity: row.to_entity.clone(),
-            host_id: host.0.id.clone(),
+            from_entity: domain.resolve_entity(&row.from_entity, host.0.id)?,
+            to_entity: domain.resolve_entity(&row.to_entity, host.0.id)?,
+            host_id: host.0.id,
 
             resource_type: row.resource_type.clone(),
             quantity_value: row.quantity_value,
@@ -154,11 +534,83 @@ async fn submit_bulk_flows(
             details: row.details.clone(),
 
             created_by: entity_user_id.clone(),
+        };
+        rows.push((new_flow, row.visibility, row.location));
+    }
+
+    // Bulk loads are admin-only but still go through approval: each row is
+    // proposed like a single submitted flow and waits for the receiver.
+    let flows = domain.submit_flows(auth.user_id, true, rows)?;
+    Ok(HttpResponse::Ok().json(json!({ "results": flows })))
+}
+
+// -----------------------------
+// CSV IMPORT ROUTES
+// -----------------------------
+#[derive(Debug, Deserialize)]
+struct ImportParams {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Reads the `file` part (CSV) and an optional `mapping` part
+/// (JSON object of import field -> CSV header) from the upload.
+async fn read_import_upload(mut payload: Multipart) -> Result<(Vec<u8>, ImportMapping), AppError> {
+    let mut data = None;
+    let mut mapping = ImportMapping::default();
+
+    while let Some(item) = payload.next().await {
+        let mut field = item.map_err(|e| AppError::BadRequest(format!("Invalid upload: {}", e)))?;
+        let name = field.name().unwrap_or_default().to_string();
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|e| AppError::BadRequest(format!("Invalid upload: {}", e)))?;
+            bytes.extend_from_slice(&chunk);
         }
-    }).collect();
 
-   let results = domain.save_all_flow_events(events);
-   format!("{}",json!({"results": results.unwrap_or_default() }))
+        match name.as_str() {
+            "file" => data = Some(bytes),
+            "mapping" => {
+                mapping = serde_json::from_slice(&bytes)
+                    .map_err(|e| AppError::BadRequest(format!("Invalid mapping: {}", e)))?;
+            }
+            _ => {}
+        }
+    }
+
+    let data = data.ok_or_else(|| AppError::BadRequest("No file found".into()))?;
+    Ok((data, mapping))
+}
+
+async fn import_entities(
+    domain: web::Data<LedgerDomain>,
+    params: web::Query<ImportParams>,
+    payload: Multipart,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let (data, mapping) = read_import_upload(payload).await?;
+    let created_by = domain.get_user_entity_id(host.0.id, auth.user_id)?;
+    let report = domain.import_entities_csv(host.0.id, &created_by, &data, &mapping, params.dry_run)?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+async fn import_flows(
+    domain: web::Data<LedgerDomain>,
+    params: web::Query<ImportParams>,
+    payload: Multipart,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let (data, mapping) = read_import_upload(payload).await?;
+    let created_by = domain.get_user_entity_id(host.0.id, auth.user_id)?;
+    let report = domain.import_flows_csv(host.0.id, &created_by, &data, &mapping, params.dry_run)?;
+    Ok(HttpResponse::Ok().json(report))
 }
 
 // -----------------------------
@@ -170,16 +622,16 @@ async fn submit_flow(
     auth: AuthContext,
     //host: web::Data<crate::middleware::host::HostContext>,
     host: HostContext
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let current_time = chrono::Utc::now().naive_utc();
     let timestamp = payload
         .timestamp
         .unwrap_or_else(|| current_time);
 
-    let entity_user_id = domain.resolve_or_create_entity(auth.user_id, host.0.id).unwrap();
+    let entity_user_id = domain.resolve_or_create_entity(auth.user_id, host.0.id)?;
 
-    let from_id = domain.resolve_entity(&payload.from_entity, host.0.id).unwrap();
-    let to_id   = domain.resolve_entity(&payload.to_entity, host.0.id).unwrap();
+    let from_id = domain.resolve_entity(&payload.from_entity, host.0.id)?;
+    let to_id   = domain.resolve_entity(&payload.to_entity, host.0.id)?;
     log::debug!("From: {:?}", from_id);
     log::debug!("To: {:?}", to_id);
 
@@ -198,77 +650,910 @@ async fn submit_flow(
         created_by: entity_user_id,
     };
 
-    match domain.record_flow(new_flow) {
-        Ok(flow) => HttpResponse::Ok().json(flow),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let flow = domain.submit_flow(auth.user_id, is_admin, new_flow, payload.visibility, payload.location);
+    match flow {
+        Ok(flow) => Ok(HttpResponse::Ok().json(flow)),
+        Err(AppError::Auth(e)) => Ok(HttpResponse::Forbidden().body(e.to_string())),
+        Err(AppError::BadRequest(e)) => Ok(HttpResponse::BadRequest().body(e)),
+        Err(e) => Err(e),
     }
 }
 
-async fn get_entity_flows(
+#[derive(Debug, Deserialize)]
+pub struct FlowVisibilityPayload {
+    pub visibility: Visibility,
+}
+
+async fn set_flow_visibility(
     domain: web::Data<LedgerDomain>,
     path: web::Path<String>,
+    payload: web::Json<FlowVisibilityPayload>,
+    auth: AuthContext,
     host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    domain.set_flow_visibility(host.0.id, &path.into_inner(), auth.user_id, is_admin, payload.visibility)?;
+    Ok(HttpResponse::NoContent().finish())
+}
 
-) -> impl Responder {
-    let entity_id = path.into_inner();
-    match domain.get_flows_for_entity(host.0.id, &entity_id) {
-        Ok(flows) => HttpResponse::Ok().json(flows),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
-    }
+// -----------------------------
+// LOCATION ROUTES
+// -----------------------------
+#[derive(Debug, Deserialize)]
+pub struct EntityLocationPayload {
+    /// GeoJSON Point or Polygon; `null` clears it.
+    pub geometry: Option<serde_json::Value>,
+}
+
+async fn set_entity_location(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<EntityLocationPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let entity = domain.set_entity_location(
+        host.0.id,
+        &path.into_inner(),
+        auth.user_id,
+        is_admin,
+        payload.into_inner().geometry,
+    )?;
+    Ok(HttpResponse::Ok().json(entity))
+}
+
+async fn set_flow_location(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<GeoPoint>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let flow = domain.set_flow_location(host.0.id, &path.into_inner(), auth.user_id, is_admin, payload.into_inner())?;
+    Ok(HttpResponse::Ok().json(flow))
 }
 
 #[derive(Deserialize)]
-pub struct FlowQueryParams {
-    entity: Option<Uuid>,
-    direction: Option<String>, // "From", "To", "Both"
-    start: Option<String>,     // YYYY-MM-DD
-    end: Option<String>,       // YYYY-MM-DD
-    limit: Option<i64>,
-    offset: Option<i64>,
+pub struct GeoAreaParams {
+    bbox: Option<String>,   // "minLon,minLat,maxLon,maxLat"
+    near: Option<String>,   // "lat,lon", with radius_km
+    radius_km: Option<f64>,
 }
 
+async fn get_entity_features(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<GeoAreaParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let area = parse_area(&query.bbox, &query.near, query.radius_km)?;
+    let viewer = viewer_for(auth.as_ref(), host.0.id);
+    let features = domain.get_entity_features(host.0.id, &viewer, area.as_ref())?;
+    Ok(HttpResponse::Ok().content_type("application/geo+json").json(features))
+}
 
-async fn get_ledger(
+/// Flows summed per location and resource type; takes the ledger filters.
+async fn get_flow_features(
     domain: web::Data<LedgerDomain>,
     host: HostContext,
     query: web::Query<FlowQueryParams>,
-) -> impl Responder {
-    // parse dates
-    let since = query.start.as_ref()
-        .and_then(|s| NaiveDateTime::parse_from_str(&(s.clone() + " 00:00:00"), "%Y-%m-%d %H:%M:%S").ok());
-    let until = query.end.as_ref()
-        .and_then(|s| NaiveDateTime::parse_from_str(&(s.clone() + " 23:59:59"), "%Y-%m-%d %H:%M:%S").ok());
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let mut flow_query = query.to_flow_query(host.0.id, viewer_for(auth.as_ref(), host.0.id))?;
+    flow_query.limit = None;
+    flow_query.offset = None;
+    flow_query.cursor = None;
+    let features = domain.get_flow_features(&flow_query)?;
+    Ok(HttpResponse::Ok().content_type("application/geo+json").json(features))
+}
 
-    // parse direction
-    let direction = match query.direction.as_deref() {
-        Some("From") => FlowDirection::From,
-        Some("To") => FlowDirection::To,
-        _ => FlowDirection::Both,
-    };
+// -----------------------------
+// INTEGRITY ROUTES
+// -----------------------------
+/// Members only: every call rehashes the whole chain.
+async fn verify_ledger(
+    domain: web::Data<LedgerDomain>,
+    data: web::Data<AppState>,
+    host: HostContext,
+    auth: AuthContext,
+) -> Result<HttpResponse, AppError> {
+    if let FlowViewer::User { is_member: false, .. } = viewer_for(Some(&auth), host.0.id) {
+        return Err(AuthError::Forbidden("Only members can verify the ledger").into());
+    }
+    let report = domain.verify_ledger(host.0.id, &data.settings.ledger.checkpoint_secret)?;
+    Ok(HttpResponse::Ok().json(report))
+}
 
-    let flow_query = FlowQuery {
-        host: host.0.id,
-        entity: query.entity,
-        direction,
-        since,
-        until,
-        limit: query.limit,
-        offset: query.offset,
-    };
+async fn get_ledger_checkpoints(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let checkpoints = domain.get_ledger_checkpoints(host.0.id)?;
+    Ok(HttpResponse::Ok().json(checkpoints))
+}
 
-    match domain.get_ledger_summary(flow_query) {
-        Ok(summary) => HttpResponse::Ok().json(summary),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
-    }
+async fn create_ledger_checkpoint(
+    domain: web::Data<LedgerDomain>,
+    data: web::Data<AppState>,
+    host: HostContext,
+    auth: AuthContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+    let checkpoint = domain.create_ledger_checkpoint(host.0.id, &data.settings.ledger.checkpoint_secret)?;
+    Ok(HttpResponse::Ok().json(checkpoint))
 }
 
 // -----------------------------
-// SCOPE REGISTRATION
+// GOAL ROUTES
 // -----------------------------
-pub fn scope(parent_path: Vec<&str>) -> Scope {
-    let full_path = parent_path.join("/");
+#[derive(Debug, Deserialize)]
+pub struct GoalPayload {
+    pub entity_id: String,
+    pub label: String,
+    pub resource_type: String,
+    pub target_value: f32,
+    pub unit: String,
+    /// Inclusive dates; the goal runs from the start of the first day to
+    /// the end of the last.
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+}
 
-    web::scope("")
+impl GoalPayload {
+    fn into_new(self, host: i32, created_by: String) -> NewEntityGoal {
+        NewEntityGoal {
+            host_id: host,
+            entity_id: self.entity_id,
+            label: self.label,
+            resource_type: self.resource_type,
+            target_value: self.target_value,
+            unit: self.unit,
+            period_start: self.period_start.and_time(NaiveTime::MIN),
+            period_end: self.period_end.and_hms_opt(23, 59, 59).unwrap_or_default(),
+            created_by,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoalQuery {
+    pub entity: Option<String>,
+}
+
+async fn get_goals(
+    domain: web::Data<LedgerDomain>,
+    query: web::Query<GoalQuery>,
+    host: HostContext,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let viewer = viewer_for(auth.as_ref(), host.0.id);
+    let goals = domain.get_goals(host.0.id, query.entity.as_deref(), &viewer)?;
+    Ok(HttpResponse::Ok().json(goals))
+}
+
+async fn create_goal(
+    domain: web::Data<LedgerDomain>,
+    payload: web::Json<GoalPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let created_by = domain.get_user_entity_id(host.0.id, auth.user_id)?;
+    let goal = domain.create_goal(auth.user_id, is_admin, payload.into_inner().into_new(host.0.id, created_by))?;
+    Ok(HttpResponse::Ok().json(goal))
+}
+
+async fn update_goal(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<i32>,
+    payload: web::Json<GoalPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let new = payload.into_inner().into_new(host.0.id, String::new());
+    let goal = domain.update_goal(path.into_inner(), auth.user_id, is_admin, new)?;
+    Ok(HttpResponse::Ok().json(goal))
+}
+
+async fn delete_goal(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<i32>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    domain.delete_goal(host.0.id, path.into_inner(), auth.user_id, is_admin)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// -----------------------------
+// COMMITMENT ROUTES
+// -----------------------------
+#[derive(Debug, Deserialize)]
+pub struct CommitmentPayload {
+    pub from_entity: String,
+    pub to_entity: String,
+    pub resource_type: String,
+    pub quantity_value: f32,
+    pub quantity_unit: String,
+    pub due_date: NaiveDateTime,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub details: JsonField,
+}
+
+async fn create_commitment(
+    domain: web::Data<LedgerDomain>,
+    payload: web::Json<CommitmentPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let payload = payload.into_inner();
+    let new = NewCommitment {
+        id: Uuid::new_v4().to_string(),
+        host_id: host.0.id,
+        from_entity: domain.resolve_entity(&payload.from_entity, host.0.id)?,
+        to_entity: domain.resolve_entity(&payload.to_entity, host.0.id)?,
+        resource_type: payload.resource_type,
+        quantity_value: payload.quantity_value,
+        quantity_unit: payload.quantity_unit,
+        due_date: payload.due_date,
+        notes: payload.notes,
+        details: payload.details,
+        created_by: domain.get_user_entity_id(host.0.id, auth.user_id)?,
+    };
+
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let commitment = domain.create_commitment(auth.user_id, is_admin, new)?;
+    Ok(HttpResponse::Ok().json(commitment))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FulfilmentPayload {
+    pub flow_id: String,
+    /// In the commitment's unit. Defaults to as much as the flow covers.
+    pub quantity_value: Option<f32>,
+}
+
+async fn fulfil_commitment(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<FulfilmentPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let view = domain.fulfil_commitment(
+        host.0.id,
+        &path.into_inner(),
+        &payload.flow_id,
+        payload.quantity_value,
+        auth.user_id,
+        is_admin,
+    )?;
+    Ok(HttpResponse::Ok().json(view))
+}
+
+async fn cancel_commitment(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let commitment = domain.cancel_commitment(host.0.id, &path.into_inner(), auth.user_id, is_admin)?;
+    Ok(HttpResponse::Ok().json(commitment))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityCommitmentsQuery {
+    #[serde(default)]
+    pub include_closed: bool,
+}
+
+async fn get_entity_commitments(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    query: web::Query<EntityCommitmentsQuery>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let viewer = viewer_for(Some(&auth), host.0.id);
+    let commitments =
+        domain.get_entity_commitments(host.0.id, &path.into_inner(), query.include_closed, &viewer)?;
+    Ok(HttpResponse::Ok().json(commitments))
+}
+
+async fn get_project_commitments(
+    domain: web::Data<LedgerDomain>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let viewer = viewer_for(Some(&auth), host.0.id);
+    let report = domain.get_project_commitments(host.0.id, &viewer)?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// -----------------------------
+// FLOW TEMPLATE ROUTES
+// -----------------------------
+#[derive(Debug, Deserialize)]
+pub struct FlowTemplatePayload {
+    pub from_entity: String,
+    pub to_entity: String,
+    pub resource_type: String,
+    pub quantity_value: f32,
+    pub quantity_unit: String,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub details: JsonField,
+    pub recurrence: Recurrence,
+    /// Repeat every N days, weeks or months. Defaults to 1.
+    pub every: Option<i32>,
+    /// First occurrence. Defaults to now.
+    pub starts_at: Option<NaiveDateTime>,
+    pub ends_at: Option<NaiveDateTime>,
+}
+
+async fn create_flow_template(
+    domain: web::Data<LedgerDomain>,
+    payload: web::Json<FlowTemplatePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let payload = payload.into_inner();
+    let starts_at = payload.starts_at.unwrap_or_else(|| chrono::Utc::now().naive_utc());
+
+    let new = NewFlowTemplate {
+        id: Uuid::new_v4().to_string(),
+        host_id: host.0.id,
+        from_entity: domain.resolve_entity(&payload.from_entity, host.0.id)?,
+        to_entity: domain.resolve_entity(&payload.to_entity, host.0.id)?,
+        resource_type: payload.resource_type,
+        quantity_value: payload.quantity_value,
+        quantity_unit: payload.quantity_unit,
+        notes: payload.notes,
+        details: payload.details,
+        recurrence: payload.recurrence.value().to_string(),
+        every: payload.every.unwrap_or(1),
+        starts_at,
+        ends_at: payload.ends_at,
+        next_run: starts_at,
+        created_by: domain.get_user_entity_id(host.0.id, auth.user_id)?,
+    };
+
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let template = domain.create_flow_template(auth.user_id, is_admin, new)?;
+    Ok(HttpResponse::Ok().json(template))
+}
+
+async fn get_entity_templates(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let viewer = viewer_for(Some(&auth), host.0.id);
+    let templates = domain.get_flow_templates(host.0.id, &path.into_inner(), &viewer)?;
+    Ok(HttpResponse::Ok().json(templates))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TemplatePausePayload {
+    pub paused: bool,
+}
+
+async fn pause_flow_template(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<TemplatePausePayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    let is_admin = require_role_for_host(&auth, host.0.id, &[MemberRole::Admin]).is_ok();
+    let template = domain.set_flow_template_paused(
+        host.0.id,
+        &path.into_inner(),
+        auth.user_id,
+        is_admin,
+        payload.paused,
+    )?;
+    Ok(HttpResponse::Ok().json(template))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlowDecisionPayload {
+    pub reason: Option<String>,
+}
+
+async fn confirm_flow(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<FlowDecisionPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    decide_flow(domain, path, payload, auth, host, FlowActionType::Confirmation)
+}
+
+async fn dispute_flow(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<FlowDecisionPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    decide_flow(domain, path, payload, auth, host, FlowActionType::Dispute)
+}
+
+async fn reject_flow(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<FlowDecisionPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    decide_flow(domain, path, payload, auth, host, FlowActionType::Rejection)
+}
+
+fn decide_flow(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<FlowDecisionPayload>,
+    auth: AuthContext,
+    host: HostContext,
+    action: FlowActionType,
+) -> Result<HttpResponse, AppError> {
+    let recorded = domain.decide_flow(
+        host.0.id,
+        &path.into_inner(),
+        auth.user_id,
+        action,
+        payload.reason.as_deref(),
+    )?;
+    Ok(HttpResponse::Ok().json(recorded))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReverseFlowPayload {
+    pub reason: Option<String>,
+}
+
+async fn reverse_flow(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<ReverseFlowPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let correction = domain.reverse_flow(
+        host.0.id,
+        &path.into_inner(),
+        auth.user_id,
+        payload.reason.as_deref(),
+    )?;
+    Ok(HttpResponse::Ok().json(correction))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AmendFlowPayload {
+    #[serde(flatten)]
+    pub flow: NewFlowPayload,
+    pub reason: Option<String>,
+}
+
+async fn amend_flow(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    payload: web::Json<AmendFlowPayload>,
+    auth: AuthContext,
+    host: HostContext,
+) -> Result<HttpResponse, AppError> {
+    require_role_for_host(&auth, host.0.id, &[MemberRole::Admin])?;
+
+    let payload = payload.into_inner();
+    let flow = payload.flow;
+    let now = chrono::Utc::now().naive_utc();
+
+    let replacement = NewFlowEvent {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: flow.timestamp.unwrap_or(now),
+        recorded_at: now,
+        from_entity: domain.resolve_entity(&flow.from_entity, host.0.id)?,
+        to_entity: domain.resolve_entity(&flow.to_entity, host.0.id)?,
+        host_id: host.0.id,
+        resource_type: flow.resource_type,
+        quantity_value: flow.quantity_value,
+        quantity_unit: flow.quantity_unit,
+        notes: flow.notes,
+        details: flow.details,
+        created_by: String::new(), // set by the domain to the acting entity
+    };
+
+    let correction = domain.amend_flow(
+        host.0.id,
+        &path.into_inner(),
+        auth.user_id,
+        replacement,
+        payload.reason.as_deref(),
+    )?;
+    Ok(HttpResponse::Ok().json(correction))
+}
+
+async fn get_entity_flows(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    host: HostContext,
+    query: web::Query<FlowQueryParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let entity_id = path.into_inner();
+    let flow_query = query.to_flow_query(host.0.id, viewer_for(auth.as_ref(), host.0.id))?;
+    let flows = domain.get_flows_for_entity(&entity_id, flow_query)?;
+    Ok(HttpResponse::Ok().json(flows))
+}
+
+#[derive(Deserialize)]
+pub struct FlowQueryParams {
+    entity: Option<Uuid>,
+    direction: Option<String>, // "From", "To", "Both"
+    start: Option<String>,     // YYYY-MM-DD
+    end: Option<String>,       // YYYY-MM-DD
+    limit: Option<i64>,
+    offset: Option<i64>,
+    hide_reversed: Option<bool>,
+    sort: Option<String>,   // "newest" (default) | "oldest"
+    cursor: Option<String>, // `next_cursor` from the previous page
+    resource_type: Option<String>,
+    entity_type: Option<String>,
+    created_by: Option<String>,
+    bbox: Option<String>,      // "minLon,minLat,maxLon,maxLat"
+    near: Option<String>,      // "lat,lon", with radius_km
+    radius_km: Option<f64>,
+}
+
+impl FlowQueryParams {
+    fn to_flow_query(&self, host: i32, viewer: FlowViewer) -> Result<FlowQuery, AppError> {
+        let (since, until) = parse_date_range(&self.start, &self.end);
+
+        let direction = match self.direction.as_deref() {
+            Some("From") => FlowDirection::From,
+            Some("To") => FlowDirection::To,
+            _ => FlowDirection::Both,
+        };
+        let sort = match self.sort.as_deref() {
+            None => FlowSort::Newest,
+            Some(s) => FlowSort::from_value(s)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown sort '{}'", s)))?,
+        };
+        let cursor = match self.cursor.as_deref() {
+            None => None,
+            Some(c) => Some(
+                FlowCursor::decode(c).ok_or_else(|| AppError::BadRequest("Invalid cursor".into()))?,
+            ),
+        };
+
+        Ok(FlowQuery {
+            host,
+            entity: self.entity,
+            direction,
+            since,
+            until,
+            limit: self.limit,
+            offset: self.offset,
+            hide_reversed: self.hide_reversed.unwrap_or(false),
+            sort,
+            cursor,
+            resource_type: self.resource_type.clone(),
+            entity_type: self.entity_type.clone(),
+            created_by: self.created_by.clone(),
+            area: parse_area(&self.bbox, &self.near, self.radius_km)?,
+            viewer,
+        })
+    }
+}
+
+async fn get_ledger(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<FlowQueryParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let flow_query = query.to_flow_query(host.0.id, viewer_for(auth.as_ref(), host.0.id))?;
+    let summary = domain.get_ledger_summary(flow_query)?;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+/// Host admins see everything; other signed-in users see by membership
+/// and entity links; everyone else sees only public rows.
+fn viewer_for(auth: Option<&AuthContext>, host: i32) -> FlowViewer {
+    match auth {
+        None => FlowViewer::Anonymous,
+        Some(auth) if require_role_for_host(auth, host, &[MemberRole::Admin]).is_ok() => FlowViewer::System,
+        Some(auth) => FlowViewer::User {
+            user_id: auth.user_id,
+            is_member: auth
+                .memberships
+                .iter()
+                .any(|m| m.host_id == host && m.role != MemberRole::Guest),
+        },
+    }
+}
+
+/// Parses `YYYY-MM-DD` start/end params into an inclusive day range.
+fn parse_date_range(
+    start: &Option<String>,
+    end: &Option<String>,
+) -> (Option<NaiveDateTime>, Option<NaiveDateTime>) {
+    let since = start.as_ref()
+        .and_then(|s| NaiveDateTime::parse_from_str(&(s.clone() + " 00:00:00"), "%Y-%m-%d %H:%M:%S").ok());
+    let until = end.as_ref()
+        .and_then(|s| NaiveDateTime::parse_from_str(&(s.clone() + " 23:59:59"), "%Y-%m-%d %H:%M:%S").ok());
+    (since, until)
+}
+
+/// `bbox` wins over `near`; a radius without a centre is an error.
+fn parse_area(
+    bbox: &Option<String>,
+    near: &Option<String>,
+    radius_km: Option<f64>,
+) -> Result<Option<GeoArea>, AppError> {
+    let area = match (bbox, near, radius_km) {
+        (Some(bbox), _, _) => GeoArea::parse_bbox(bbox).map(Some),
+        (None, Some(near), Some(km)) => GeoPoint::parse(near).and_then(|c| GeoArea::radius(c, km)).map(Some),
+        (None, Some(_), None) => Err("near needs radius_km".to_string()),
+        (None, None, Some(_)) => Err("radius_km needs near".to_string()),
+        (None, None, None) => Ok(None),
+    };
+    area.map_err(AppError::BadRequest)
+}
+
+#[derive(Deserialize)]
+pub struct DateRangeParams {
+    start: Option<String>, // YYYY-MM-DD
+    end: Option<String>,   // YYYY-MM-DD
+}
+
+impl DateRangeParams {
+    fn to_flow_query(&self, host: i32) -> FlowQuery {
+        let (since, until) = parse_date_range(&self.start, &self.end);
+        let mut flow_query = FlowQuery::new(host);
+        flow_query.since = since;
+        flow_query.until = until;
+        flow_query
+    }
+}
+
+// -----------------------------
+// VITAL SIGNS ROUTES
+// -----------------------------
+async fn get_vital_signs(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<DateRangeParams>,
+) -> Result<HttpResponse, AppError> {
+    let signs = domain.get_vital_signs(&query.to_flow_query(host.0.id))?;
+    Ok(HttpResponse::Ok().json(signs))
+}
+
+async fn get_public_summary(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<DateRangeParams>,
+) -> Result<HttpResponse, AppError> {
+    let summary = domain.get_public_summary(&query.to_flow_query(host.0.id))?;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+async fn get_balances(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<DateRangeParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let mut flow_query = query.to_flow_query(host.0.id);
+    flow_query.viewer = viewer_for(auth.as_ref(), host.0.id);
+    let balances = domain.get_entity_balances(&flow_query)?;
+    Ok(HttpResponse::Ok().json(balances))
+}
+
+#[derive(Deserialize)]
+pub struct SeriesParams {
+    start: Option<String>,  // YYYY-MM-DD
+    end: Option<String>,    // YYYY-MM-DD
+    bucket: Option<String>, // "day" | "week" (default) | "month"
+}
+
+async fn get_activity_series(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<SeriesParams>,
+) -> Result<HttpResponse, AppError> {
+    let bucket = match query.bucket.as_deref() {
+        None => TimeBucket::Week,
+        Some(b) => TimeBucket::from_value(b)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown bucket '{}'", b)))?,
+    };
+    let (since, until) = parse_date_range(&query.start, &query.end);
+    let mut flow_query = FlowQuery::new(host.0.id);
+    flow_query.since = since;
+    flow_query.until = until;
+
+    let series = domain.get_activity_series(&flow_query, bucket)?;
+    Ok(HttpResponse::Ok().json(series))
+}
+
+#[derive(Deserialize)]
+pub struct FlowGraphParams {
+    entity: Option<Uuid>,
+    depth: Option<u32>,
+    start: Option<String>, // YYYY-MM-DD
+    end: Option<String>,   // YYYY-MM-DD
+}
+
+async fn get_flow_graph(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<FlowGraphParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let (since, until) = parse_date_range(&query.start, &query.end);
+    let mut flow_query = FlowQuery::new(host.0.id);
+    flow_query.viewer = viewer_for(auth.as_ref(), host.0.id);
+    flow_query.entity = query.entity;
+    flow_query.since = since;
+    flow_query.until = until;
+
+    let graph = domain.get_flow_graph(&flow_query, query.depth.unwrap_or(1))?;
+    Ok(HttpResponse::Ok().json(graph))
+}
+
+#[derive(Deserialize)]
+pub struct StatementParams {
+    start: Option<String>,  // YYYY-MM-DD
+    end: Option<String>,    // YYYY-MM-DD
+    format: Option<String>, // "json" (default) | "csv"
+}
+
+async fn get_entity_statement(
+    domain: web::Data<LedgerDomain>,
+    path: web::Path<String>,
+    host: HostContext,
+    query: web::Query<StatementParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let entity_id = Uuid::parse_str(&path.into_inner())
+        .map_err(|e| AppError::BadRequest(format!("Bad UUID: {}", e)))?;
+    let (since, until) = parse_date_range(&query.start, &query.end);
+
+    let mut flow_query = FlowQuery::new(host.0.id).entity(entity_id);
+    flow_query.viewer = viewer_for(auth.as_ref(), host.0.id);
+    flow_query.since = since;
+    flow_query.until = until;
+
+    let statement = domain.get_entity_statement(&flow_query)?;
+
+    match query.format.as_deref() {
+        Some("csv") => {
+            let filename = format!("statement-{}.csv", entity_id);
+            Ok(HttpResponse::Ok()
+                .content_type("text/csv")
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"{}\"", filename),
+                ))
+                .body(statement.to_csv()?))
+        }
+        _ => Ok(HttpResponse::Ok().json(statement)),
+    }
+}
+
+// -----------------------------
+// EXPORT ROUTES
+// -----------------------------
+#[derive(Deserialize)]
+pub struct ExportParams {
+    entity: Option<Uuid>,
+    direction: Option<String>, // "From", "To", "Both"
+    start: Option<String>,     // YYYY-MM-DD
+    end: Option<String>,       // YYYY-MM-DD
+    format: Option<String>,    // "csv" (default) | "ndjson" | "jsonld"
+}
+
+async fn export_flows(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<ExportParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let viewer = viewer_for(auth.as_ref(), host.0.id);
+    export(domain, host, query.into_inner(), viewer, ExportKind::Flows)
+}
+
+async fn export_entities(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    query: web::Query<ExportParams>,
+    auth: Option<AuthContext>,
+) -> Result<HttpResponse, AppError> {
+    let viewer = viewer_for(auth.as_ref(), host.0.id);
+    export(domain, host, query.into_inner(), viewer, ExportKind::Entities)
+}
+
+/// Streams the export a page at a time so large ledgers never sit in memory.
+fn export(
+    domain: web::Data<LedgerDomain>,
+    host: HostContext,
+    params: ExportParams,
+    viewer: FlowViewer,
+    kind: ExportKind,
+) -> Result<HttpResponse, AppError> {
+    let format = match params.format.as_deref() {
+        None => ExportFormat::Csv,
+        Some(f) => ExportFormat::from_value(f)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown export format '{}'", f)))?,
+    };
+
+    let (since, until) = parse_date_range(&params.start, &params.end);
+    let mut flow_query = FlowQuery::new(host.0.id);
+    flow_query.viewer = viewer;
+    flow_query.entity = params.entity;
+    flow_query.direction = match params.direction.as_deref() {
+        Some("From") => FlowDirection::From,
+        Some("To") => FlowDirection::To,
+        _ => FlowDirection::Both,
+    };
+    flow_query.since = since;
+    flow_query.until = until;
+
+    let pages = futures_util::stream::unfold(Some((None::<String>, true)), move |state| {
+        let domain = domain.clone();
+        let flow_query = flow_query.clone();
+        async move {
+            let (after, first) = state?;
+            match domain.export_page(kind, format, &flow_query, after.as_deref(), first) {
+                Ok(Some((chunk, next))) => {
+                    let first = first && chunk.is_empty();
+                    Some((Ok(web::Bytes::from(chunk)), Some((Some(next), first))))
+                }
+                Ok(None) => None,
+                Err(e) => Some((Err(actix_web::Error::from(e)), None)),
+            }
+        }
+    });
+    let header = ExportService::header(kind, format);
+    let footer = ExportService::footer(format);
+    let body = futures_util::stream::once(async move { Ok::<_, actix_web::Error>(web::Bytes::from(header)) })
+        .chain(pages)
+        .chain(futures_util::stream::once(async move { Ok(web::Bytes::from(footer)) }));
+
+    let name = match kind {
+        ExportKind::Flows => "flows",
+        ExportKind::Entities => "entities",
+    };
+    let filename = format!("{}-{}.{}", host.0.slug, name, format.extension());
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .streaming(body))
+}
+
+// -----------------------------
+// SCOPE REGISTRATION
+// -----------------------------
+pub fn scope(parent_path: Vec<&str>) -> Scope {
+    let full_path = parent_path.join("/");
+
+    web::scope("")
         // Entities
         .service(register(
             "create_entity",
@@ -294,6 +1579,158 @@ pub fn scope(parent_path: Vec<&str>) -> Scope {
             get_entity,
             crate::types::MemberRole::Public,
         ))
+        .service(register(
+            "ledger_replace_entity",
+            Method::PUT,
+            &full_path,
+            "entity/{id}",
+            replace_entity,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_patch_entity",
+            Method::PATCH,
+            &full_path,
+            "entity/{id}",
+            patch_entity,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_delete_entity",
+            Method::DELETE,
+            &full_path,
+            "entity/{id}",
+            delete_entity,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_entity_types",
+            Method::GET,
+            &full_path,
+            "entity-types",
+            get_entity_types,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_create_entity_type",
+            Method::POST,
+            &full_path,
+            "entity-types",
+            create_entity_type,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_update_entity_type",
+            Method::PUT,
+            &full_path,
+            "entity-types/{id}",
+            update_entity_type,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_delete_entity_type",
+            Method::DELETE,
+            &full_path,
+            "entity-types/{id}",
+            delete_entity_type,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_resource_types",
+            Method::GET,
+            &full_path,
+            "resources",
+            get_resource_types,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_create_resource_type",
+            Method::POST,
+            &full_path,
+            "resources",
+            create_resource_type,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_update_resource_type",
+            Method::PUT,
+            &full_path,
+            "resources/{id}",
+            update_resource_type,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_delete_resource_type",
+            Method::DELETE,
+            &full_path,
+            "resources/{id}",
+            delete_resource_type,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_entity_people",
+            Method::GET,
+            &full_path,
+            "entity/{id}/people",
+            get_entity_people,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_invite_to_entity",
+            Method::POST,
+            &full_path,
+            "entity/{id}/people/invite",
+            invite_to_entity,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_accept_entity_invite",
+            Method::POST,
+            &full_path,
+            "entity/{id}/people/accept",
+            accept_entity_invite,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_decline_entity_invite",
+            Method::POST,
+            &full_path,
+            "entity/{id}/people/decline",
+            decline_entity_invite,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_set_entity_role",
+            Method::PATCH,
+            &full_path,
+            "entity/{id}/people/{user_id}",
+            set_entity_role,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_add_entity_alias",
+            Method::POST,
+            &full_path,
+            "entity/{id}/aliases",
+            add_entity_alias,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_merge_entity",
+            Method::POST,
+            &full_path,
+            "entity/{id}/merge",
+            merge_entity,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_duplicate_entities",
+            Method::GET,
+            &full_path,
+            "entities/duplicates",
+            get_duplicate_entities,
+            crate::types::MemberRole::Admin,
+        ))
         // Flows
         .service(register(
             "submit_flow",
@@ -303,6 +1740,206 @@ pub fn scope(parent_path: Vec<&str>) -> Scope {
             submit_flow,
             crate::types::MemberRole::Admin,
         ))
+        .service(register(
+            "ledger_confirm_flow",
+            Method::POST,
+            &full_path,
+            "flow/{id}/confirm",
+            confirm_flow,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_dispute_flow",
+            Method::POST,
+            &full_path,
+            "flow/{id}/dispute",
+            dispute_flow,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_reject_flow",
+            Method::POST,
+            &full_path,
+            "flow/{id}/reject",
+            reject_flow,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_reverse_flow",
+            Method::POST,
+            &full_path,
+            "flow/{id}/reverse",
+            reverse_flow,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_amend_flow",
+            Method::POST,
+            &full_path,
+            "flow/{id}/amend",
+            amend_flow,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_entity_location",
+            Method::PUT,
+            &full_path,
+            "entity/{id}/location",
+            set_entity_location,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_flow_location",
+            Method::PUT,
+            &full_path,
+            "flow/{id}/location",
+            set_flow_location,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_geo_entities",
+            Method::GET,
+            &full_path,
+            "geo/entities",
+            get_entity_features,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_geo_flows",
+            Method::GET,
+            &full_path,
+            "geo/flows",
+            get_flow_features,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_verify",
+            Method::GET,
+            &full_path,
+            "integrity",
+            verify_ledger,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_get_checkpoints",
+            Method::GET,
+            &full_path,
+            "checkpoints",
+            get_ledger_checkpoints,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_create_checkpoint",
+            Method::POST,
+            &full_path,
+            "checkpoints",
+            create_ledger_checkpoint,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_get_goals",
+            Method::GET,
+            &full_path,
+            "goals",
+            get_goals,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_create_goal",
+            Method::POST,
+            &full_path,
+            "goals",
+            create_goal,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_update_goal",
+            Method::PUT,
+            &full_path,
+            "goals/{id}",
+            update_goal,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_delete_goal",
+            Method::DELETE,
+            &full_path,
+            "goals/{id}",
+            delete_goal,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_create_commitment",
+            Method::POST,
+            &full_path,
+            "commitments",
+            create_commitment,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_project_commitments",
+            Method::GET,
+            &full_path,
+            "commitments/projects",
+            get_project_commitments,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_fulfil_commitment",
+            Method::POST,
+            &full_path,
+            "commitments/{id}/fulfilments",
+            fulfil_commitment,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_cancel_commitment",
+            Method::PUT,
+            &full_path,
+            "commitments/{id}/cancel",
+            cancel_commitment,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_entity_commitments",
+            Method::GET,
+            &full_path,
+            "entity/{id}/commitments",
+            get_entity_commitments,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_create_flow_template",
+            Method::POST,
+            &full_path,
+            "templates",
+            create_flow_template,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_pause_flow_template",
+            Method::PUT,
+            &full_path,
+            "templates/{id}/pause",
+            pause_flow_template,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_entity_templates",
+            Method::GET,
+            &full_path,
+            "entity/{id}/templates",
+            get_entity_templates,
+            crate::types::MemberRole::Member,
+        ))
+        .service(register(
+            "ledger_flow_visibility",
+            Method::PUT,
+            &full_path,
+            "flow/{id}/visibility",
+            set_flow_visibility,
+            crate::types::MemberRole::Member,
+        ))
         .service(register(
             "get_entity_flows",
             Method::GET,
@@ -319,6 +1956,22 @@ pub fn scope(parent_path: Vec<&str>) -> Scope {
             get_ledger,
             crate::types::MemberRole::Public,
         ))
+        .service(register(
+            "ledger_export_flows",
+            Method::GET,
+            &full_path,
+            "export/flows",
+            export_flows,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_export_entities",
+            Method::GET,
+            &full_path,
+            "export/entities",
+            export_entities,
+            crate::types::MemberRole::Public,
+        ))
         .service(register(
             "ledger_submit_bulk_flow",
             Method::POST,
@@ -327,6 +1980,22 @@ pub fn scope(parent_path: Vec<&str>) -> Scope {
             submit_bulk_flows,
             crate::types::MemberRole::Admin,
         ))
+        .service(register(
+            "ledger_import_entities",
+            Method::POST,
+            &full_path,
+            "import/entities",
+            import_entities,
+            crate::types::MemberRole::Admin,
+        ))
+        .service(register(
+            "ledger_import_flows",
+            Method::POST,
+            &full_path,
+            "import/flows",
+            import_flows,
+            crate::types::MemberRole::Admin,
+        ))
         .service(register(
             "submit_bulk_entities",
             Method::POST,
@@ -335,6 +2004,54 @@ pub fn scope(parent_path: Vec<&str>) -> Scope {
             submit_bulk_entities,
             crate::types::MemberRole::Admin,
         ))
+        .service(register(
+            "ledger_vital_signs",
+            Method::GET,
+            &full_path,
+            "vital-signs",
+            get_vital_signs,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_balances",
+            Method::GET,
+            &full_path,
+            "balances",
+            get_balances,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_activity_series",
+            Method::GET,
+            &full_path,
+            "series",
+            get_activity_series,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_public_summary",
+            Method::GET,
+            &full_path,
+            "public/summary",
+            get_public_summary,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_flow_graph",
+            Method::GET,
+            &full_path,
+            "graph",
+            get_flow_graph,
+            crate::types::MemberRole::Public,
+        ))
+        .service(register(
+            "ledger_entity_statement",
+            Method::GET,
+            &full_path,
+            "entity/{id}/statement",
+            get_entity_statement,
+            crate::types::MemberRole::Public,
+        ))
         //
         //submit_bulk_flows
 }
\ No newline at end of file
@@ -207,6 +207,7 @@ where
     }
 
     actix_web::web::resource(path)
+        .guard(method.to_guard())
         .route(method.to_route().to(handler))
 }
 
@@ -1,5 +1,33 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    commitment_fulfilments (commitment_id, flow_id) {
+        commitment_id -> Text,
+        flow_id -> Text,
+        quantity_value -> Float,
+        created_by -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    commitments (id) {
+        id -> Text,
+        host_id -> Integer,
+        from_entity -> Text,
+        to_entity -> Text,
+        resource_type -> Text,
+        quantity_value -> Float,
+        quantity_unit -> Text,
+        due_date -> Timestamp,
+        notes -> Nullable<Text>,
+        details -> Text,
+        status -> Text,
+        created_by -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     completed_offers (id) {
         id -> Integer,
@@ -36,6 +64,57 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    draft_comments (id) {
+        id -> Integer,
+        draft_id -> Integer,
+        host_id -> Integer,
+        parent_id -> Nullable<Integer>,
+        revision -> Nullable<Integer>,
+        line_start -> Nullable<Integer>,
+        line_end -> Nullable<Integer>,
+        field -> Nullable<Text>,
+        body -> Text,
+        author_id -> Integer,
+        created_at -> Timestamp,
+        resolved_by -> Nullable<Integer>,
+        resolved_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    draft_revisions (id) {
+        id -> Integer,
+        draft_id -> Integer,
+        host_id -> Integer,
+        revision -> Integer,
+        title -> Text,
+        description -> Nullable<Text>,
+        tags -> Nullable<Text>,
+        author -> Nullable<Text>,
+        meta -> Nullable<Text>,
+        body_md -> Text,
+        restored_from -> Nullable<Integer>,
+        created_by -> Integer,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    draft_transitions (id) {
+        id -> Integer,
+        draft_id -> Integer,
+        host_id -> Integer,
+        action -> Text,
+        from_status -> Text,
+        to_status -> Text,
+        actor_id -> Integer,
+        notes -> Nullable<Text>,
+        created_at -> Timestamp,
+        commit_id -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     drafts (id) {
         id -> Integer,
@@ -54,6 +133,9 @@ diesel::table! {
         review_notes -> Nullable<Text>,
         details -> Nullable<Text>,
         host_id -> Integer,
+        published_path -> Nullable<Text>,
+        published_commit -> Nullable<Text>,
+        published_at -> Nullable<Timestamp>,
     }
 }
 
@@ -78,6 +160,11 @@ diesel::table! {
         created_by -> Text,
         created_at -> Timestamp,
         details -> Text,
+        archived_at -> Nullable<Timestamp>,
+        visibility -> Text,
+        geometry -> Nullable<Text>,
+        latitude -> Nullable<Double>,
+        longitude -> Nullable<Double>,
     }
 }
 
@@ -88,6 +175,34 @@ diesel::table! {
         alias -> Text,
         created_by -> Text,
         created_at -> Timestamp,
+        normalized -> Text,
+    }
+}
+
+diesel::table! {
+    entity_goals (id) {
+        id -> Integer,
+        host_id -> Integer,
+        entity_id -> Text,
+        label -> Text,
+        resource_type -> Text,
+        target_value -> Float,
+        unit -> Text,
+        period_start -> Timestamp,
+        period_end -> Timestamp,
+        created_by -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    entity_types (id) {
+        id -> Integer,
+        host_id -> Integer,
+        key -> Text,
+        label -> Text,
+        fields -> Text,
+        created_at -> Timestamp,
     }
 }
 
@@ -122,6 +237,9 @@ diesel::table! {
         actor_entity -> Text,
         timestamp -> Timestamp,
         details -> Text,
+        chain_seq -> Nullable<Integer>,
+        prev_hash -> Nullable<Text>,
+        hash -> Nullable<Text>,
     }
 }
 
@@ -139,6 +257,36 @@ diesel::table! {
         notes -> Nullable<Text>,
         details -> Text,
         created_by -> Text,
+        visibility -> Text,
+        latitude -> Nullable<Double>,
+        longitude -> Nullable<Double>,
+        chain_seq -> Nullable<Integer>,
+        prev_hash -> Nullable<Text>,
+        hash -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    flow_templates (id) {
+        id -> Text,
+        host_id -> Integer,
+        from_entity -> Text,
+        to_entity -> Text,
+        resource_type -> Text,
+        quantity_value -> Float,
+        quantity_unit -> Text,
+        notes -> Nullable<Text>,
+        details -> Text,
+        recurrence -> Text,
+        every -> Integer,
+        starts_at -> Timestamp,
+        ends_at -> Nullable<Timestamp>,
+        next_run -> Timestamp,
+        run_count -> Integer,
+        last_run_at -> Nullable<Timestamp>,
+        paused -> Bool,
+        created_by -> Text,
+        created_at -> Timestamp,
     }
 }
 
@@ -154,6 +302,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    ledger_checkpoints (id) {
+        id -> Integer,
+        host_id -> Integer,
+        chain_seq -> Integer,
+        head_hash -> Text,
+        signature -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     mailing_list_subscribers (id) {
         id -> Integer,
@@ -273,6 +432,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    resource_types (id) {
+        id -> Integer,
+        host_id -> Integer,
+        key -> Text,
+        label -> Text,
+        default_unit -> Text,
+        units -> Text,
+        is_monetary -> Bool,
+        is_labor -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     roles (id) {
         id -> Integer,
@@ -357,18 +530,28 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(commitment_fulfilments -> commitments (commitment_id));
+diesel::joinable!(commitment_fulfilments -> flow_events (flow_id));
+diesel::joinable!(commitments -> hosts (host_id));
 diesel::joinable!(completed_offers -> offers (offer_id));
 diesel::joinable!(completed_offers -> users (reviewer_id));
 diesel::joinable!(contribution_events -> contributors (contributor_id));
 diesel::joinable!(contribution_events -> effort_contexts (context_id));
 diesel::joinable!(contributors -> users (user_id));
+diesel::joinable!(draft_comments -> drafts (draft_id));
+diesel::joinable!(draft_revisions -> drafts (draft_id));
+diesel::joinable!(draft_transitions -> drafts (draft_id));
 diesel::joinable!(entities -> hosts (host_id));
 diesel::joinable!(entity_aliases -> entities (entity_id));
+diesel::joinable!(entity_goals -> entities (entity_id));
+diesel::joinable!(entity_goals -> hosts (host_id));
+diesel::joinable!(entity_types -> hosts (host_id));
 diesel::joinable!(entity_users -> entities (entity_id));
 diesel::joinable!(entity_users -> users (user_id));
 diesel::joinable!(flow_actions -> entities (actor_entity));
 diesel::joinable!(flow_actions -> flow_events (flow_id));
 diesel::joinable!(flow_events -> hosts (host_id));
+diesel::joinable!(ledger_checkpoints -> hosts (host_id));
 diesel::joinable!(mailing_list_subscribers -> hosts (host_id));
 diesel::joinable!(memberships -> hosts (host_id));
 diesel::joinable!(memberships -> roles (role_id));
@@ -376,6 +559,8 @@ diesel::joinable!(memberships -> users (user_id));
 diesel::joinable!(offers -> users (user_id));
 diesel::joinable!(registration -> events (event_id));
 diesel::joinable!(registration -> users (user_id));
+diesel::joinable!(flow_templates -> hosts (host_id));
+diesel::joinable!(resource_types -> hosts (host_id));
 diesel::joinable!(sms_replies -> registration (registration_id));
 diesel::joinable!(ticket -> events (event_id));
 diesel::joinable!(ticket -> registration (registration_id));
@@ -385,18 +570,27 @@ diesel::joinable!(wants_to_contribute -> offers (offer_id));
 diesel::joinable!(wants_to_contribute -> users (helper_user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    commitment_fulfilments,
+    commitments,
     completed_offers,
     contribution_events,
     contributors,
+    draft_comments,
+    draft_revisions,
+    draft_transitions,
     drafts,
     effort_contexts,
     entities,
     entity_aliases,
+    entity_goals,
+    entity_types,
     entity_users,
     events,
     flow_actions,
     flow_events,
+    flow_templates,
     hosts,
+    ledger_checkpoints,
     mailing_list_subscribers,
     memberships,
     offers,
@@ -405,6 +599,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     rating_summary,
     recipe_drafts,
     registration,
+    resource_types,
     roles,
     sms_replies,
     ticket,
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDateTime;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use serde::Serialize;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::flow_events::FlowEvent;
+use crate::models::ledger_views::CONFIRMED;
+use crate::schema::flow_events;
+use crate::services::ledger_service::{EntityRef, LedgerService};
+use crate::types::flow_query::FlowQuery;
+
+/// Balances are always kept per (resource_type, quantity_unit) so that
+/// hours and dollars are never summed together.
+pub type BalanceKey = (String, String);
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BalanceSummary {
+    pub resource_type: String,
+    pub quantity_unit: String,
+    pub opening: f64,
+    pub inflows: f64,
+    pub outflows: f64,
+    pub closing: f64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct StatementLine {
+    pub id: String,
+    pub timestamp: NaiveDateTime,
+    pub direction: &'static str, // "in" | "out"
+    pub counterparty: EntityRef,
+    pub resource_type: String,
+    pub quantity_unit: String,
+    pub quantity_value: f64,
+    pub running_balance: f64,
+    pub notes: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct EntityStatement {
+    pub entity: EntityRef,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub balances: Vec<BalanceSummary>,
+    pub lines: Vec<StatementLine>,
+}
+
+pub struct BalanceService;
+
+impl BalanceService {
+    /// Builds a statement for `flow_query.entity` over [since, until].
+    /// Everything before `since` rolls into the opening balance.
+    pub fn get_entity_statement(
+        conn: &mut DbConn,
+        flow_query: &FlowQuery,
+    ) -> Result<EntityStatement, AppError> {
+        let entity_id = flow_query
+            .entity
+            .ok_or_else(|| AppError::BadRequest("Statement requires an entity".into()))?
+            .to_string();
+
+        let entity = LedgerService::get_entity(conn, &entity_id)?;
+        if entity.host_id != flow_query.host {
+            return Err(AppError::NotFound(format!("Entity {}", entity_id)));
+        }
+
+        // Opening balances need all history, so drop `since` for the load
+        // and split on it below. Only confirmed flows count, as in balances.
+        let history = FlowQuery {
+            since: None,
+            limit: None,
+            offset: None,
+            ..flow_query.clone()
+        }
+        .both();
+
+        let flows: Vec<FlowEvent> = history
+            .apply(flow_events::table.into_boxed())
+            .filter(sql::<Bool>(CONFIRMED))
+            .order((flow_events::timestamp.asc(), flow_events::id.asc()))
+            .select(FlowEvent::as_select())
+            .load(conn)?;
+
+        let mut counterparty_ids: Vec<String> = flows
+            .iter()
+            .map(|f| counterparty_of(f, &entity_id).to_string())
+            .collect();
+        counterparty_ids.sort();
+        counterparty_ids.dedup();
+        let counterparties: HashMap<String, EntityRef> =
+            LedgerService::get_all_entities(conn, counterparty_ids)?
+                .into_iter()
+                .map(|e| (e.id.clone(), e))
+                .collect();
+
+        let mut balances: BTreeMap<BalanceKey, BalanceSummary> = BTreeMap::new();
+        let mut lines = Vec::new();
+
+        for flow in &flows {
+            let key = (flow.resource_type.clone(), flow.quantity_unit.clone());
+            let summary = balances.entry(key).or_insert_with(|| BalanceSummary {
+                resource_type: flow.resource_type.clone(),
+                quantity_unit: flow.quantity_unit.clone(),
+                opening: 0.0,
+                inflows: 0.0,
+                outflows: 0.0,
+                closing: 0.0,
+            });
+
+            let inbound = flow.to_entity == entity_id;
+            let value = flow.quantity_value as f64;
+            let in_period = flow_query.since.is_none_or(|since| flow.timestamp >= since);
+
+            if !in_period {
+                summary.opening += if inbound { value } else { -value };
+                summary.closing = summary.opening;
+                continue;
+            }
+
+            if inbound {
+                summary.inflows += value;
+                summary.closing += value;
+            } else {
+                summary.outflows += value;
+                summary.closing -= value;
+            }
+
+            let counterparty_id = counterparty_of(flow, &entity_id);
+            let counterparty = counterparties
+                .get(counterparty_id)
+                .cloned()
+                .unwrap_or_else(|| EntityRef {
+                    id: counterparty_id.to_string(),
+                    name: counterparty_id.to_string(),
+                    entity_type: "unknown".to_string(),
+                });
+
+            lines.push(StatementLine {
+                id: flow.id.clone(),
+                timestamp: flow.timestamp,
+                direction: if inbound { "in" } else { "out" },
+                counterparty,
+                resource_type: flow.resource_type.clone(),
+                quantity_unit: flow.quantity_unit.clone(),
+                quantity_value: value,
+                running_balance: summary.closing,
+                notes: flow.notes.clone(),
+            });
+        }
+
+        Ok(EntityStatement {
+            entity: EntityRef {
+                id: entity.id,
+                name: entity.name,
+                entity_type: entity.entity_type,
+            },
+            since: flow_query.since,
+            until: flow_query.until,
+            balances: balances.into_values().collect(),
+            lines,
+        })
+    }
+}
+
+fn counterparty_of<'a>(flow: &'a FlowEvent, entity_id: &str) -> &'a str {
+    if flow.to_entity == entity_id {
+        &flow.from_entity
+    } else {
+        &flow.to_entity
+    }
+}
+
+impl EntityStatement {
+    /// One row per statement line, followed by one summary row per balance.
+    pub fn to_csv(&self) -> Result<String, AppError> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        let internal = |e: csv::Error| AppError::Internal(e.to_string());
+
+        writer
+            .write_record([
+                "row_type",
+                "id",
+                "timestamp",
+                "direction",
+                "counterparty",
+                "resource_type",
+                "quantity_unit",
+                "quantity_value",
+                "running_balance",
+                "opening",
+                "inflows",
+                "outflows",
+                "closing",
+                "notes",
+            ])
+            .map_err(internal)?;
+
+        for line in &self.lines {
+            writer
+                .write_record([
+                    "flow",
+                    &line.id,
+                    &line.timestamp.to_string(),
+                    line.direction,
+                    &line.counterparty.name,
+                    &line.resource_type,
+                    &line.quantity_unit,
+                    &line.quantity_value.to_string(),
+                    &line.running_balance.to_string(),
+                    "",
+                    "",
+                    "",
+                    "",
+                    line.notes.as_deref().unwrap_or(""),
+                ])
+                .map_err(internal)?;
+        }
+
+        for b in &self.balances {
+            writer
+                .write_record([
+                    "balance",
+                    "",
+                    "",
+                    "",
+                    "",
+                    &b.resource_type,
+                    &b.quantity_unit,
+                    "",
+                    "",
+                    &b.opening.to_string(),
+                    &b.inflows.to_string(),
+                    &b.outflows.to_string(),
+                    &b.closing.to_string(),
+                    "",
+                ])
+                .map_err(internal)?;
+        }
+
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        String::from_utf8(bytes).map_err(|e| AppError::Internal(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::{NewFlowEvent, create_flow_event};
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::JsonField;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    fn flow(conn: &mut DbConn, from: &str, to: &str, qty: f32, unit: &str, at: NaiveDateTime) {
+        let new = NewFlowEvent {
+            id: Uuid::new_v4().to_string(),
+            timestamp: at,
+            recorded_at: at,
+            from_entity: from.to_string(),
+            to_entity: to.to_string(),
+            host_id: 1,
+            resource_type: "labor_time".to_string(),
+            quantity_value: qty,
+            quantity_unit: unit.to_string(),
+            notes: None,
+            details: JsonField::default(),
+            created_by: "test".to_string(),
+        };
+        create_flow_event(conn, &new).unwrap();
+    }
+
+    #[test]
+    fn statement_splits_opening_and_period_per_unit() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+
+        flow(&mut conn, &jane, &garden, 4.0, "hours", now - Duration::days(40));
+        flow(&mut conn, &jane, &garden, 2.0, "hours", now - Duration::days(5));
+        flow(&mut conn, &garden, &jane, 1.0, "hours", now - Duration::days(1));
+        flow(&mut conn, &jane, &garden, 30.0, "minutes", now - Duration::days(1));
+
+        let query = FlowQuery::new(1)
+            .entity(Uuid::parse_str(&garden).unwrap())
+            .since(now - Duration::days(30))
+            .until(now);
+        let statement = BalanceService::get_entity_statement(&mut conn, &query).unwrap();
+
+        let hours = statement
+            .balances
+            .iter()
+            .find(|b| b.quantity_unit == "hours")
+            .unwrap();
+        assert_eq!(hours.opening, 4.0);
+        assert_eq!(hours.inflows, 2.0);
+        assert_eq!(hours.outflows, 1.0);
+        assert_eq!(hours.closing, 5.0);
+
+        let minutes = statement
+            .balances
+            .iter()
+            .find(|b| b.quantity_unit == "minutes")
+            .unwrap();
+        assert_eq!(minutes.closing, 30.0);
+        assert_eq!(statement.lines.len(), 3);
+
+        let csv = statement.to_csv().unwrap();
+        assert_eq!(csv.lines().count(), 1 + 3 + 2);
+    }
+}
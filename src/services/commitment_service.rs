@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::errors::auth_error::AuthError;
+use crate::models::commitments::{
+    self as commitment_model, Commitment, CommitmentFulfilment, NewCommitment, ProjectCommitments,
+};
+use crate::models::flow_events::FlowEvent;
+use crate::models::resource_types::ResourceType;
+use crate::schema::{commitments, flow_actions, flow_events};
+use crate::services::entity_service::EntityService;
+use crate::services::ledger_service::LedgerService;
+use crate::services::resource_service::ResourceService;
+use crate::types::{CommitmentStatus, FlowActionType, FlowStatus};
+
+/// Rounding slack when deciding whether a commitment is covered.
+const EPSILON: f64 = 1e-6;
+
+#[derive(Serialize)]
+pub struct CommitmentView {
+    #[serde(flatten)]
+    pub commitment: Commitment,
+    pub fulfilled: f64,
+    pub outstanding: f64,
+    pub overdue: bool,
+    pub fulfilments: Vec<CommitmentFulfilment>,
+}
+
+#[derive(Serialize)]
+pub struct EntityCommitments {
+    pub entity_id: String,
+    /// Commitments this entity has made.
+    pub owes: Vec<CommitmentView>,
+    /// Commitments made to this entity.
+    pub owed: Vec<CommitmentView>,
+}
+
+pub struct CommitmentService;
+
+impl CommitmentService {
+    /// Records a commitment on behalf of a user who records flows for
+    /// `new.from_entity`, or a host admin.
+    pub fn create(
+        conn: &mut DbConn,
+        user: i32,
+        is_host_admin: bool,
+        new: NewCommitment,
+    ) -> Result<Commitment, AppError> {
+        EntityService::require_recorder(conn, &new.from_entity, user, is_host_admin)?;
+        ResourceService::validate_flow(conn, new.host_id, &new.resource_type, &new.quantity_unit)?;
+        if !(new.quantity_value.is_finite() && new.quantity_value > 0.0) {
+            return Err(AppError::BadRequest("quantity_value must be positive".into()));
+        }
+        commitment_model::create_commitment(conn, &new).map_err(AppError::Db)
+    }
+
+    /// Links a flow to a commitment. The flow must be confirmed, move the
+    /// same resource between the same entities and not have been reversed. It covers
+    /// `quantity` (in the commitment's unit) or as much as it can, without
+    /// counting any part of the flow already drawn on by other commitments.
+    pub fn fulfil(
+        conn: &mut DbConn,
+        host: i32,
+        commitment_id: &str,
+        flow_id: &str,
+        quantity: Option<f32>,
+        user: i32,
+        is_host_admin: bool,
+    ) -> Result<CommitmentView, AppError> {
+        let now = chrono::Utc::now().naive_utc();
+        conn.transaction(|conn| {
+            let commitment = Self::get_on_host(conn, host, commitment_id)?;
+            Self::require_party(conn, &commitment, user, is_host_admin)?;
+            if commitment.status != CommitmentStatus::Open.value() {
+                return Err(AppError::BadRequest(format!(
+                    "Commitment is {}",
+                    commitment.status
+                )));
+            }
+
+            let flow: FlowEvent = flow_events::table
+                .find(flow_id)
+                .filter(flow_events::host_id.eq(host))
+                .select(FlowEvent::as_select())
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("Flow {}", flow_id)))?;
+
+            if flow.from_entity != commitment.from_entity
+                || flow.to_entity != commitment.to_entity
+                || flow.resource_type != commitment.resource_type
+            {
+                return Err(AppError::BadRequest(
+                    "Flow does not match the commitment's entities and resource type".into(),
+                ));
+            }
+            let reversed: i64 = flow_actions::table
+                .filter(flow_actions::flow_id.eq(&flow.id))
+                .filter(flow_actions::action_type.eq(FlowActionType::Reversal.value()))
+                .count()
+                .get_result(conn)?;
+            if reversed > 0 || flow.details.0.get("reverses").is_some() {
+                return Err(AppError::BadRequest("Reversed flows cannot fulfil a commitment".into()));
+            }
+            if LedgerService::get_flow_status(conn, &flow.id)? != FlowStatus::Confirmed {
+                return Err(AppError::BadRequest("Only confirmed flows can fulfil a commitment".into()));
+            }
+
+            let registry = ResourceService::get_registry(conn, host)?;
+            let resource = registry.iter().find(|r| r.key == commitment.resource_type);
+            let in_commitment_unit = |qty: f64, unit: &str| {
+                convert(resource, qty, unit, &commitment.quantity_unit).ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "Cannot convert {} to {}",
+                        unit, commitment.quantity_unit
+                    ))
+                })
+            };
+
+            let mut available = in_commitment_unit(flow.quantity_value as f64, &flow.quantity_unit)?;
+            for (qty, unit) in commitment_model::get_flow_allocations(conn, &flow.id)? {
+                available -= in_commitment_unit(qty as f64, &unit)?;
+            }
+
+            let fulfilments = commitment_model::get_fulfilments(conn, std::slice::from_ref(&commitment.id))?;
+            if fulfilments.iter().any(|f| f.flow_id == flow.id) {
+                return Err(AppError::BadRequest("Flow is already linked to this commitment".into()));
+            }
+            let outstanding = outstanding(&commitment, &fulfilments);
+
+            let amount = quantity.map(f64::from).unwrap_or(outstanding).min(outstanding).min(available);
+            if amount <= EPSILON {
+                return Err(AppError::BadRequest("Nothing left on this flow to apply".into()));
+            }
+
+            let created_by = LedgerService::get_user_entity_id(conn, host, user)?;
+            commitment_model::create_fulfilment(
+                conn,
+                &CommitmentFulfilment {
+                    commitment_id: commitment.id.clone(),
+                    flow_id: flow.id.clone(),
+                    quantity_value: amount as f32,
+                    created_by,
+                    created_at: now,
+                },
+            )?;
+            if outstanding - amount <= EPSILON {
+                commitment_model::set_status(conn, &commitment.id, CommitmentStatus::Fulfilled.value())?;
+            }
+
+            let commitment = commitment_model::get_commitment(conn, &commitment.id)?;
+            Ok(Self::views(conn, vec![commitment], now)?.remove(0))
+        })
+    }
+
+    /// Unlinks a reversed flow from the commitments it covered and reopens
+    /// any it had fulfilled.
+    pub fn release_flow(conn: &mut DbConn, flow_id: &str) -> Result<(), AppError> {
+        let commitment_ids = commitment_model::delete_flow_fulfilments(conn, flow_id)?;
+        diesel::update(commitments::table)
+            .filter(commitments::id.eq_any(&commitment_ids))
+            .filter(commitments::status.eq(CommitmentStatus::Fulfilled.value()))
+            .set(commitments::status.eq(CommitmentStatus::Open.value()))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn cancel(
+        conn: &mut DbConn,
+        host: i32,
+        commitment_id: &str,
+        user: i32,
+        is_host_admin: bool,
+    ) -> Result<Commitment, AppError> {
+        let commitment = Self::get_on_host(conn, host, commitment_id)?;
+        EntityService::require_recorder(conn, &commitment.from_entity, user, is_host_admin)?;
+        if commitment.status != CommitmentStatus::Open.value() {
+            return Err(AppError::BadRequest(format!("Commitment is {}", commitment.status)));
+        }
+        commitment_model::set_status(conn, commitment_id, CommitmentStatus::Cancelled.value())?;
+        commitment_model::get_commitment(conn, commitment_id).map_err(AppError::Db)
+    }
+
+    /// The entity's commitments in both directions. Closed ones are left
+    /// out unless `include_closed`.
+    pub fn for_entity(
+        conn: &mut DbConn,
+        host: i32,
+        entity_id: &str,
+        include_closed: bool,
+        now: NaiveDateTime,
+    ) -> Result<EntityCommitments, AppError> {
+        let rows: Vec<Commitment> = commitment_model::get_commitments_for_entity(conn, host, entity_id)?
+            .into_iter()
+            .filter(|c| include_closed || c.status == CommitmentStatus::Open.value())
+            .collect();
+
+        let (owes, owed) = Self::views(conn, rows, now)?
+            .into_iter()
+            .partition(|v| v.commitment.from_entity == entity_id);
+
+        Ok(EntityCommitments {
+            entity_id: entity_id.to_string(),
+            owes,
+            owed,
+        })
+    }
+
+    pub fn outstanding_by_project(
+        conn: &mut DbConn,
+        host: i32,
+        now: NaiveDateTime,
+    ) -> Result<Vec<ProjectCommitments>, AppError> {
+        commitment_model::get_outstanding_by_project(conn, host, now).map_err(AppError::Db)
+    }
+
+    fn views(
+        conn: &mut DbConn,
+        rows: Vec<Commitment>,
+        now: NaiveDateTime,
+    ) -> Result<Vec<CommitmentView>, AppError> {
+        let ids: Vec<String> = rows.iter().map(|c| c.id.clone()).collect();
+        let mut by_commitment: HashMap<String, Vec<CommitmentFulfilment>> = HashMap::new();
+        for f in commitment_model::get_fulfilments(conn, &ids)? {
+            by_commitment.entry(f.commitment_id.clone()).or_default().push(f);
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|commitment| {
+                let fulfilments = by_commitment.remove(&commitment.id).unwrap_or_default();
+                let outstanding = outstanding(&commitment, &fulfilments);
+                CommitmentView {
+                    fulfilled: commitment.quantity_value as f64 - outstanding,
+                    overdue: commitment.status == CommitmentStatus::Open.value()
+                        && commitment.due_date < now,
+                    outstanding,
+                    fulfilments,
+                    commitment,
+                }
+            })
+            .collect())
+    }
+
+    fn require_party(
+        conn: &mut DbConn,
+        commitment: &Commitment,
+        user: i32,
+        is_host_admin: bool,
+    ) -> Result<(), AppError> {
+        let from = EntityService::require_recorder(conn, &commitment.from_entity, user, is_host_admin);
+        if from.is_ok() || EntityService::require_recorder(conn, &commitment.to_entity, user, false).is_ok() {
+            return Ok(());
+        }
+        Err(AuthError::Forbidden("Only parties to a commitment can fulfil it").into())
+    }
+
+    fn get_on_host(conn: &mut DbConn, host: i32, commitment_id: &str) -> Result<Commitment, AppError> {
+        commitments::table
+            .find(commitment_id)
+            .filter(commitments::host_id.eq(host))
+            .select(Commitment::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Commitment {}", commitment_id)))
+    }
+}
+
+fn outstanding(commitment: &Commitment, fulfilments: &[CommitmentFulfilment]) -> f64 {
+    let fulfilled: f64 = fulfilments.iter().map(|f| f.quantity_value as f64).sum();
+    (commitment.quantity_value as f64 - fulfilled).max(0.0)
+}
+
+/// Converts between units of one resource type through its registry
+/// factors. Without a registry entry only identical units convert.
+fn convert(resource: Option<&ResourceType>, qty: f64, from: &str, to: &str) -> Option<f64> {
+    if from == to {
+        return Some(qty);
+    }
+    let resource = resource?;
+    Some(qty * resource.factor(from)? / resource.factor(to)?)
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::{NewFlowEvent, create_flow_event};
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::JsonField;
+
+    #[test]
+    fn flows_fulfil_commitments_partially_then_fully() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut entity = |name: &str, entity_type: &str| create_test_entity(&mut conn, 1, name, entity_type).id;
+        let (jane, garden) = (entity("Jane", "Person"), entity("Garden", "project"));
+
+        let commitment = CommitmentService::create(
+            &mut conn,
+            user_id,
+            true,
+            NewCommitment {
+                id: uuid::Uuid::new_v4().to_string(),
+                host_id: 1,
+                from_entity: jane.clone(),
+                to_entity: garden.clone(),
+                resource_type: "labor_time".to_string(),
+                quantity_value: 3.0,
+                quantity_unit: "hours".to_string(),
+                due_date: now - chrono::Duration::days(1),
+                notes: None,
+                details: JsonField::default(),
+                created_by: jane.clone(),
+            },
+        )
+        .unwrap();
+
+        let mut flow = |qty: f32, unit: &str| {
+            let new = NewFlowEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: now,
+                recorded_at: now,
+                from_entity: jane.clone(),
+                to_entity: garden.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: qty,
+                quantity_unit: unit.to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: jane.clone(),
+            };
+            create_flow_event(&mut conn, &new).unwrap().id
+        };
+        let (short_flow, big_flow) = (flow(90.0, "minutes"), flow(5.0, "hours"));
+
+        let view = CommitmentService::fulfil(&mut conn, 1, &commitment.id, &short_flow, None, user_id, true).unwrap();
+        assert!((view.outstanding - 1.5).abs() < 1e-4);
+        assert!(view.overdue);
+
+        let report = CommitmentService::outstanding_by_project(&mut conn, 1, now).unwrap();
+        assert_eq!((report.len(), report[0].overdue_count), (1, 1));
+
+        let view = CommitmentService::fulfil(&mut conn, 1, &commitment.id, &big_flow, None, user_id, true).unwrap();
+        assert_eq!(view.commitment.status, "fulfilled");
+        assert!(view.outstanding.abs() < 1e-4);
+
+        let again = CommitmentService::fulfil(&mut conn, 1, &commitment.id, &big_flow, None, user_id, true);
+        assert!(matches!(again, Err(AppError::BadRequest(_))));
+        assert!(CommitmentService::outstanding_by_project(&mut conn, 1, now).unwrap().is_empty());
+
+        // reversing a linked flow puts its share back on the commitment
+        LedgerService::reverse_flow(&mut conn, 1, &big_flow, &jane, None).unwrap();
+        let reopened = commitment_model::get_commitment(&mut conn, &commitment.id).unwrap();
+        assert_eq!(reopened.status, CommitmentStatus::Open.value());
+        let view = CommitmentService::views(&mut conn, vec![reopened], now).unwrap().remove(0);
+        assert!((view.outstanding - 1.5).abs() < 1e-4);
+
+        // proposals only count once confirmed
+        let proposed = LedgerService::propose_flow(
+            &mut conn,
+            NewFlowEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: now,
+                recorded_at: now,
+                from_entity: jane.clone(),
+                to_entity: garden.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: 2.0,
+                quantity_unit: "hours".to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: jane.clone(),
+            },
+        )
+        .unwrap();
+        let early = CommitmentService::fulfil(&mut conn, 1, &commitment.id, &proposed.id, None, user_id, true);
+        assert!(matches!(early, Err(AppError::BadRequest(_))));
+    }
+}
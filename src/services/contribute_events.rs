@@ -119,7 +119,7 @@ impl ContributionEventsService {
             created_by: entity_user_id,
         };
 
-        if let Err(e) = ledger_domain.record_flow(new_flow) {
+        if let Err(e) = ledger_domain.propose_flow(new_flow) {
             log::error!("Failed to save FlowEvent {:?}", e);
         }
         Ok(ResultSaved {
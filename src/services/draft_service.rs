@@ -1,8 +1,18 @@
 use diesel::{QueryResult, Queryable, Selectable, SqliteConnection};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 
 use crate::{
-    models::drafts::{Draft, NewDraft}, schema::drafts, types::{DocType, DraftStatus}
+    errors::{app_error::{AppError, FieldError}, auth_error::AuthError},
+    models::drafts::{
+        self as drafts_model, Draft, DraftComment, DraftRevision, DraftRevisionSummary, DraftTransition,
+        NewDraft, NewDraftComment, NewDraftTransition,
+    },
+    models::users::get_user_by_id,
+    schema::drafts,
+    services::publish_service::{GitAuthor, SitePublisher},
+    types::{DocType, DraftAction, DraftStatus, FieldSchema, FrontendSchema},
+    types::diff::{FieldChange, LineChange, diff_fields, diff_lines},
 };
 use diesel::prelude::*;
 
@@ -37,10 +47,866 @@ impl DraftService {
 
     // CREATE
     pub fn create_draft(conn: &mut SqliteConnection, new: &NewDraft) -> QueryResult<Draft> {
-        diesel::insert_into(drafts::table)
-            .values(new)
-            .execute(conn)?;
+        conn.transaction(|conn| {
+            diesel::insert_into(drafts::table)
+                .values(new)
+                .execute(conn)?;
 
-        drafts::table.order(drafts::id.desc()).first(conn)
+            let draft: Draft = drafts::table.order(drafts::id.desc()).first(conn)?;
+            drafts_model::create_draft_revision(conn, &draft, draft.submitted_by, None)?;
+            Ok(draft)
+        })
+    }
+
+    /// Saves an edit and snapshots it as a new revision, unless nothing
+    /// changed since the last one. Status and review fields only change
+    /// through transitions, and the draft keeps its author.
+    pub fn save_draft(
+        conn: &mut SqliteConnection,
+        draft: &Draft,
+        updated: &mut NewDraft,
+        actor: &DraftActor,
+    ) -> Result<Draft, AppError> {
+        Self::check_can_edit(draft, actor)?;
+        updated.status = None;
+        updated.submitted_by = None;
+        updated.submitted_at = None;
+        updated.reviewed_by = None;
+        updated.reviewed_at = None;
+        updated.review_notes = None;
+        updated.host_id = draft.host_id;
+
+        conn.transaction(|conn| {
+            let saved = drafts_model::update_draft(conn, draft.id, updated)?;
+            let latest = drafts_model::get_latest_draft_revision(conn, draft.id)?;
+            if !latest.is_some_and(|r| same_content(&r, &saved)) {
+                drafts_model::create_draft_revision(conn, &saved, actor.user_id, None)?;
+            }
+            Ok(saved)
+        })
+    }
+
+    pub fn revisions(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+    ) -> Result<Vec<DraftRevisionSummary>, AppError> {
+        Self::get_draft(conn, host, draft_id)?;
+        drafts_model::get_draft_revisions(conn, draft_id).map_err(AppError::Db)
+    }
+
+    pub fn revision(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        revision: i32,
+    ) -> Result<DraftRevision, AppError> {
+        Self::get_draft(conn, host, draft_id)?;
+        drafts_model::get_draft_revision(conn, draft_id, revision)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Revision {} of draft {}", revision, draft_id)))
+    }
+
+    /// What changed from one revision to another: `body_md` line by line,
+    /// the other fields and `meta` key by key.
+    pub fn diff(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        from: i32,
+        to: i32,
+    ) -> Result<DraftDiff, AppError> {
+        let old = Self::revision(conn, host, draft_id, from)?;
+        let new = Self::revision(conn, host, draft_id, to)?;
+        Ok(DraftDiff {
+            draft_id,
+            from,
+            to,
+            body: diff_lines(&old.body_md, &new.body_md),
+            fields: diff_fields(&revision_fields(&old), &revision_fields(&new)),
+        })
+    }
+
+    /// Makes an older revision's content current again, as a new revision,
+    /// so nothing in between is lost.
+    pub fn restore(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        revision: i32,
+        actor: &DraftActor,
+    ) -> Result<Draft, AppError> {
+        let draft = Self::get_draft(conn, host, draft_id)?;
+        Self::check_can_edit(&draft, actor)?;
+        let old = Self::revision(conn, host, draft_id, revision)?;
+
+        conn.transaction(|conn| {
+            let restored = drafts_model::restore_draft_content(conn, &old)?;
+            drafts_model::create_draft_revision(conn, &restored, actor.user_id, Some(revision))?;
+            Ok(restored)
+        })
+    }
+
+    pub fn get_draft(conn: &mut SqliteConnection, host: i32, draft_id: i32) -> Result<Draft, AppError> {
+        drafts::table
+            .find(draft_id)
+            .filter(drafts::host_id.eq(host))
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Draft {}", draft_id)))
+    }
+
+    /// The only way a draft's status changes. Checks the move is legal from
+    /// the draft's current status and that the actor may make it, then
+    /// records who made it.
+    pub fn transition(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        action: DraftAction,
+        actor: &DraftActor,
+        notes: Option<String>,
+    ) -> Result<Draft, AppError> {
+        let draft = Self::get_draft(conn, host, draft_id)?;
+        let (from, to) = Self::check_transition(&draft, action, actor)?;
+        Self::record_transition(conn, &draft, action, (from, to), actor, notes, None)
+    }
+
+    /// Deploys an approved draft: writes `markdown` into the host's site
+    /// repository, commits it with the reviewer as author and records the
+    /// commit on the draft. A page another draft already holds gets the
+    /// draft's id added to its slug. If recording fails, the commit is
+    /// undone.
+    pub fn publish(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        actor: &DraftActor,
+        publisher: &SitePublisher,
+        render: impl Fn(&Draft) -> String,
+    ) -> Result<Draft, AppError> {
+        publisher.exclusive(|| {
+            let draft = Self::get_draft(conn, host, draft_id)?;
+            let moves = Self::check_transition(&draft, DraftAction::Deploy, actor)?;
+
+            let slug = draft.meta.as_ref().and_then(|m| m.0.get("slug")).and_then(Value::as_str);
+            let slug = slug.unwrap_or(&draft.title);
+            let mut path = publisher.path_for(draft.doc_type, slug)?;
+            if drafts_model::published_path_taken(conn, host, &path, draft.id)? {
+                path = publisher.path_for(draft.doc_type, &format!("{}-{}", slug, draft.id))?;
+            }
+            let message = format!("Publish {} \"{}\"", draft.doc_type.label().to_lowercase(), draft.title);
+            let author = Self::git_author(conn, actor)?;
+            let before = publisher.head()?;
+            let commit = publisher.publish(&path, &render(&draft), &author, &message)?;
+
+            let recorded = conn.transaction(|conn| {
+                Self::record_transition(conn, &draft, DraftAction::Deploy, moves, actor, None, Some(commit.clone()))?;
+                let published = (path.as_str(), commit.as_str(), chrono::Utc::now().naive_utc());
+                drafts_model::set_draft_publication(conn, draft_id, Some(published)).map_err(AppError::Db)
+            });
+            Self::undo_unrecorded(publisher, &before, &path, recorded)
+        })
+    }
+
+    /// Takes a deployed draft off the site in a new commit and returns it
+    /// to approved. If recording fails, the commit is undone.
+    pub fn unpublish(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        actor: &DraftActor,
+        publisher: &SitePublisher,
+    ) -> Result<Draft, AppError> {
+        publisher.exclusive(|| {
+            let draft = Self::get_draft(conn, host, draft_id)?;
+            let moves = Self::check_transition(&draft, DraftAction::Unpublish, actor)?;
+
+            let before = publisher.head()?;
+            let commit = match &draft.published_path {
+                Some(path) => {
+                    let message = format!("Unpublish {} \"{}\"", draft.doc_type.label().to_lowercase(), draft.title);
+                    Some(publisher.unpublish(path, &Self::git_author(conn, actor)?, &message)?)
+                }
+                None => None,
+            };
+
+            let recorded = conn.transaction(|conn| {
+                Self::record_transition(conn, &draft, DraftAction::Unpublish, moves, actor, None, commit)?;
+                drafts_model::set_draft_publication(conn, draft_id, None).map_err(AppError::Db)
+            });
+            match &draft.published_path {
+                Some(path) => Self::undo_unrecorded(publisher, &before, path, recorded),
+                None => recorded,
+            }
+        })
+    }
+
+    /// Undoes the site commit to `path` when it could not be recorded, so
+    /// the site and the drafts agree.
+    fn undo_unrecorded(
+        publisher: &SitePublisher,
+        before: &str,
+        path: &str,
+        recorded: Result<Draft, AppError>,
+    ) -> Result<Draft, AppError> {
+        let Err(error) = recorded else {
+            return recorded;
+        };
+        if let Err(e) = publisher.undo_commit(before, path) {
+            log::error!("Could not undo the site commit after {}: {}", before, e);
+        }
+        Err(error)
+    }
+
+    fn check_transition(
+        draft: &Draft,
+        action: DraftAction,
+        actor: &DraftActor,
+    ) -> Result<(DraftStatus, DraftStatus), AppError> {
+        if action.needs_reviewer() {
+            if !actor.is_reviewer {
+                return Err(AuthError::Forbidden("Only reviewers can review drafts").into());
+            }
+        } else if !actor.owns(draft) {
+            return Err(AuthError::Forbidden("Only the author can submit this draft").into());
+        }
+
+        let from = Self::status(draft)?;
+        let to = action.apply(from).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Cannot {} a draft that is {}",
+                action.label().to_lowercase(),
+                from.label().to_lowercase()
+            ))
+        })?;
+        Ok((from, to))
+    }
+
+    fn record_transition(
+        conn: &mut SqliteConnection,
+        draft: &Draft,
+        action: DraftAction,
+        (from, to): (DraftStatus, DraftStatus),
+        actor: &DraftActor,
+        notes: Option<String>,
+        commit_id: Option<String>,
+    ) -> Result<Draft, AppError> {
+        let now = chrono::Utc::now().naive_utc();
+        let new = NewDraftTransition {
+            draft_id: draft.id,
+            host_id: draft.host_id,
+            action: action.value().to_string(),
+            from_status: from,
+            to_status: to,
+            actor_id: actor.user_id,
+            notes: notes.filter(|n| !n.trim().is_empty()),
+            created_at: now,
+            commit_id,
+        };
+        // review notes also start a comment thread, so they are kept
+        // after the next review replaces `review_notes`
+        conn.transaction(|conn| {
+            let moved = drafts_model::transition_draft(conn, &new)?;
+            if let Some(notes) = &new.notes {
+                let comment = NewDraftComment {
+                    draft_id: draft.id,
+                    host_id: draft.host_id,
+                    parent_id: None,
+                    revision: None,
+                    line_start: None,
+                    line_end: None,
+                    field: None,
+                    body: notes.clone(),
+                    author_id: actor.user_id,
+                    created_at: now,
+                };
+                drafts_model::create_draft_comment(conn, &comment)?;
+            }
+            Ok(moved)
+        })
+        .map_err(|e| match e {
+            diesel::result::Error::NotFound => {
+                AppError::BadRequest(format!("Draft {} changed status; reload it and try again", draft.id))
+            }
+            e => AppError::Db(e),
+        })
+    }
+
+    fn git_author(conn: &mut SqliteConnection, actor: &DraftActor) -> Result<GitAuthor, AppError> {
+        let user = get_user_by_id(conn, actor.user_id)?;
+        Ok(GitAuthor { name: user.username, email: user.email })
+    }
+
+    /// Starts a thread, or replies to one when `parent_id` is set. Only
+    /// the author and the host's reviewers take part in a draft's review.
+    /// Threads may be anchored to lines of the current `body_md` or to a
+    /// field; replies follow their thread's anchor.
+    pub fn add_comment(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        actor: &DraftActor,
+        body: &str,
+        parent_id: Option<i32>,
+        anchor: CommentAnchor,
+    ) -> Result<DraftComment, AppError> {
+        let draft = Self::get_draft(conn, host, draft_id)?;
+        if !actor.is_reviewer && !actor.owns(&draft) {
+            return Err(AuthError::Forbidden("Only the author and reviewers can comment on this draft").into());
+        }
+        if body.trim().is_empty() {
+            return Err(AppError::BadRequest("A comment needs some text".into()));
+        }
+
+        let parent_id = match parent_id {
+            Some(id) => {
+                if !anchor.is_empty() {
+                    return Err(AppError::BadRequest("Replies follow their thread's anchor".into()));
+                }
+                let parent = Self::comment(conn, draft_id, id)?;
+                // a reply to a reply joins the same thread
+                Some(parent.parent_id.unwrap_or(parent.id))
+            }
+            None => None,
+        };
+        let (line_start, line_end) = anchor.lines(&draft.body_md)?;
+        let revision = if line_start.is_some() {
+            drafts_model::get_latest_draft_revision(conn, draft_id)?.map(|r| r.revision)
+        } else {
+            None
+        };
+
+        let new = NewDraftComment {
+            draft_id,
+            host_id: host,
+            parent_id,
+            revision,
+            line_start,
+            line_end,
+            field: anchor.field.filter(|f| !f.trim().is_empty()),
+            body: body.trim().to_string(),
+            author_id: actor.user_id,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+        drafts_model::create_draft_comment(conn, &new).map_err(AppError::Db)
+    }
+
+    /// Resolves a thread, or reopens it with `resolved == false`. The
+    /// draft's author, the thread's author and reviewers may do either.
+    pub fn resolve_comment(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        comment_id: i32,
+        actor: &DraftActor,
+        resolved: bool,
+    ) -> Result<DraftComment, AppError> {
+        let draft = Self::get_draft(conn, host, draft_id)?;
+        let comment = Self::comment(conn, draft_id, comment_id)?;
+        if comment.parent_id.is_some() {
+            return Err(AppError::BadRequest("Resolve the thread, not a reply".into()));
+        }
+        let allowed = actor.is_reviewer || actor.owns(&draft) || comment.author_id == actor.user_id;
+        if !allowed {
+            return Err(AuthError::Forbidden("You cannot resolve this thread").into());
+        }
+
+        let resolution = resolved.then(|| (actor.user_id, chrono::Utc::now().naive_utc()));
+        drafts_model::set_draft_comment_resolved(conn, comment_id, resolution).map_err(AppError::Db)
+    }
+
+    /// The draft's threads, oldest first, each with its replies.
+    pub fn comment_threads(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+    ) -> Result<Vec<CommentThread>, AppError> {
+        Self::get_draft(conn, host, draft_id)?;
+        let comments = drafts_model::get_draft_comments(conn, draft_id)?;
+
+        let (roots, replies): (Vec<_>, Vec<_>) = comments.into_iter().partition(|c| c.parent_id.is_none());
+        let mut threads: Vec<CommentThread> = roots
+            .into_iter()
+            .map(|comment| CommentThread { comment, replies: Vec::new() })
+            .collect();
+        for reply in replies {
+            if let Some(thread) = threads.iter_mut().find(|t| Some(t.comment.id) == reply.parent_id) {
+                thread.replies.push(reply);
+            }
+        }
+        Ok(threads)
+    }
+
+    fn comment(conn: &mut SqliteConnection, draft_id: i32, comment_id: i32) -> Result<DraftComment, AppError> {
+        drafts_model::get_draft_comment(conn, comment_id)
+            .optional()?
+            .filter(|c| c.draft_id == draft_id)
+            .ok_or_else(|| AppError::NotFound(format!("Comment {} on draft {}", comment_id, draft_id)))
+    }
+
+    /// Approves every listed draft or, if any cannot be, none of them.
+    pub fn approve_all(
+        conn: &mut SqliteConnection,
+        host: i32,
+        ids: &[i32],
+        actor: &DraftActor,
+    ) -> Result<Vec<Draft>, AppError> {
+        conn.transaction(|conn| {
+            ids.iter()
+                .map(|&id| Self::transition(conn, host, id, DraftAction::Approve, actor, None))
+                .collect()
+        })
+    }
+
+    pub fn transitions(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+    ) -> Result<Vec<DraftTransition>, AppError> {
+        Self::get_draft(conn, host, draft_id)?;
+        drafts_model::get_draft_transitions(conn, draft_id).map_err(AppError::Db)
+    }
+
+    /// Authors edit their drafts until they are submitted, and again when
+    /// changes are requested; reviewers can edit anything not yet deployed.
+    pub fn check_can_edit(draft: &Draft, actor: &DraftActor) -> Result<(), AppError> {
+        let status = Self::status(draft)?;
+        let allowed = if actor.is_reviewer {
+            status != DraftStatus::Deployed
+        } else {
+            actor.owns(draft) && matches!(status, DraftStatus::Draft | DraftStatus::ChangesRequested)
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden("This draft cannot be edited").into())
+        }
+    }
+
+    pub fn delete(
+        conn: &mut SqliteConnection,
+        host: i32,
+        draft_id: i32,
+        actor: &DraftActor,
+    ) -> Result<(), AppError> {
+        let draft = Self::get_draft(conn, host, draft_id)?;
+        let unsubmitted = matches!(Self::status(&draft)?, DraftStatus::Draft | DraftStatus::ChangesRequested);
+        let allowed = actor.is_reviewer || (actor.owns(&draft) && unsubmitted);
+        if !allowed {
+            return Err(AuthError::Forbidden("This draft cannot be deleted").into());
+        }
+        drafts_model::delete_draft(conn, draft_id)?;
+        log::info!("Draft {} deleted by user {}", draft_id, actor.user_id);
+        Ok(())
+    }
+
+    fn status(draft: &Draft) -> Result<DraftStatus, AppError> {
+        DraftStatus::from_value(&draft.status)
+            .ok_or_else(|| AppError::Internal(format!("Draft {} has unknown status '{}'", draft.id, draft.status)))
+    }
+}
+
+/// Where a thread points: a line range of `body_md`, a field, or
+/// neither for a comment on the whole draft.
+#[derive(Deserialize, Debug, Default)]
+pub struct CommentAnchor {
+    pub line_start: Option<i32>,
+    pub line_end: Option<i32>,
+    pub field: Option<String>,
+}
+
+impl CommentAnchor {
+    fn is_empty(&self) -> bool {
+        self.line_start.is_none() && self.line_end.is_none() && self.field.is_none()
+    }
+
+    /// Checks the line range against the text; a single line may omit
+    /// `line_end`.
+    fn lines(&self, body_md: &str) -> Result<(Option<i32>, Option<i32>), AppError> {
+        let Some(start) = self.line_start else {
+            if self.line_end.is_some() {
+                return Err(AppError::BadRequest("line_end needs a line_start".into()));
+            }
+            return Ok((None, None));
+        };
+        if self.field.is_some() {
+            return Err(AppError::BadRequest("Anchor a comment to lines or to a field, not both".into()));
+        }
+        let end = self.line_end.unwrap_or(start);
+        let line_count = body_md.lines().count() as i32;
+        if start < 1 || end < start || end > line_count {
+            return Err(AppError::BadRequest(format!(
+                "Lines {}-{} are outside the draft's {} lines",
+                start, end, line_count
+            )));
+        }
+        Ok((Some(start), Some(end)))
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: DraftComment,
+    pub replies: Vec<DraftComment>,
+}
+
+/// A draft as the review screen shows it.
+#[derive(Serialize, Debug)]
+pub struct DraftWithComments {
+    #[serde(flatten)]
+    pub draft: Draft,
+    pub comments: Vec<CommentThread>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DraftDiff {
+    pub draft_id: i32,
+    pub from: i32,
+    pub to: i32,
+    pub body: Vec<LineChange>,
+    pub fields: Vec<FieldChange>,
+}
+
+fn revision_fields(revision: &DraftRevision) -> Value {
+    json!({
+        "title": revision.title,
+        "description": revision.description,
+        "tags": revision.tags,
+        "author": revision.author,
+        "meta": revision.meta.as_ref().map(|m| &m.0),
+    })
+}
+
+fn same_content(revision: &DraftRevision, draft: &Draft) -> bool {
+    revision.title == draft.title
+        && revision.description == draft.description
+        && revision.tags == draft.tags
+        && revision.author == draft.author
+        && revision.meta.as_ref().map(|m| &m.0) == draft.meta.as_ref().map(|m| &m.0)
+        && revision.body_md == draft.body_md
+}
+
+/// Who is acting on a draft: their user id, and whether they hold a
+/// reviewing role (admin or reviewer) on the draft's host.
+pub struct DraftActor {
+    pub user_id: i32,
+    pub is_reviewer: bool,
+}
+
+impl DraftActor {
+    fn owns(&self, draft: &Draft) -> bool {
+        draft.submitted_by == self.user_id
+    }
+}
+
+/// A draft's column and `meta` values, as the schema validator sees them.
+pub struct DraftFields {
+    pub doc_type: DocType,
+    columns: Value,
+    meta: Value,
+}
+
+impl DraftFields {
+    pub fn from_new(new: &NewDraft) -> Result<Self, AppError> {
+        let doc_type = DocType::from_value(&new.doc_type).ok_or_else(|| {
+            AppError::Validation(vec![FieldError {
+                field: "doc_type".to_string(),
+                message: format!("Unknown document type '{}'", new.doc_type),
+            }])
+        })?;
+        Ok(Self {
+            doc_type,
+            columns: json!({
+                "title": new.title,
+                "description": new.description,
+                "tags": new.tags,
+                "author": new.author,
+                "body_md": new.body_md,
+            }),
+            meta: new.meta.as_ref().map(|m| m.0.clone()).unwrap_or(Value::Null),
+        })
+    }
+
+    pub fn from_draft(draft: &Draft) -> Self {
+        Self {
+            doc_type: draft.doc_type,
+            columns: json!({
+                "title": draft.title,
+                "description": draft.description,
+                "tags": draft.tags,
+                "author": draft.author,
+                "body_md": draft.body_md,
+            }),
+            meta: draft.meta.as_ref().map(|m| m.0.clone()).unwrap_or(Value::Null),
+        }
+    }
+
+    /// Meta fields are looked up by key, then under their `path`, which
+    /// names either the value itself or the object holding the key.
+    /// Fields that are not drafts columns live in meta too.
+    fn value(&self, field: &FieldSchema) -> Option<&Value> {
+        let column = self.columns.get(&field.key);
+        if field.storage.as_deref() != Some("meta") && column.is_some() {
+            return column;
+        }
+        if let Some(value) = self.meta.get(&field.key) {
+            return Some(value);
+        }
+        let mut node = &self.meta;
+        for part in field.path.as_deref()?.split('.') {
+            node = node.get(part)?;
+        }
+        if node.is_object() { node.get(&field.key) } else { Some(node) }
+    }
+}
+
+impl DraftService {
+    /// Checks a draft against its type's fields in doc_schema.json.
+    /// Types and options are always checked; `required` only when the
+    /// draft is being submitted. Read-only fields keep their saved value.
+    pub fn validate(
+        schema: &FrontendSchema,
+        fields: &DraftFields,
+        saved: Option<&DraftFields>,
+        submitting: bool,
+    ) -> Result<(), AppError> {
+        let Some(doc_schema) = schema.types.get(&fields.doc_type) else {
+            return Ok(());
+        };
+
+        let mut errors = Vec::new();
+        for field in &doc_schema.fields {
+            let value = fields.value(field);
+            let checked = if submitting {
+                field.check_value(value)
+            } else {
+                FieldSchema { required: None, ..field.clone() }.check_value(value)
+            };
+            if let Err(message) = checked {
+                errors.push(FieldError { field: field.key.clone(), message });
+                continue;
+            }
+
+            let before = saved.and_then(|s| s.value(field)).filter(|v| !v.is_null());
+            if field.readonly == Some(true) && before.is_some() && before != value {
+                errors.push(FieldError {
+                    field: field.key.clone(),
+                    message: format!("{} is read-only", field.label),
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::JsonField;
+
+    fn draft(title: &str, meta: Value) -> NewDraft {
+        NewDraft {
+            doc_type: "organization".to_string(),
+            title: title.to_string(),
+            description: None,
+            tags: None,
+            author: None,
+            body_md: String::new(),
+            meta: Some(JsonField(meta)),
+            status: None,
+            submitted_by: None,
+            submitted_at: None,
+            reviewed_by: None,
+            reviewed_at: None,
+            review_notes: None,
+            details: None,
+            host_id: 1,
+        }
+    }
+
+    #[test]
+    fn drafts_are_checked_against_their_doc_type() {
+        let schema: FrontendSchema = serde_json::from_value(json!({ "types": { "organization": {
+            "label": "Organization",
+            "has_markdown": true,
+            "fields": [
+                { "key": "title", "label": "Title", "type": "text", "required": true },
+                { "key": "slug", "label": "Slug", "type": "text", "readonly": true },
+                { "key": "city", "label": "City", "type": "text", "required": true,
+                  "storage": "meta", "path": "location.address" },
+                { "key": "lat", "label": "Latitude", "type": "number",
+                  "storage": "meta", "path": "location.coordinates" },
+                { "key": "watersheds", "label": "Watersheds", "type": "multiselect", "storage": "meta",
+                  "options": [{ "label": "Skagit", "value": "skagit" }] }
+            ]
+        }}}))
+        .unwrap();
+        let fields = |new: &NewDraft| DraftFields::from_new(new).unwrap();
+
+        // saving an incomplete draft is fine; submitting it is not
+        let partial = draft("Skagit Land Trust", json!({ "slug": "skagit-land-trust" }));
+        assert!(DraftService::validate(&schema, &fields(&partial), None, false).is_ok());
+        let Err(AppError::Validation(errors)) =
+            DraftService::validate(&schema, &fields(&partial), None, true)
+        else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(), ["city"]);
+
+        let complete = draft(
+            "Skagit Land Trust",
+            json!({
+                "slug": "skagit-land-trust",
+                "location": { "address": { "city": "Mount Vernon" }, "coordinates": { "lat": "48.42" } },
+                "watersheds": ["skagit"]
+            }),
+        );
+        assert!(DraftService::validate(&schema, &fields(&complete), None, true).is_ok());
+
+        let edited = draft(
+            "",
+            json!({ "slug": "renamed", "location": { "coordinates": { "lat": "north" } }, "watersheds": ["nooksack"] }),
+        );
+        let Err(AppError::Validation(errors)) =
+            DraftService::validate(&schema, &fields(&edited), Some(&fields(&complete)), false)
+        else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(
+            errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(),
+            ["slug", "lat", "watersheds"]
+        );
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_support::db::setup_test_db;
+    use crate::types::diff::DiffOp;
+
+    fn post(user_id: i32, body_md: &str, meta: Option<Value>) -> NewDraft {
+        NewDraft {
+            doc_type: "post".to_string(),
+            title: "Spring planting day".to_string(),
+            description: None,
+            tags: None,
+            author: None,
+            body_md: body_md.to_string(),
+            meta: meta.map(crate::types::JsonField),
+            status: Some("draft".to_string()),
+            submitted_by: Some(user_id),
+            submitted_at: None,
+            reviewed_by: None,
+            reviewed_at: None,
+            review_notes: None,
+            details: None,
+            host_id: 1,
+        }
+    }
+
+    #[test]
+    fn drafts_move_only_along_the_review_workflow() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let id = DraftService::create_draft(&mut conn, &post(user_id, "", None)).unwrap().id;
+
+        let author = DraftActor { user_id, is_reviewer: false };
+        let stranger = DraftActor { user_id: user_id + 100, is_reviewer: false };
+        let reviewer = DraftActor { user_id: user_id + 200, is_reviewer: true };
+        let mut act = |action, actor: &DraftActor| DraftService::transition(&mut conn, 1, id, action, actor, None);
+
+        assert!(act(DraftAction::Approve, &reviewer).is_err());
+        assert!(act(DraftAction::Submit, &stranger).is_err());
+        assert_eq!(act(DraftAction::Submit, &author).unwrap().status, "submitted");
+        assert!(act(DraftAction::Approve, &author).is_err());
+        assert!(act(DraftAction::Deploy, &reviewer).is_err());
+        assert_eq!(act(DraftAction::Approve, &reviewer).unwrap().reviewed_by, Some(reviewer.user_id));
+        assert_eq!(act(DraftAction::Deploy, &reviewer).unwrap().status, "deployed");
+        assert!(DraftService::transition(&mut conn, 2, id, DraftAction::Deploy, &reviewer, None).is_err());
+
+        let history = DraftService::transitions(&mut conn, 1, id).unwrap();
+        let steps: Vec<_> = history.iter().map(|t| (t.action.as_str(), t.actor_id)).collect();
+        assert_eq!(
+            steps,
+            [("submit", user_id), ("approve", reviewer.user_id), ("deploy", reviewer.user_id)]
+        );
+        assert!(DraftService::delete(&mut conn, 1, id, &author).is_err());
+    }
+
+    #[test]
+    fn saves_are_kept_as_revisions_that_can_be_diffed_and_restored() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let author = DraftActor { user_id, is_reviewer: false };
+
+        let first = post(user_id, "Bring gloves.\nMeet at 9.", Some(json!({ "venue": "North plot" })));
+        let draft = DraftService::create_draft(&mut conn, &first).unwrap();
+        let mut save = |new: &mut NewDraft| {
+            let current = DraftService::get_draft(&mut conn, 1, draft.id).unwrap();
+            DraftService::save_draft(&mut conn, &current, new, &author).unwrap()
+        };
+        save(&mut post(user_id, "Bring gloves.\nMeet at 10.", Some(json!({ "venue": "South plot" }))));
+        // saving again without changes adds nothing
+        save(&mut post(user_id, "Bring gloves.\nMeet at 10.", Some(json!({ "venue": "South plot" }))));
+
+        let revisions = DraftService::revisions(&mut conn, 1, draft.id).unwrap();
+        assert_eq!(revisions.iter().map(|r| r.revision).collect::<Vec<_>>(), [2, 1]);
+
+        let diff = DraftService::diff(&mut conn, 1, draft.id, 1, 2).unwrap();
+        let changed: Vec<_> = diff.body.iter().filter(|l| l.op != DiffOp::Equal).map(|l| l.text.as_str()).collect();
+        assert_eq!(changed, ["Meet at 9.", "Meet at 10."]);
+        assert_eq!(diff.fields.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), ["meta.venue"]);
+
+        let restored = DraftService::restore(&mut conn, 1, draft.id, 1, &author).unwrap();
+        assert_eq!(restored.body_md, "Bring gloves.\nMeet at 9.");
+        let latest = DraftService::revision(&mut conn, 1, draft.id, 3).unwrap();
+        assert_eq!((latest.restored_from, latest.body_md.as_str()), (Some(1), restored.body_md.as_str()));
+    }
+
+    #[test]
+    fn review_comments_thread_and_resolve() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let author = DraftActor { user_id, is_reviewer: false };
+        let stranger = DraftActor { user_id: user_id + 100, is_reviewer: false };
+        let reviewer = DraftActor { user_id: user_id + 200, is_reviewer: true };
+
+        let draft = DraftService::create_draft(&mut conn, &post(user_id, "Bring gloves.\nMeet at 9.", None)).unwrap();
+        DraftService::transition(&mut conn, 1, draft.id, DraftAction::Submit, &author, None).unwrap();
+        let notes = Some("A couple of things before this goes out".to_string());
+        DraftService::transition(&mut conn, 1, draft.id, DraftAction::RequestChanges, &reviewer, notes).unwrap();
+
+        let lines = |start, end| CommentAnchor { line_start: Some(start), line_end: end, field: None };
+        let mut comment = |actor: &DraftActor, parent, anchor| {
+            DraftService::add_comment(&mut conn, 1, draft.id, actor, "Which entrance?", parent, anchor)
+        };
+        let on_time = comment(&reviewer, None, lines(2, None)).unwrap();
+        assert_eq!((on_time.line_start, on_time.line_end, on_time.revision), (Some(2), Some(2), Some(1)));
+        assert!(comment(&reviewer, None, lines(2, Some(3))).is_err());
+        assert!(comment(&stranger, None, CommentAnchor::default()).is_err());
+        let reply = comment(&author, Some(on_time.id), CommentAnchor::default()).unwrap();
+        // replying to the reply stays in the thread
+        comment(&reviewer, Some(reply.id), CommentAnchor::default()).unwrap();
+
+        assert!(DraftService::resolve_comment(&mut conn, 1, draft.id, reply.id, &author, true).is_err());
+        let resolved = DraftService::resolve_comment(&mut conn, 1, draft.id, on_time.id, &author, true).unwrap();
+        assert_eq!(resolved.resolved_by, Some(user_id));
+
+        let threads = DraftService::comment_threads(&mut conn, 1, draft.id).unwrap();
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].comment.author_id, reviewer.user_id);
+        assert_eq!(threads[1].replies.len(), 2);
+
+        let reopened = DraftService::resolve_comment(&mut conn, 1, draft.id, on_time.id, &reviewer, false).unwrap();
+        assert_eq!(reopened.resolved_at, None);
     }
 }
@@ -0,0 +1,1221 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::errors::auth_error::AuthError;
+use crate::models::entities::{
+    self as entity_model, Entity, EntityAlias, EntityChanges, EntityUser, NewEntityAlias, NewEntityUser,
+};
+use crate::models::flow_events::{self as flow_model, NewFlowAction};
+use crate::models::ledger_chain;
+use crate::schema::{
+    commitments, entities, entity_aliases, entity_goals, entity_users, flow_actions, flow_events,
+    flow_templates, users,
+};
+use crate::services::ledger_service::EntityRef;
+use crate::types::flow_query::FlowViewer;
+use crate::types::{EntityRole, EntityUserStatus, FlowActionType, Visibility};
+
+/// Outcome of folding a duplicate entity into a canonical one.
+#[derive(Serialize)]
+pub struct EntityMerge {
+    pub canonical: EntityRef,
+    pub merged_id: String,
+    pub flows_repointed: usize,
+    pub actions_repointed: usize,
+    pub users_repointed: usize,
+    pub templates_repointed: usize,
+    pub commitments_repointed: usize,
+    pub goals_repointed: usize,
+    pub aliases: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct DuplicateCandidate {
+    pub entities: [EntityRef; 2],
+    pub reason: &'static str,
+}
+
+/// A user linked to an entity, for the people list.
+#[derive(Serialize, Queryable)]
+pub struct EntityPerson {
+    pub user_id: i32,
+    pub username: String,
+    pub role: String,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// How an invitation names the user.
+pub enum UserLookup<'a> {
+    Id(i32),
+    Email(&'a str),
+}
+
+pub struct EntityService;
+
+impl EntityService {
+    /// Entity id for an id, name or alias given by a user or import.
+    pub fn resolve(conn: &mut DbConn, input: &str, host: i32) -> Result<String, AppError> {
+        if Uuid::parse_str(input).is_ok() && Self::get_on_host(conn, host, input).is_ok() {
+            return Ok(input.to_string());
+        }
+        Ok(Self::find_by_name_or_alias(conn, input, host)?.id)
+    }
+
+    /// Finds an entity on `host` by exact name, then case-insensitive name,
+    /// then normalised alias (which also covers ids of merged duplicates).
+    pub fn find_by_name_or_alias(
+        conn: &mut DbConn,
+        input: &str,
+        host: i32,
+    ) -> Result<Entity, AppError> {
+        let exact = entities::table
+            .filter(entities::host_id.eq(host))
+            .filter(entities::name.eq(input))
+            .select(Entity::as_select())
+            .first(conn)
+            .optional()?;
+        if let Some(entity) = exact {
+            return Ok(entity);
+        }
+
+        let normalized = normalize_alias(input);
+
+        let by_name = entities::table
+            .filter(entities::host_id.eq(host))
+            .filter(diesel::dsl::sql::<diesel::sql_types::Bool>("lower(trim(entities.name)) = ")
+                .bind::<diesel::sql_types::Text, _>(&normalized))
+            .select(Entity::as_select())
+            .first(conn)
+            .optional()?;
+        if let Some(entity) = by_name {
+            return Ok(entity);
+        }
+
+        entity_aliases::table
+            .inner_join(entities::table)
+            .filter(entities::host_id.eq(host))
+            .filter(entity_aliases::normalized.eq(&normalized))
+            .select(Entity::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Entity {}", input)))
+    }
+
+    /// Recomputes the normalised form of every alias with `normalize_alias`.
+    pub fn normalize_aliases(conn: &mut SqliteConnection) -> Result<usize, AppError> {
+        let aliases: Vec<(String, String)> = entity_aliases::table
+            .select((entity_aliases::id, entity_aliases::alias))
+            .load(conn)?;
+        conn.transaction(|conn| {
+            for (id, alias) in &aliases {
+                diesel::update(entity_aliases::table.find(id))
+                    .set(entity_aliases::normalized.eq(normalize_alias(alias)))
+                    .execute(conn)?;
+            }
+            Ok(aliases.len())
+        })
+    }
+
+    /// Adds `alias` to an entity, refusing aliases already claimed on the host.
+    pub fn add_alias(
+        conn: &mut DbConn,
+        entity: &Entity,
+        alias: &str,
+        created_by: &str,
+    ) -> Result<EntityAlias, AppError> {
+        let normalized = normalize_alias(alias);
+        if normalized.is_empty() {
+            return Err(AppError::BadRequest("Alias cannot be empty".into()));
+        }
+
+        match Self::find_by_name_or_alias(conn, alias, entity.host_id) {
+            Ok(existing) if existing.id == entity.id => {}
+            Ok(existing) => {
+                return Err(AppError::BadRequest(format!(
+                    "'{}' already refers to {}",
+                    alias, existing.name
+                )));
+            }
+            Err(AppError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let id = Uuid::new_v4().to_string();
+        entity_model::create_entity_alias(
+            conn,
+            &NewEntityAlias {
+                id: &id,
+                entity_id: &entity.id,
+                alias,
+                created_by,
+                normalized: &normalized,
+            },
+        )
+        .map_err(AppError::Db)
+    }
+
+    /// Folds `duplicate_id` into `canonical_id`: repoints flows, flow actions,
+    /// user links and aliases, keeps the old id and name as aliases, then
+    /// deletes the duplicate. Each repointed flow gets an `entity_merge`
+    /// action holding its previous endpoints.
+    pub fn merge_entities(
+        conn: &mut DbConn,
+        host: i32,
+        canonical_id: &str,
+        duplicate_id: &str,
+        actor_entity: &str,
+    ) -> Result<EntityMerge, AppError> {
+        if canonical_id == duplicate_id {
+            return Err(AppError::BadRequest("Cannot merge an entity into itself".into()));
+        }
+
+        conn.transaction(|conn| {
+            let canonical = Self::get_on_host(conn, host, canonical_id)?;
+            let duplicate = Self::get_on_host(conn, host, duplicate_id)?;
+            // anything still unlinked is hashed with the ids it was recorded with
+            ledger_chain::link_pending(conn, host)?;
+
+            let between: i64 = flow_events::table
+                .filter(
+                    flow_events::from_entity
+                        .eq(&canonical.id)
+                        .and(flow_events::to_entity.eq(&duplicate.id))
+                        .or(flow_events::from_entity
+                            .eq(&duplicate.id)
+                            .and(flow_events::to_entity.eq(&canonical.id))),
+                )
+                .count()
+                .get_result(conn)?;
+            if between > 0 {
+                return Err(AppError::BadRequest(format!(
+                    "{} flows run between these entities and would become self-flows",
+                    between
+                )));
+            }
+            let templates_between: i64 = flow_templates::table
+                .filter(
+                    flow_templates::from_entity
+                        .eq(&canonical.id)
+                        .and(flow_templates::to_entity.eq(&duplicate.id))
+                        .or(flow_templates::from_entity
+                            .eq(&duplicate.id)
+                            .and(flow_templates::to_entity.eq(&canonical.id))),
+                )
+                .count()
+                .get_result(conn)?;
+            if templates_between > 0 {
+                return Err(AppError::BadRequest(format!(
+                    "{} flow templates run between these entities and would propose self-flows",
+                    templates_between
+                )));
+            }
+            let commitments_between: i64 = commitments::table
+                .filter(
+                    commitments::from_entity
+                        .eq(&canonical.id)
+                        .and(commitments::to_entity.eq(&duplicate.id))
+                        .or(commitments::from_entity
+                            .eq(&duplicate.id)
+                            .and(commitments::to_entity.eq(&canonical.id))),
+                )
+                .count()
+                .get_result(conn)?;
+            if commitments_between > 0 {
+                return Err(AppError::BadRequest(format!(
+                    "{} commitments run between these entities and would become self-commitments",
+                    commitments_between
+                )));
+            }
+
+            // Existing aliases move over first so the duplicate's id and name
+            // can be added without tripping the host-wide uniqueness check.
+            diesel::update(entity_aliases::table.filter(entity_aliases::entity_id.eq(&duplicate.id)))
+                .set(entity_aliases::entity_id.eq(&canonical.id))
+                .execute(conn)?;
+            let mut aliases = vec![duplicate.id.clone()];
+            for alias in [duplicate.id.as_str(), duplicate.name.as_str()] {
+                let normalized = normalize_alias(alias);
+                let id = Uuid::new_v4().to_string();
+                entity_model::create_entity_alias(
+                    conn,
+                    &NewEntityAlias {
+                        id: &id,
+                        entity_id: &canonical.id,
+                        alias,
+                        created_by: actor_entity,
+                        normalized: &normalized,
+                    },
+                )?;
+            }
+            aliases.push(duplicate.name.clone());
+
+            // The restrict_flow_repoint trigger only lets these through
+            // because the duplicate's id is now an alias of the canonical.
+            let moved: Vec<(String, String, String)> = flow_events::table
+                .filter(
+                    flow_events::from_entity
+                        .eq(&duplicate.id)
+                        .or(flow_events::to_entity.eq(&duplicate.id)),
+                )
+                .select((flow_events::id, flow_events::from_entity, flow_events::to_entity))
+                .load(conn)?;
+            diesel::update(flow_events::table.filter(flow_events::from_entity.eq(&duplicate.id)))
+                .set(flow_events::from_entity.eq(&canonical.id))
+                .execute(conn)?;
+            diesel::update(flow_events::table.filter(flow_events::to_entity.eq(&duplicate.id)))
+                .set(flow_events::to_entity.eq(&canonical.id))
+                .execute(conn)?;
+
+            let acted: Vec<(String, String)> = flow_actions::table
+                .filter(flow_actions::actor_entity.eq(&duplicate.id))
+                .select((flow_actions::id, flow_actions::flow_id))
+                .load(conn)?;
+            let actions_repointed = diesel::update(
+                flow_actions::table.filter(flow_actions::actor_entity.eq(&duplicate.id)),
+            )
+            .set(flow_actions::actor_entity.eq(&canonical.id))
+            .execute(conn)?;
+
+            // Every repoint is recorded on the chain, so integrity checks can
+            // tell the ids each flow and action was linked with.
+            let flow_repoints = moved.iter().map(|(flow_id, from, to)| {
+                let details = json!({
+                    "merged_from": duplicate.id,
+                    "merged_into": canonical.id,
+                    "previous_from_entity": from,
+                    "previous_to_entity": to,
+                });
+                (flow_id, details)
+            });
+            let actor_repoints = acted.iter().map(|(action_id, flow_id)| {
+                let details = json!({
+                    "merged_from": duplicate.id,
+                    "merged_into": canonical.id,
+                    "action_id": action_id,
+                    "previous_actor_entity": duplicate.id,
+                });
+                (flow_id, details)
+            });
+            for (flow_id, details) in flow_repoints.chain(actor_repoints) {
+                let action_id = Uuid::new_v4().to_string();
+                let details = details.to_string();
+                flow_model::create_flow_action(
+                    conn,
+                    &NewFlowAction {
+                        id: &action_id,
+                        flow_id,
+                        action_type: FlowActionType::EntityMerge.value(),
+                        actor_entity,
+                        details: &details,
+                    },
+                    &action_id,
+                )?;
+            }
+
+            // Users already linked to the canonical keep that link.
+            let already_linked: Vec<i32> = entity_users::table
+                .filter(entity_users::entity_id.eq(&canonical.id))
+                .select(entity_users::user_id)
+                .load(conn)?;
+            diesel::delete(
+                entity_users::table
+                    .filter(entity_users::entity_id.eq(&duplicate.id))
+                    .filter(entity_users::user_id.eq_any(&already_linked)),
+            )
+            .execute(conn)?;
+            let users_repointed = diesel::update(
+                entity_users::table.filter(entity_users::entity_id.eq(&duplicate.id)),
+            )
+            .set(entity_users::entity_id.eq(&canonical.id))
+            .execute(conn)?;
+
+            let templates_from = diesel::update(
+                flow_templates::table.filter(flow_templates::from_entity.eq(&duplicate.id)),
+            )
+            .set(flow_templates::from_entity.eq(&canonical.id))
+            .execute(conn)?;
+            let templates_to = diesel::update(
+                flow_templates::table.filter(flow_templates::to_entity.eq(&duplicate.id)),
+            )
+            .set(flow_templates::to_entity.eq(&canonical.id))
+            .execute(conn)?;
+
+            let commitments_from = diesel::update(
+                commitments::table.filter(commitments::from_entity.eq(&duplicate.id)),
+            )
+            .set(commitments::from_entity.eq(&canonical.id))
+            .execute(conn)?;
+            let commitments_to = diesel::update(
+                commitments::table.filter(commitments::to_entity.eq(&duplicate.id)),
+            )
+            .set(commitments::to_entity.eq(&canonical.id))
+            .execute(conn)?;
+
+            let goals_repointed = diesel::update(
+                entity_goals::table.filter(entity_goals::entity_id.eq(&duplicate.id)),
+            )
+            .set(entity_goals::entity_id.eq(&canonical.id))
+            .execute(conn)?;
+
+            entity_model::delete_entity(conn, &duplicate.id)?;
+
+            Ok(EntityMerge {
+                canonical: EntityRef {
+                    id: canonical.id,
+                    name: canonical.name,
+                    entity_type: canonical.entity_type,
+                },
+                merged_id: duplicate.id,
+                flows_repointed: moved.len(),
+                actions_repointed,
+                users_repointed,
+                templates_repointed: templates_from + templates_to,
+                commitments_repointed: commitments_from + commitments_to,
+                goals_repointed,
+                aliases,
+            })
+        })
+    }
+
+    /// Pairs of entities on a host that look like the same actor.
+    pub fn find_duplicates(conn: &mut DbConn, host: i32) -> Result<Vec<DuplicateCandidate>, AppError> {
+        let all: Vec<Entity> = entities::table
+            .filter(entities::host_id.eq(host))
+            .order(entities::name.asc())
+            .select(Entity::as_select())
+            .load(conn)?;
+
+        let aliases: Vec<(String, String)> = entity_aliases::table
+            .inner_join(entities::table)
+            .filter(entities::host_id.eq(host))
+            .select((entity_aliases::entity_id, entity_aliases::normalized))
+            .load(conn)?;
+        let mut aliases_by_entity: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (entity_id, normalized) in &aliases {
+            aliases_by_entity
+                .entry(entity_id.as_str())
+                .or_default()
+                .push(normalized.as_str());
+        }
+
+        let mut candidates = Vec::new();
+        for (i, a) in all.iter().enumerate() {
+            for b in &all[i + 1..] {
+                let reason = duplicate_reason(a, b, &aliases_by_entity)
+                    .or_else(|| duplicate_reason(b, a, &aliases_by_entity));
+                if let Some(reason) = reason {
+                    candidates.push(DuplicateCandidate {
+                        entities: [entity_ref(a), entity_ref(b)],
+                        reason,
+                    });
+                }
+            }
+        }
+        Ok(candidates)
+    }
+
+    /// Host admins, or users linked to the entity as an active owner, may
+    /// edit, archive or delete it.
+    pub fn require_manager(
+        conn: &mut DbConn,
+        entity: &Entity,
+        user: i32,
+        is_host_admin: bool,
+    ) -> Result<(), AppError> {
+        if is_host_admin || Self::active_role(conn, &entity.id, user)? == Some(EntityRole::Owner) {
+            return Ok(());
+        }
+        Err(AuthError::Forbidden("Only host admins or entity owners can change this entity").into())
+    }
+
+    /// Host admins, owners and stewards may invite people and change roles.
+    pub fn require_people_manager(
+        conn: &mut DbConn,
+        entity: &Entity,
+        user: i32,
+        is_host_admin: bool,
+    ) -> Result<(), AppError> {
+        if is_host_admin
+            || Self::active_role(conn, &entity.id, user)?.is_some_and(EntityRole::manages_people)
+        {
+            return Ok(());
+        }
+        Err(AuthError::Forbidden("Only host admins, owners or stewards can manage people").into())
+    }
+
+    /// Stewards manage people below owner; granting, changing or replacing
+    /// an owner role takes an owner or host admin.
+    fn require_role_grant(
+        conn: &mut DbConn,
+        entity: &Entity,
+        user: i32,
+        is_host_admin: bool,
+        current: Option<&str>,
+        role: EntityRole,
+    ) -> Result<(), AppError> {
+        Self::require_people_manager(conn, entity, user, is_host_admin)?;
+        if role == EntityRole::Owner || current == Some(EntityRole::Owner.value()) {
+            Self::require_manager(conn, entity, user, is_host_admin)?;
+        }
+        Ok(())
+    }
+
+    /// Host admins, or any active non-observer link, may record flows
+    /// on behalf of the entity.
+    pub fn require_recorder(
+        conn: &mut DbConn,
+        entity_id: &str,
+        user: i32,
+        is_host_admin: bool,
+    ) -> Result<(), AppError> {
+        if is_host_admin || Self::active_role(conn, entity_id, user)?.is_some_and(EntityRole::records_flows) {
+            return Ok(());
+        }
+        Err(AuthError::Forbidden("You are not linked to this entity").into())
+    }
+
+    /// The user's role on the entity, if their link is active.
+    pub fn active_role(
+        conn: &mut DbConn,
+        entity_id: &str,
+        user: i32,
+    ) -> Result<Option<EntityRole>, AppError> {
+        let role: Option<String> = entity_users::table
+            .filter(entity_users::entity_id.eq(entity_id))
+            .filter(entity_users::user_id.eq(user))
+            .filter(entity_users::status.eq(EntityUserStatus::Active.value()))
+            .select(entity_users::role)
+            .first(conn)
+            .optional()?;
+        Ok(role.as_deref().and_then(EntityRole::from_value))
+    }
+
+    // ----------------------------
+    // PEOPLE
+    // ----------------------------
+
+    pub fn list_people(conn: &mut DbConn, entity: &Entity) -> Result<Vec<EntityPerson>, AppError> {
+        entity_users::table
+            .inner_join(users::table)
+            .filter(entity_users::entity_id.eq(&entity.id))
+            .order(users::username.asc())
+            .select((
+                entity_users::user_id,
+                users::username,
+                entity_users::role,
+                entity_users::status,
+                entity_users::created_at,
+            ))
+            .load(conn)
+            .map_err(AppError::Db)
+    }
+
+    /// Invites a user by id or email on behalf of `actor`. Declined
+    /// invitations can be re-sent; existing active links are left alone.
+    pub fn invite(
+        conn: &mut DbConn,
+        entity: &Entity,
+        actor: i32,
+        is_host_admin: bool,
+        user: UserLookup<'_>,
+        role: EntityRole,
+    ) -> Result<EntityUser, AppError> {
+        let user_id: i32 = match user {
+            UserLookup::Id(id) => users::table.find(id).select(users::id).first(conn),
+            UserLookup::Email(email) => users::table
+                .filter(users::email.eq(email.trim().to_lowercase()))
+                .select(users::id)
+                .first(conn),
+        }
+        .optional()?
+        .ok_or_else(|| AppError::NotFound("User".to_string()))?;
+
+        let existing = Self::get_link(conn, &entity.id, user_id)?;
+        let current = existing.as_ref().map(|link| link.role.as_str());
+        Self::require_role_grant(conn, entity, actor, is_host_admin, current, role)?;
+        match existing {
+            Some(link) if link.status == EntityUserStatus::Active.value() => Err(AppError::BadRequest(
+                "User is already linked to this entity".into(),
+            )),
+            Some(link) => {
+                diesel::update(entity_users::table.find(link.id))
+                    .set((
+                        entity_users::role.eq(role.value()),
+                        entity_users::status.eq(EntityUserStatus::Invited.value()),
+                    ))
+                    .execute(conn)?;
+                Ok(entity_model::get_entity_user(conn, link.id)?)
+            }
+            None => {
+                diesel::insert_into(entity_users::table)
+                    .values(&NewEntityUser {
+                        entity_id: &entity.id,
+                        user_id,
+                        role: role.value(),
+                        status: EntityUserStatus::Invited.value(),
+                    })
+                    .execute(conn)?;
+                Self::get_link(conn, &entity.id, user_id)?
+                    .ok_or_else(|| AppError::Internal("Invitation was not saved".into()))
+            }
+        }
+    }
+
+    /// The invited user accepts or declines their own pending invitation.
+    pub fn respond_to_invite(
+        conn: &mut DbConn,
+        entity: &Entity,
+        user: i32,
+        accept: bool,
+    ) -> Result<EntityUser, AppError> {
+        let link = Self::get_link(conn, &entity.id, user)?
+            .filter(|l| l.status == EntityUserStatus::Invited.value())
+            .ok_or_else(|| AppError::NotFound("Invitation".to_string()))?;
+
+        let status = if accept {
+            EntityUserStatus::Active
+        } else {
+            EntityUserStatus::Declined
+        };
+        diesel::update(entity_users::table.find(link.id))
+            .set(entity_users::status.eq(status.value()))
+            .execute(conn)?;
+        Ok(entity_model::get_entity_user(conn, link.id)?)
+    }
+
+    /// Changes a linked user's role on behalf of `actor`. The last active
+    /// owner cannot be demoted.
+    pub fn set_role(
+        conn: &mut DbConn,
+        entity: &Entity,
+        actor: i32,
+        is_host_admin: bool,
+        user: i32,
+        role: EntityRole,
+    ) -> Result<EntityUser, AppError> {
+        let link = Self::get_link(conn, &entity.id, user)?
+            .ok_or_else(|| AppError::NotFound("Entity user".to_string()))?;
+        Self::require_role_grant(conn, entity, actor, is_host_admin, Some(&link.role), role)?;
+
+        if link.role == EntityRole::Owner.value() && role != EntityRole::Owner {
+            let owners: i64 = entity_users::table
+                .filter(entity_users::entity_id.eq(&entity.id))
+                .filter(entity_users::role.eq(EntityRole::Owner.value()))
+                .filter(entity_users::status.eq(EntityUserStatus::Active.value()))
+                .count()
+                .get_result(conn)?;
+            if owners <= 1 && link.status == EntityUserStatus::Active.value() {
+                return Err(AppError::BadRequest("An entity must keep at least one owner".into()));
+            }
+        }
+
+        diesel::update(entity_users::table.find(link.id))
+            .set(entity_users::role.eq(role.value()))
+            .execute(conn)?;
+        Ok(entity_model::get_entity_user(conn, link.id)?)
+    }
+
+    fn get_link(conn: &mut DbConn, entity_id: &str, user: i32) -> Result<Option<EntityUser>, AppError> {
+        entity_users::table
+            .filter(entity_users::entity_id.eq(entity_id))
+            .filter(entity_users::user_id.eq(user))
+            .select(EntityUser::as_select())
+            .first(conn)
+            .optional()
+            .map_err(AppError::Db)
+    }
+
+    pub fn update_entity(
+        conn: &mut DbConn,
+        entity: &Entity,
+        changes: EntityChanges,
+    ) -> Result<Entity, AppError> {
+        if changes.name.is_none()
+            && changes.entity_type.is_none()
+            && changes.details.is_none()
+            && changes.archived_at.is_none()
+            && changes.visibility.is_none()
+            && changes.geometry.is_none()
+        {
+            return Err(AppError::BadRequest("No changes given".into()));
+        }
+
+        if let Some(name) = &changes.name {
+            if name.trim().is_empty() {
+                return Err(AppError::BadRequest("Entity name cannot be empty".into()));
+            }
+            let taken: i64 = entities::table
+                .filter(entities::host_id.eq(entity.host_id))
+                .filter(entities::name.eq(name))
+                .filter(entities::id.ne(&entity.id))
+                .count()
+                .get_result(conn)?;
+            if taken > 0 {
+                return Err(AppError::BadRequest(format!("An entity named '{}' already exists", name)));
+            }
+        }
+
+        diesel::update(entities::table.find(&entity.id))
+            .set(&changes)
+            .execute(conn)?;
+        Self::get_on_host(conn, entity.host_id, &entity.id)
+    }
+
+    /// Refuses while any flow or flow action references the entity, since
+    /// the ledger is append-only, or while a flow template or commitment
+    /// still names it. Aliases, user links and goals go with it.
+    pub fn delete_entity(conn: &mut DbConn, entity: &Entity) -> Result<(), AppError> {
+        let flows: i64 = flow_events::table
+            .filter(
+                flow_events::from_entity
+                    .eq(&entity.id)
+                    .or(flow_events::to_entity.eq(&entity.id)),
+            )
+            .count()
+            .get_result(conn)?;
+        let actions: i64 = flow_actions::table
+            .filter(flow_actions::actor_entity.eq(&entity.id))
+            .count()
+            .get_result(conn)?;
+        if flows > 0 || actions > 0 {
+            return Err(AppError::BadRequest(format!(
+                "{} is referenced by {} flows and {} flow actions; archive it instead",
+                entity.name, flows, actions
+            )));
+        }
+        let templates: i64 = flow_templates::table
+            .filter(
+                flow_templates::from_entity
+                    .eq(&entity.id)
+                    .or(flow_templates::to_entity.eq(&entity.id)),
+            )
+            .count()
+            .get_result(conn)?;
+        if templates > 0 {
+            return Err(AppError::BadRequest(format!(
+                "{} is referenced by {} flow templates; archive it instead",
+                entity.name, templates
+            )));
+        }
+        let commitments: i64 = commitments::table
+            .filter(
+                commitments::from_entity
+                    .eq(&entity.id)
+                    .or(commitments::to_entity.eq(&entity.id)),
+            )
+            .count()
+            .get_result(conn)?;
+        if commitments > 0 {
+            return Err(AppError::BadRequest(format!(
+                "{} is referenced by {} commitments; archive it instead",
+                entity.name, commitments
+            )));
+        }
+
+        conn.transaction(|conn| {
+            diesel::delete(entity_users::table.filter(entity_users::entity_id.eq(&entity.id)))
+                .execute(conn)?;
+            diesel::delete(entity_aliases::table.filter(entity_aliases::entity_id.eq(&entity.id)))
+                .execute(conn)?;
+            // foreign keys are off on pooled connections, so no cascade
+            diesel::delete(entity_goals::table.filter(entity_goals::entity_id.eq(&entity.id)))
+                .execute(conn)?;
+            entity_model::delete_entity(conn, &entity.id)?;
+            Ok(())
+        })
+    }
+
+    pub fn get_on_host(conn: &mut DbConn, host: i32, id: &str) -> Result<Entity, AppError> {
+        entities::table
+            .find(id)
+            .filter(entities::host_id.eq(host))
+            .select(Entity::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Entity {}", id)))
+    }
+
+    /// The subset of `ids` whose entity visibility lets `viewer` see them.
+    pub fn visible_ids(
+        conn: &mut DbConn,
+        viewer: &FlowViewer,
+        ids: &[String],
+    ) -> Result<HashSet<String>, AppError> {
+        if *viewer == FlowViewer::System {
+            return Ok(ids.iter().cloned().collect());
+        }
+
+        let mut query = entities::table
+            .filter(entities::id.eq_any(ids))
+            .select(entities::id)
+            .into_boxed();
+        query = match viewer {
+            FlowViewer::User { user_id, .. } => {
+                let linked = |manager_only: bool| {
+                    let mut links = entity_users::table
+                        .filter(entity_users::user_id.eq(*user_id))
+                        .filter(entity_users::status.eq(EntityUserStatus::Active.value()))
+                        .select(entity_users::entity_id)
+                        .into_boxed();
+                    if manager_only {
+                        links = links.filter(
+                            entity_users::role
+                                .eq_any([EntityRole::Owner.value(), EntityRole::Steward.value()]),
+                        );
+                    }
+                    links
+                };
+                query.filter(
+                    entities::visibility
+                        .eq_any(viewer.open_levels())
+                        .or(entities::visibility
+                            .eq(Visibility::Stewards.value())
+                            .and(entities::id.eq_any(linked(true))))
+                        .or(entities::visibility
+                            .eq(Visibility::Parties.value())
+                            .and(entities::id.eq_any(linked(false)))),
+                )
+            }
+            _ => query.filter(entities::visibility.eq_any(viewer.open_levels())),
+        };
+        Ok(query.load::<String>(conn)?.into_iter().collect())
+    }
+
+    /// Replaces the names of entities `viewer` may not see.
+    pub fn anonymise(
+        conn: &mut DbConn,
+        viewer: &FlowViewer,
+        refs: &mut [EntityRef],
+    ) -> Result<(), AppError> {
+        let ids: Vec<String> = refs.iter().map(|e| e.id.clone()).collect();
+        let visible = Self::visible_ids(conn, viewer, &ids)?;
+        for entity in refs.iter_mut().filter(|e| !visible.contains(&e.id)) {
+            entity.name = ANONYMOUS.to_string();
+        }
+        Ok(())
+    }
+}
+
+/// Shown in place of an entity's name when its visibility hides it.
+pub const ANONYMOUS: &str = "Anonymous";
+
+/// Trims and lower-cases; for email addresses also drops a `mailto:` prefix
+/// and any `+tag` in the local part.
+pub fn normalize_alias(input: &str) -> String {
+    let lowered = input.trim().to_lowercase();
+    let lowered = lowered.strip_prefix("mailto:").unwrap_or(&lowered);
+
+    match lowered.split_once('@') {
+        Some((local, domain)) => {
+            let local = local.split('+').next().unwrap_or(local);
+            format!("{}@{}", local, domain)
+        }
+        None => lowered.split_whitespace().collect::<Vec<_>>().join(" "),
+    }
+}
+
+/// Turns `jane.doe@x.org` into `jane doe`; `None` for non-email names.
+fn email_person_name(normalized: &str) -> Option<String> {
+    let (local, _) = normalized.split_once('@')?;
+    Some(
+        local
+            .split(['.', '_', '-'])
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+fn duplicate_reason(
+    a: &Entity,
+    b: &Entity,
+    aliases: &HashMap<&str, Vec<&str>>,
+) -> Option<&'static str> {
+    let a_name = normalize_alias(&a.name);
+    let b_name = normalize_alias(&b.name);
+
+    if a_name == b_name {
+        return Some("same_name");
+    }
+    if aliases
+        .get(a.id.as_str())
+        .is_some_and(|list| list.contains(&b_name.as_str()))
+    {
+        return Some("alias_matches_name");
+    }
+    if let Some(person) = email_person_name(&a_name) {
+        let b_first = b_name.split(' ').next().unwrap_or_default();
+        if person == b_name || (!b_first.is_empty() && person == b_first) {
+            return Some("email_matches_name");
+        }
+    }
+    // "Jane" vs "Jane Doe"
+    if !a_name.contains(' ') && !a_name.contains('@') && b_name.starts_with(&format!("{} ", a_name)) {
+        return Some("name_is_prefix");
+    }
+    None
+}
+
+fn entity_ref(entity: &Entity) -> EntityRef {
+    EntityRef {
+        id: entity.id.clone(),
+        name: entity.name.clone(),
+        entity_type: entity.entity_type.clone(),
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::{NewFlowEvent, create_flow_event};
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::JsonField;
+
+    #[test]
+    fn normalizes_emails_and_names() {
+        assert_eq!(normalize_alias("  Jane.Doe+ledger@X.org "), "jane.doe@x.org");
+        assert_eq!(normalize_alias("mailto:jane@x.org"), "jane@x.org");
+        assert_eq!(normalize_alias("Jane   Doe"), "jane doe");
+    }
+
+    #[test]
+    fn merge_repoints_flows_and_keeps_old_id_resolvable() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane Doe", "Person").id;
+        let jane_email = create_test_entity(&mut conn, 1, "jane.doe@x.org", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+
+        let flow = create_flow_event(
+            &mut conn,
+            &NewFlowEvent {
+                id: Uuid::new_v4().to_string(),
+                timestamp: now,
+                recorded_at: now,
+                from_entity: jane_email.clone(),
+                to_entity: garden.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: 2.0,
+                quantity_unit: "hours".to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: jane_email.clone(),
+            },
+        )
+        .unwrap();
+
+        let report = EntityService::find_duplicates(&mut conn, 1).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].reason, "email_matches_name");
+
+        let merge = EntityService::merge_entities(&mut conn, 1, &jane, &jane_email, &garden).unwrap();
+        assert_eq!(merge.flows_repointed, 1);
+
+        let moved = flow_events::table
+            .find(&flow.id)
+            .select(flow_events::from_entity)
+            .first::<String>(&mut conn)
+            .unwrap();
+        assert_eq!(moved, jane);
+
+        let found = EntityService::find_by_name_or_alias(&mut conn, "Jane.Doe+x@X.org", 1).unwrap();
+        assert_eq!(found.id, jane);
+        let found = EntityService::find_by_name_or_alias(&mut conn, &jane_email, 1).unwrap();
+        assert_eq!(found.id, jane);
+
+        // content columns stay append-only
+        let edit = diesel::update(flow_events::table.find(&flow.id))
+            .set(flow_events::quantity_value.eq(5.0))
+            .execute(&mut conn);
+        assert!(edit.is_err());
+    }
+
+    #[test]
+    fn aliases_are_renormalized_and_ids_resolve_on_their_host() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane Doe", "Person").id;
+        let elsewhere = create_test_entity(&mut conn, 2, "Elsewhere", "Person").id;
+
+        // as the SQL backfill left it
+        diesel::insert_into(entity_aliases::table)
+            .values((
+                entity_aliases::id.eq(Uuid::new_v4().to_string()),
+                entity_aliases::entity_id.eq(&jane),
+                entity_aliases::alias.eq("mailto:Jane+news@X.org"),
+                entity_aliases::created_by.eq("System"),
+                entity_aliases::normalized.eq("mailto:jane+news@x.org"),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        assert_eq!(EntityService::normalize_aliases(&mut conn).unwrap(), 1);
+        assert_eq!(EntityService::resolve(&mut conn, "jane@x.org", 1).unwrap(), jane);
+
+        assert_eq!(EntityService::resolve(&mut conn, &elsewhere, 2).unwrap(), elsewhere);
+        assert!(EntityService::resolve(&mut conn, &elsewhere, 1).is_err());
+    }
+
+    #[test]
+    fn owners_edit_and_referenced_entities_cannot_be_deleted() {
+        use crate::models::entities::{NewEntityUser, create_entity_user};
+        use crate::services::ledger_service::LedgerService;
+
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person");
+
+        assert!(EntityService::require_manager(&mut conn, &garden, user_id, false).is_err());
+        create_entity_user(
+            &mut conn,
+            &NewEntityUser {
+                entity_id: &garden.id,
+                user_id,
+                role: EntityRole::Owner.value(),
+                status: "active",
+            },
+        )
+        .unwrap();
+        EntityService::require_manager(&mut conn, &garden, user_id, false).unwrap();
+
+        let changes = EntityChanges {
+            archived_at: Some(Some(now)),
+            ..Default::default()
+        };
+        EntityService::update_entity(&mut conn, &garden, changes).unwrap();
+        let listed = LedgerService::get_entities(&mut conn, 1, false).unwrap();
+        assert!(listed.iter().all(|e| e.id != garden.id));
+
+        create_flow_event(
+            &mut conn,
+            &NewFlowEvent {
+                id: Uuid::new_v4().to_string(),
+                timestamp: now,
+                recorded_at: now,
+                from_entity: jane.clone(),
+                to_entity: garden.id.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: 1.0,
+                quantity_unit: "hours".to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: jane.clone(),
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            EntityService::delete_entity(&mut conn, &garden),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn merge_and_delete_handle_dependent_rows() {
+        use crate::models::commitments::{NewCommitment, create_commitment};
+        use crate::models::entity_goals::{NewEntityGoal, create_goal};
+        use crate::models::flow_templates::{NewFlowTemplate, create_flow_template};
+        use crate::types::Recurrence;
+
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane Doe", "Person").id;
+        let jane_email = create_test_entity(&mut conn, 1, "jane.doe@x.org", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person");
+        let pantry = create_test_entity(&mut conn, 1, "Pantry", "Person");
+        let shed = create_test_entity(&mut conn, 1, "Shed", "Person");
+
+        let goal = |entity_id: &str| NewEntityGoal {
+            host_id: 1,
+            entity_id: entity_id.to_string(),
+            label: "Volunteer hours".to_string(),
+            resource_type: "labor_time".to_string(),
+            target_value: 10.0,
+            unit: "hours".to_string(),
+            period_start: now,
+            period_end: now + chrono::Duration::days(30),
+            created_by: jane.clone(),
+        };
+        create_goal(&mut conn, &goal(&jane_email)).unwrap();
+        create_goal(&mut conn, &goal(&shed.id)).unwrap();
+
+        let template = create_flow_template(
+            &mut conn,
+            &NewFlowTemplate {
+                id: Uuid::new_v4().to_string(),
+                host_id: 1,
+                from_entity: jane_email.clone(),
+                to_entity: garden.id.clone(),
+                resource_type: "labor_time".to_string(),
+                quantity_value: 2.0,
+                quantity_unit: "hours".to_string(),
+                notes: None,
+                details: JsonField::default(),
+                recurrence: Recurrence::Weekly.value().to_string(),
+                every: 1,
+                starts_at: now,
+                ends_at: None,
+                next_run: now,
+                created_by: jane_email.clone(),
+            },
+        )
+        .unwrap();
+        let commitment = create_commitment(
+            &mut conn,
+            &NewCommitment {
+                id: Uuid::new_v4().to_string(),
+                host_id: 1,
+                from_entity: jane_email.clone(),
+                to_entity: pantry.id.clone(),
+                resource_type: "labor_time".to_string(),
+                quantity_value: 4.0,
+                quantity_unit: "hours".to_string(),
+                due_date: now,
+                notes: None,
+                details: JsonField::default(),
+                created_by: jane_email.clone(),
+            },
+        )
+        .unwrap();
+
+        let merge = EntityService::merge_entities(&mut conn, 1, &jane, &jane_email, &garden.id).unwrap();
+        assert_eq!(merge.templates_repointed, 1);
+        assert_eq!(merge.commitments_repointed, 1);
+        assert_eq!(merge.goals_repointed, 1);
+        let from = flow_templates::table
+            .find(&template.id)
+            .select(flow_templates::from_entity)
+            .first::<String>(&mut conn)
+            .unwrap();
+        assert_eq!(from, jane);
+        let from = commitments::table
+            .find(&commitment.id)
+            .select(commitments::from_entity)
+            .first::<String>(&mut conn)
+            .unwrap();
+        assert_eq!(from, jane);
+
+        for referenced in [&garden, &pantry] {
+            assert!(matches!(
+                EntityService::delete_entity(&mut conn, referenced),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+
+        EntityService::delete_entity(&mut conn, &shed).unwrap();
+        let goals: Vec<String> = entity_goals::table
+            .select(entity_goals::entity_id)
+            .load(&mut conn)
+            .unwrap();
+        assert_eq!(goals, [jane]);
+    }
+
+    #[test]
+    fn invited_users_record_only_after_accepting() {
+        use crate::models::users::create_user;
+
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person");
+        let sam = create_user(&mut conn, "sam", Some("sam@example.com")).unwrap().id;
+
+        let link = EntityService::invite(
+            &mut conn,
+            &garden,
+            user_id,
+            true,
+            UserLookup::Email("Sam@Example.com"),
+            EntityRole::Observer,
+        )
+        .unwrap();
+        assert_eq!(link.status, EntityUserStatus::Invited.value());
+        assert!(EntityService::require_recorder(&mut conn, &garden.id, sam, false).is_err());
+
+        EntityService::respond_to_invite(&mut conn, &garden, sam, true).unwrap();
+        // observers can see but not record
+        assert!(EntityService::require_recorder(&mut conn, &garden.id, sam, false).is_err());
+
+        EntityService::set_role(&mut conn, &garden, user_id, true, sam, EntityRole::Steward).unwrap();
+        EntityService::require_recorder(&mut conn, &garden.id, sam, false).unwrap();
+        EntityService::require_people_manager(&mut conn, &garden, sam, false).unwrap();
+
+        let people = EntityService::list_people(&mut conn, &garden).unwrap();
+        assert_eq!(people.len(), 1);
+        assert_eq!(people[0].role, "steward");
+    }
+
+    #[test]
+    fn stewards_cannot_grant_or_take_ownership() {
+        use crate::models::entities::{NewEntityUser, create_entity_user};
+        use crate::models::users::create_user;
+
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person");
+        let steward = create_user(&mut conn, "sam", Some("sam@example.com")).unwrap().id;
+        let member = create_user(&mut conn, "kim", Some("kim@example.com")).unwrap().id;
+        let newcomer = create_user(&mut conn, "lee", Some("lee@example.com")).unwrap().id;
+        for (user, role) in [
+            (user_id, EntityRole::Owner),
+            (steward, EntityRole::Steward),
+            (member, EntityRole::Member),
+        ] {
+            create_entity_user(
+                &mut conn,
+                &NewEntityUser {
+                    entity_id: &garden.id,
+                    user_id: user,
+                    role: role.value(),
+                    status: "active",
+                },
+            )
+            .unwrap();
+        }
+        let forbidden = |result: Result<EntityUser, AppError>| {
+            matches!(result, Err(AppError::Auth(AuthError::Forbidden(_))))
+        };
+
+        // invite someone straight in as owner
+        assert!(forbidden(EntityService::invite(
+            &mut conn,
+            &garden,
+            steward,
+            false,
+            UserLookup::Id(newcomer),
+            EntityRole::Owner,
+        )));
+        // promote a member to owner
+        assert!(forbidden(EntityService::set_role(
+            &mut conn, &garden, steward, false, member, EntityRole::Owner
+        )));
+        // demote the existing owner
+        assert!(forbidden(EntityService::set_role(
+            &mut conn, &garden, steward, false, user_id, EntityRole::Member
+        )));
+
+        // stewards still manage roles below owner, and owners hand out ownership
+        EntityService::set_role(&mut conn, &garden, steward, false, member, EntityRole::Observer).unwrap();
+        EntityService::invite(&mut conn, &garden, steward, false, UserLookup::Id(newcomer), EntityRole::Member)
+            .unwrap();
+        EntityService::set_role(&mut conn, &garden, user_id, false, member, EntityRole::Owner).unwrap();
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashSet;
+
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::entity_types::{self as type_model, EntityType, NewEntityType};
+use crate::schema::{entities, entity_types};
+use crate::types::{FieldSchema, check_fields};
+
+/// Field types the validator understands; anything else is rejected when
+/// a type is saved so typos don't silently accept everything.
+const FIELD_TYPES: &[&str] = &[
+    "text", "textarea", "markdown", "hidden", "email", "url", "number", "date", "select",
+    "multiselect", "checkbox", "boolean",
+];
+
+/// Types every host is seeded with. Reports, goals and the vital-signs
+/// queries match on these keys, so they cannot be renamed or deleted.
+pub const BUILT_IN_ENTITY_TYPES: [&str; 4] = ["Person", "project", "team", "organization"];
+
+pub struct EntityTypeService;
+
+impl EntityTypeService {
+    pub fn get_registry(conn: &mut DbConn, host: i32) -> Result<Vec<EntityType>, AppError> {
+        type_model::get_entity_types(conn, host).map_err(AppError::Db)
+    }
+
+    pub fn create(conn: &mut DbConn, new: NewEntityType) -> Result<EntityType, AppError> {
+        Self::check(&new)?;
+        let exists = type_model::get_entity_type(conn, new.host_id, &new.key)
+            .optional()?
+            .is_some();
+        if exists {
+            return Err(AppError::BadRequest(format!(
+                "Entity type '{}' already exists",
+                new.key
+            )));
+        }
+        type_model::create_entity_type(conn, &new).map_err(AppError::Db)
+    }
+
+    /// Renaming a type's key moves its entities with it.
+    pub fn update(conn: &mut DbConn, id: i32, new: NewEntityType) -> Result<EntityType, AppError> {
+        Self::check(&new)?;
+        let existing = Self::get_on_host(conn, new.host_id, id)?;
+        if existing.key != new.key && is_built_in(&existing.key) {
+            return Err(AppError::BadRequest(format!(
+                "'{}' is a built-in entity type and cannot be renamed",
+                existing.key
+            )));
+        }
+        conn.transaction(|conn| {
+            if existing.key != new.key {
+                diesel::update(
+                    entities::table
+                        .filter(entities::host_id.eq(new.host_id))
+                        .filter(entities::entity_type.eq(&existing.key)),
+                )
+                .set(entities::entity_type.eq(&new.key))
+                .execute(conn)?;
+            }
+            type_model::update_entity_type(conn, id, &new).map_err(AppError::Db)
+        })
+    }
+
+    pub fn delete(conn: &mut DbConn, host: i32, id: i32) -> Result<(), AppError> {
+        let existing = Self::get_on_host(conn, host, id)?;
+        if is_built_in(&existing.key) {
+            return Err(AppError::BadRequest(format!(
+                "'{}' is a built-in entity type and cannot be deleted",
+                existing.key
+            )));
+        }
+        let in_use: i64 = entities::table
+            .filter(entities::host_id.eq(host))
+            .filter(entities::entity_type.eq(&existing.key))
+            .count()
+            .get_result(conn)?;
+        if in_use > 0 {
+            return Err(AppError::BadRequest(format!(
+                "{} entities still use '{}'",
+                in_use, existing.key
+            )));
+        }
+        type_model::delete_entity_type(conn, id)?;
+        Ok(())
+    }
+
+    /// The registered key for `entity_type`, matched case-insensitively.
+    /// Hosts that have not set up a schema accept anything as given.
+    pub fn resolve_type(conn: &mut DbConn, host: i32, entity_type: &str) -> Result<String, AppError> {
+        let registry = Self::get_registry(conn, host)?;
+        if registry.is_empty() {
+            return Ok(entity_type.to_string());
+        }
+        registry
+            .iter()
+            .find(|t| t.key.eq_ignore_ascii_case(entity_type.trim()))
+            .map(|t| t.key.clone())
+            .ok_or_else(|| {
+                let known: Vec<&str> = registry.iter().map(|t| t.key.as_str()).collect();
+                AppError::BadRequest(format!(
+                    "Unknown entity type '{}'; expected one of: {}",
+                    entity_type,
+                    known.join(", ")
+                ))
+            })
+    }
+
+    /// Checks an entity's type and details against the host schema and
+    /// returns the registered type key to store.
+    pub fn validate_entity(
+        conn: &mut DbConn,
+        host: i32,
+        entity_type: &str,
+        details: &Value,
+    ) -> Result<String, AppError> {
+        let key = Self::resolve_type(conn, host, entity_type)?;
+        let Some(schema) = type_model::get_entity_type(conn, host, &key).optional()? else {
+            return Ok(key);
+        };
+
+        let problems = check_fields(&schema.field_list(), details);
+        if !problems.is_empty() {
+            let messages: Vec<String> = problems.into_iter().map(|(_, message)| message).collect();
+            return Err(AppError::BadRequest(messages.join("; ")));
+        }
+        Ok(key)
+    }
+
+    fn check(new: &NewEntityType) -> Result<(), AppError> {
+        if new.key.trim().is_empty() || new.label.trim().is_empty() {
+            return Err(AppError::BadRequest("Entity type key and label are required".into()));
+        }
+        let fields: Vec<FieldSchema> = serde_json::from_value(new.fields.0.clone())
+            .map_err(|e| AppError::BadRequest(format!("Invalid fields: {}", e)))?;
+
+        let mut keys = HashSet::new();
+        for field in &fields {
+            if field.key.trim().is_empty() || !keys.insert(field.key.as_str()) {
+                return Err(AppError::BadRequest(format!(
+                    "Field keys must be present and unique ('{}')",
+                    field.key
+                )));
+            }
+            if !FIELD_TYPES.contains(&field.field_type.as_str()) {
+                return Err(AppError::BadRequest(format!(
+                    "Field '{}' has unknown type '{}'; expected one of: {}",
+                    field.key,
+                    field.field_type,
+                    FIELD_TYPES.join(", ")
+                )));
+            }
+            if matches!(field.field_type.as_str(), "select" | "multiselect")
+                && field.options.as_ref().is_none_or(Vec::is_empty)
+            {
+                return Err(AppError::BadRequest(format!("Field '{}' needs options", field.key)));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_on_host(conn: &mut DbConn, host: i32, id: i32) -> Result<EntityType, AppError> {
+        entity_types::table
+            .find(id)
+            .filter(entity_types::host_id.eq(host))
+            .select(EntityType::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Entity type {}", id)))
+    }
+}
+
+fn is_built_in(key: &str) -> bool {
+    BUILT_IN_ENTITY_TYPES.contains(&key)
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_support::db::setup_test_db;
+    use crate::types::JsonField;
+    use serde_json::json;
+
+    #[test]
+    fn admins_add_types_that_validate_details() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        assert_eq!(EntityTypeService::resolve_type(&mut conn, 1, "person").unwrap(), "Person");
+        assert!(EntityTypeService::resolve_type(&mut conn, 1, "farm").is_err());
+
+        EntityTypeService::create(
+            &mut conn,
+            NewEntityType {
+                host_id: 1,
+                key: "farm".to_string(),
+                label: "Farm".to_string(),
+                fields: JsonField(json!([
+                    { "key": "acres", "label": "Acres", "type": "number", "required": true }
+                ])),
+            },
+        )
+        .unwrap();
+
+        let valid = EntityTypeService::validate_entity(&mut conn, 1, "Farm", &json!({ "acres": 40 }));
+        assert_eq!(valid.unwrap(), "farm");
+        assert!(matches!(
+            EntityTypeService::validate_entity(&mut conn, 1, "farm", &json!({})),
+            Err(AppError::BadRequest(_))
+        ));
+
+        // host 0 has no schema, so it accepts anything
+        assert_eq!(EntityTypeService::resolve_type(&mut conn, 0, "vibes").unwrap(), "vibes");
+    }
+
+    #[test]
+    fn built_in_types_are_seeded_and_kept() {
+        use crate::schema::hosts;
+
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let project = type_model::get_entity_type(&mut conn, 1, "project").unwrap();
+        let renamed = NewEntityType {
+            host_id: 1,
+            key: "initiative".to_string(),
+            label: "Initiative".to_string(),
+            fields: project.fields.clone(),
+        };
+        assert!(matches!(
+            EntityTypeService::update(&mut conn, project.id, renamed),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(EntityTypeService::delete(&mut conn, 1, project.id).is_err());
+        let relabelled = NewEntityType {
+            host_id: 1,
+            key: "project".to_string(),
+            label: "Initiative".to_string(),
+            fields: project.fields,
+        };
+        EntityTypeService::update(&mut conn, project.id, relabelled).unwrap();
+
+        diesel::insert_into(hosts::table)
+            .values((
+                hosts::slug.eq("ames"),
+                hosts::host_name.eq("ames.example.org"),
+                hosts::display_name.eq("Ames"),
+                hosts::base_url.eq("https://ames.example.org"),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+        let host: i32 = hosts::table
+            .filter(hosts::slug.eq("ames"))
+            .select(hosts::id)
+            .first(&mut conn)
+            .unwrap();
+        let keys: Vec<String> = EntityTypeService::get_registry(&mut conn, host)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.key)
+            .collect();
+        assert!(BUILT_IN_ENTITY_TYPES.iter().all(|key| keys.iter().any(|k| k == key)));
+    }
+}
@@ -0,0 +1,407 @@
+use diesel::prelude::*;
+use serde_json::{Value, json};
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::entities::Entity;
+use crate::models::flow_events::FlowEvent;
+use crate::models::resource_types::ResourceType;
+use crate::schema::{entities, flow_events};
+use crate::types::flow_query::{FlowCursor, FlowQuery, FlowSort};
+
+/// Rows loaded per chunk while streaming an export.
+pub const EXPORT_PAGE_SIZE: i64 = 500;
+
+const VF_CONTEXT: &str = "https://w3id.org/valueflows/ont/vf#";
+const OM2_CONTEXT: &str = "http://www.ontology-of-units-of-measure.org/resource/om-2/";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    JsonLd,
+}
+
+impl ExportFormat {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "csv" => Some(Self::Csv),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "jsonld" => Some(Self::JsonLd),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Ndjson => "application/x-ndjson",
+            Self::JsonLd => "application/ld+json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+            Self::JsonLd => "jsonld",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportKind {
+    Flows,
+    Entities,
+}
+
+const FLOW_COLUMNS: [&str; 15] = [
+    "id",
+    "timestamp",
+    "recorded_at",
+    "from_entity",
+    "to_entity",
+    "resource_type",
+    "quantity_value",
+    "quantity_unit",
+    "notes",
+    "details",
+    "created_by",
+    "host_id",
+    "visibility",
+    "latitude",
+    "longitude",
+];
+
+const ENTITY_COLUMNS: [&str; 12] = [
+    "id",
+    "name",
+    "entity_type",
+    "created_at",
+    "created_by",
+    "archived_at",
+    "details",
+    "host_id",
+    "visibility",
+    "geometry",
+    "latitude",
+    "longitude",
+];
+
+pub struct ExportService;
+
+impl ExportService {
+    /// Flows matching the query's filters, oldest first so an export can be
+    /// replayed in order. Pages continue after `after`, so flows recorded
+    /// mid-export never shift rows between pages.
+    pub fn flows_page(
+        conn: &mut DbConn,
+        flow_query: &FlowQuery,
+        after: Option<FlowCursor>,
+    ) -> Result<Vec<FlowEvent>, AppError> {
+        let mut query = flow_query.clone();
+        query.sort = FlowSort::Oldest;
+        query.cursor = after;
+        query.limit = Some(EXPORT_PAGE_SIZE);
+        query.offset = None;
+
+        query
+            .apply(flow_events::table.into_boxed())
+            .select(FlowEvent::as_select())
+            .load(conn)
+            .map_err(AppError::Db)
+    }
+
+    /// Entities on the query's host, archived ones included, ordered by id
+    /// and continuing after `after`. `entity` narrows to one entity and
+    /// `since`/`until` bound when it was created.
+    pub fn entities_page(
+        conn: &mut DbConn,
+        flow_query: &FlowQuery,
+        after: Option<&str>,
+    ) -> Result<Vec<Entity>, AppError> {
+        let mut query = entities::table
+            .filter(entities::host_id.eq(flow_query.host))
+            .into_boxed();
+        if let Some(entity) = flow_query.entity {
+            query = query.filter(entities::id.eq(entity.to_string()));
+        }
+        if let Some(since) = flow_query.since {
+            query = query.filter(entities::created_at.ge(since));
+        }
+        if let Some(until) = flow_query.until {
+            query = query.filter(entities::created_at.le(until));
+        }
+        if let Some(after) = after {
+            query = query.filter(entities::id.gt(after.to_string()));
+        }
+
+        query
+            .order(entities::id.asc())
+            .limit(EXPORT_PAGE_SIZE)
+            .select(Entity::as_select())
+            .load(conn)
+            .map_err(AppError::Db)
+    }
+
+    pub fn header(kind: ExportKind, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Csv => {
+                let columns: &[&str] = match kind {
+                    ExportKind::Flows => &FLOW_COLUMNS,
+                    ExportKind::Entities => &ENTITY_COLUMNS,
+                };
+                format!("{}\n", columns.join(","))
+            }
+            ExportFormat::Ndjson => String::new(),
+            ExportFormat::JsonLd => {
+                let context = json!({ "vf": VF_CONTEXT, "om2": OM2_CONTEXT });
+                format!("{{\"@context\":{},\"@graph\":[\n", context)
+            }
+        }
+    }
+
+    pub fn footer(format: ExportFormat) -> String {
+        match format {
+            ExportFormat::JsonLd => "\n]}\n".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Renders one page. `first` is true until a node has been written,
+    /// telling JSON-LD whether a separator is needed.
+    pub fn render_flows(
+        format: ExportFormat,
+        flows: &[FlowEvent],
+        registry: &[ResourceType],
+        first: bool,
+    ) -> Result<String, AppError> {
+        match format {
+            ExportFormat::Csv => write_csv(flows.iter().map(|f| {
+                vec![
+                    f.id.clone(),
+                    f.timestamp.to_string(),
+                    f.recorded_at.to_string(),
+                    f.from_entity.clone(),
+                    f.to_entity.clone(),
+                    f.resource_type.clone(),
+                    f.quantity_value.to_string(),
+                    f.quantity_unit.clone(),
+                    f.notes.clone().unwrap_or_default(),
+                    f.details.0.to_string(),
+                    f.created_by.clone(),
+                    f.host_id.to_string(),
+                    f.visibility.clone(),
+                    optional(f.latitude),
+                    optional(f.longitude),
+                ]
+            })),
+            ExportFormat::Ndjson => write_lines(flows.iter().map(|f| json!(f))),
+            ExportFormat::JsonLd => {
+                write_graph(flows.iter().map(|f| economic_event(f, registry)), first)
+            }
+        }
+    }
+
+    pub fn render_entities(
+        format: ExportFormat,
+        entities: &[Entity],
+        first: bool,
+    ) -> Result<String, AppError> {
+        match format {
+            ExportFormat::Csv => write_csv(entities.iter().map(|e| {
+                vec![
+                    e.id.clone(),
+                    e.name.clone(),
+                    e.entity_type.clone(),
+                    e.created_at.to_string(),
+                    e.created_by.clone(),
+                    e.archived_at.map(|t| t.to_string()).unwrap_or_default(),
+                    e.details.0.to_string(),
+                    e.host_id.to_string(),
+                    e.visibility.clone(),
+                    e.geometry.as_ref().map(|g| g.0.to_string()).unwrap_or_default(),
+                    optional(e.latitude),
+                    optional(e.longitude),
+                ]
+            })),
+            ExportFormat::Ndjson => write_lines(entities.iter().map(|e| json!(e))),
+            ExportFormat::JsonLd => write_graph(entities.iter().map(agent), first),
+        }
+    }
+}
+
+fn optional(value: Option<f64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn write_csv(rows: impl Iterator<Item = Vec<String>>) -> Result<String, AppError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let internal = |e: csv::Error| AppError::Internal(e.to_string());
+    for row in rows {
+        writer.write_record(&row).map_err(internal)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+fn write_lines(values: impl Iterator<Item = Value>) -> Result<String, AppError> {
+    Ok(values.map(|v| format!("{}\n", v)).collect())
+}
+
+fn write_graph(nodes: impl Iterator<Item = Value>, first: bool) -> Result<String, AppError> {
+    let nodes: Vec<String> = nodes.map(|n| n.to_string()).collect();
+    if nodes.is_empty() {
+        return Ok(String::new());
+    }
+    let separator = if first { "" } else { ",\n" };
+    Ok(format!("{}{}", separator, nodes.join(",\n")))
+}
+
+fn node_id(id: &str) -> Value {
+    json!({ "@id": format!("urn:uuid:{}", id) })
+}
+
+/// A flow as a ValueFlows `EconomicEvent`. Labor is `work`; everything
+/// else moves between agents as a `transfer`.
+fn economic_event(flow: &FlowEvent, registry: &[ResourceType]) -> Value {
+    let is_labor = registry
+        .iter()
+        .find(|r| r.key == flow.resource_type)
+        .map(|r| r.is_labor)
+        .unwrap_or(flow.resource_type == "labor_time");
+
+    json!({
+        "@id": format!("urn:uuid:{}", flow.id),
+        "@type": "vf:EconomicEvent",
+        "vf:action": if is_labor { "work" } else { "transfer" },
+        "vf:provider": node_id(&flow.from_entity),
+        "vf:receiver": node_id(&flow.to_entity),
+        "vf:resourceClassifiedAs": flow.resource_type,
+        "vf:resourceQuantity": {
+            "@type": "om2:Measure",
+            "om2:hasNumericalValue": flow.quantity_value,
+            "om2:hasUnit": flow.quantity_unit,
+        },
+        "vf:hasPointInTime": flow.timestamp.and_utc().to_rfc3339(),
+        "vf:note": flow.notes,
+    })
+}
+
+fn agent(entity: &Entity) -> Value {
+    let agent_type = if entity.entity_type.eq_ignore_ascii_case("person") {
+        "vf:Person"
+    } else {
+        "vf:Organization"
+    };
+    json!({
+        "@id": format!("urn:uuid:{}", entity.id),
+        "@type": agent_type,
+        "vf:name": entity.name,
+        "vf:classifiedAs": entity.entity_type,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::JsonField;
+
+    #[test]
+    fn json_ld_pages_join_into_one_graph() {
+        let flow = |id: &str| FlowEvent {
+            id: id.to_string(),
+            timestamp: chrono::Utc::now().naive_utc(),
+            recorded_at: chrono::Utc::now().naive_utc(),
+            from_entity: "a".to_string(),
+            to_entity: "b".to_string(),
+            host_id: 1,
+            resource_type: "labor_time".to_string(),
+            quantity_value: 2.0,
+            quantity_unit: "hours".to_string(),
+            notes: None,
+            details: JsonField::default(),
+            created_by: "test".to_string(),
+            visibility: "public".to_string(),
+            latitude: None,
+            longitude: None,
+            chain_seq: None,
+            prev_hash: None,
+            hash: None,
+        };
+
+        let mut doc = ExportService::header(ExportKind::Flows, ExportFormat::JsonLd);
+        doc += &ExportService::render_flows(ExportFormat::JsonLd, &[flow("1"), flow("2")], &[], true).unwrap();
+        doc += &ExportService::render_flows(ExportFormat::JsonLd, &[flow("3")], &[], false).unwrap();
+        doc += &ExportService::render_flows(ExportFormat::JsonLd, &[], &[], false).unwrap();
+        doc += &ExportService::footer(ExportFormat::JsonLd);
+
+        let parsed: Value = serde_json::from_str(&doc).unwrap();
+        let graph = parsed["@graph"].as_array().unwrap();
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph[0]["vf:action"], "work");
+        assert_eq!(graph[2]["@id"], "urn:uuid:3");
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::NewFlowEvent;
+    use crate::services::ledger_service::LedgerService;
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::JsonField;
+    use uuid::Uuid;
+
+    #[test]
+    fn pages_continue_after_the_cursor_and_entities_follow_filters() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+        let base = chrono::Utc::now().naive_utc() - chrono::Duration::days(1);
+        for i in 0..3 {
+            let at = base + chrono::Duration::minutes(i);
+            LedgerService::create_flow_event(
+                &mut conn,
+                NewFlowEvent {
+                    id: Uuid::new_v4().to_string(),
+                    timestamp: at,
+                    recorded_at: at,
+                    from_entity: jane.clone(),
+                    to_entity: garden.clone(),
+                    host_id: 1,
+                    resource_type: "labor_time".to_string(),
+                    quantity_value: (i + 1) as f32,
+                    quantity_unit: "hours".to_string(),
+                    notes: None,
+                    details: JsonField::default(),
+                    created_by: jane.clone(),
+                },
+            )
+            .unwrap();
+        }
+
+        let query = FlowQuery::new(1);
+        let all = ExportService::flows_page(&mut conn, &query, None).unwrap();
+        let quantities: Vec<f32> = all.iter().map(|f| f.quantity_value).collect();
+        assert_eq!(quantities, vec![1.0, 2.0, 3.0]);
+
+        let after = FlowCursor { timestamp: all[0].timestamp, id: all[0].id.clone() };
+        let rest = ExportService::flows_page(&mut conn, &query, Some(after)).unwrap();
+        let quantities: Vec<f32> = rest.iter().map(|f| f.quantity_value).collect();
+        assert_eq!(quantities, vec![2.0, 3.0]);
+
+        let one = query.clone().entity(Uuid::parse_str(&garden).unwrap());
+        let entities = ExportService::entities_page(&mut conn, &one, None).unwrap();
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].id, garden);
+
+        let all = ExportService::entities_page(&mut conn, &query, None).unwrap();
+        let after = ExportService::entities_page(&mut conn, &query, Some(&all[0].id)).unwrap();
+        assert_eq!(after.len(), all.len() - 1);
+    }
+}
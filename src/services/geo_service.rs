@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use serde_json::{Value, json};
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::errors::auth_error::AuthError;
+use crate::models::entities::{Entity, EntityChanges};
+use crate::models::flow_events::FlowEvent;
+use crate::models::ledger_views;
+use crate::schema::{entities, flow_events};
+use crate::services::entity_service::EntityService;
+use crate::services::resource_service::ResourceService;
+use crate::types::JsonField;
+use crate::types::flow_query::{FlowQuery, FlowViewer};
+use crate::types::geo::{GeoArea, GeoPoint, feature, feature_collection, parse_geometry};
+
+struct LocationTotal {
+    point: GeoPoint,
+    unit: String,
+    quantity: f64,
+    flow_count: i64,
+}
+
+pub struct GeoService;
+
+impl GeoService {
+    /// Sets or, with `None`, clears an entity's geometry and the point it
+    /// is mapped at.
+    pub fn set_entity_location(
+        conn: &mut DbConn,
+        entity: &Entity,
+        geometry: Option<Value>,
+    ) -> Result<Entity, AppError> {
+        let point = geometry
+            .as_ref()
+            .map(parse_geometry)
+            .transpose()
+            .map_err(AppError::BadRequest)?;
+        let changes = EntityChanges {
+            geometry: Some(geometry.map(JsonField)),
+            latitude: Some(point.map(|p| p.latitude)),
+            longitude: Some(point.map(|p| p.longitude)),
+            ..Default::default()
+        };
+        EntityService::update_entity(conn, entity, changes)
+    }
+
+    /// Parties to a flow and host admins may say where it happened, once.
+    pub fn set_flow_location(
+        conn: &mut DbConn,
+        host: i32,
+        flow_id: &str,
+        user: i32,
+        is_host_admin: bool,
+        point: GeoPoint,
+    ) -> Result<FlowEvent, AppError> {
+        let flow: FlowEvent = flow_events::table
+            .find(flow_id)
+            .filter(flow_events::host_id.eq(host))
+            .select(FlowEvent::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Flow {}", flow_id)))?;
+
+        let is_party = EntityService::active_role(conn, &flow.from_entity, user)?.is_some()
+            || EntityService::active_role(conn, &flow.to_entity, user)?.is_some();
+        if !is_host_admin && !is_party {
+            return Err(AuthError::Forbidden("Only parties to a flow can set its location").into());
+        }
+        if flow.latitude.is_some() {
+            return Err(AppError::BadRequest("This flow's location is already recorded".into()));
+        }
+        let point = GeoPoint::new(point.latitude, point.longitude).map_err(AppError::BadRequest)?;
+
+        diesel::update(flow_events::table.find(&flow.id))
+            .set((
+                flow_events::latitude.eq(point.latitude),
+                flow_events::longitude.eq(point.longitude),
+            ))
+            .execute(conn)?;
+        Ok(FlowEvent {
+            latitude: Some(point.latitude),
+            longitude: Some(point.longitude),
+            ..flow
+        })
+    }
+
+    /// The host's located, unarchived entities the viewer may see, as a
+    /// GeoJSON FeatureCollection.
+    pub fn entity_features(
+        conn: &mut DbConn,
+        host: i32,
+        viewer: &FlowViewer,
+        area: Option<&GeoArea>,
+    ) -> Result<Value, AppError> {
+        let mut query = entities::table
+            .filter(entities::host_id.eq(host))
+            .filter(entities::archived_at.is_null())
+            .filter(entities::latitude.is_not_null())
+            .filter(entities::longitude.is_not_null())
+            .into_boxed();
+        if let Some(area) = area {
+            query = query.filter(sql::<Bool>(&area.sql("entities.latitude", "entities.longitude")));
+        }
+        let located: Vec<Entity> = query
+            .order(entities::name.asc())
+            .select(Entity::as_select())
+            .load(conn)?;
+
+        let ids: Vec<String> = located.iter().map(|e| e.id.clone()).collect();
+        let visible = EntityService::visible_ids(conn, viewer, &ids)?;
+
+        let features = located
+            .into_iter()
+            .filter(|e| visible.contains(&e.id))
+            .filter_map(|e| {
+                let point = GeoPoint { latitude: e.latitude?, longitude: e.longitude? };
+                let geometry = e.geometry.map(|g| g.0).unwrap_or_else(|| point.geometry());
+                Some(feature(
+                    geometry,
+                    json!({ "id": e.id, "name": e.name, "entity_type": e.entity_type }),
+                ))
+            })
+            .collect();
+        Ok(feature_collection(features))
+    }
+
+    /// Counted flows matching the query, summed per location and resource
+    /// type in the resource's default unit, as GeoJSON Points.
+    pub fn flow_features(conn: &mut DbConn, flow_query: &FlowQuery) -> Result<Value, AppError> {
+        let registry = ResourceService::get_registry(conn, flow_query.host)?;
+        let flows = ledger_views::get_located_flows(conn, flow_query)?;
+
+        // a flow placed at its receiving entity would reveal where that
+        // entity is, so it is left off the map unless the viewer may see it
+        let mut placed_at: Vec<String> = flows.iter().filter_map(|f| f.placed_at.clone()).collect();
+        placed_at.sort();
+        placed_at.dedup();
+        let visible = EntityService::visible_ids(conn, &flow_query.viewer, &placed_at)?;
+
+        // keyed by the exact coordinates, which flows placed at the same
+        // entity share
+        let mut totals: HashMap<(String, u64, u64), LocationTotal> = HashMap::new();
+        for flow in flows {
+            if flow.placed_at.as_ref().is_some_and(|id| !visible.contains(id)) {
+                continue;
+            }
+            let resource = registry.iter().find(|r| r.key == flow.resource_type);
+            let (unit, quantity) = match resource {
+                Some(r) => (
+                    r.default_unit.clone(),
+                    flow.quantity_value as f64 * r.factor(&flow.quantity_unit).unwrap_or(1.0),
+                ),
+                None => (flow.quantity_unit.clone(), flow.quantity_value as f64),
+            };
+            let total = totals
+                .entry((flow.resource_type, flow.latitude.to_bits(), flow.longitude.to_bits()))
+                .or_insert(LocationTotal {
+                    point: GeoPoint { latitude: flow.latitude, longitude: flow.longitude },
+                    unit,
+                    quantity: 0.0,
+                    flow_count: 0,
+                });
+            total.quantity += quantity;
+            total.flow_count += 1;
+        }
+
+        let mut totals: Vec<_> = totals.into_iter().collect();
+        totals.sort_by(|a, b| a.0.cmp(&b.0));
+        let features = totals
+            .into_iter()
+            .map(|((resource_type, _, _), total)| {
+                let properties = json!({
+                    "resource_type": resource_type,
+                    "unit": total.unit,
+                    "quantity": total.quantity,
+                    "flow_count": total.flow_count,
+                });
+                feature(total.point.geometry(), properties)
+            })
+            .collect();
+        Ok(feature_collection(features))
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::{NewFlowEvent, create_flow_event};
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+
+    #[test]
+    fn flows_are_mapped_at_their_own_or_receiving_location() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut entity = |name: &str| create_test_entity(&mut conn, 1, name, "project");
+        let (jane, garden, pantry) = (entity("Jane"), entity("Garden"), entity("Pantry"));
+
+        // Garden in Iowa City, Pantry about 40km away in Cedar Rapids
+        let point = |lon: f64, lat: f64| json!({ "type": "Point", "coordinates": [lon, lat] });
+        GeoService::set_entity_location(&mut conn, &garden, Some(point(-91.53, 41.66))).unwrap();
+        GeoService::set_entity_location(&mut conn, &pantry, Some(point(-91.67, 41.98))).unwrap();
+
+        let mut flow = |to: &Entity, hours: f32| {
+            let new = NewFlowEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: now,
+                recorded_at: now,
+                from_entity: jane.id.clone(),
+                to_entity: to.id.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: hours,
+                quantity_unit: "hours".to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: "test".to_string(),
+            };
+            create_flow_event(&mut conn, &new).unwrap().id
+        };
+        flow(&garden, 2.0);
+        flow(&garden, 3.0);
+        let at_market = flow(&pantry, 1.0);
+
+        // recorded at the Cedar Rapids farmers market, not the pantry
+        let market = GeoPoint { latitude: 41.97, longitude: -91.66 };
+        GeoService::set_flow_location(&mut conn, 1, &at_market, 0, true, market).unwrap();
+        assert!(GeoService::set_flow_location(&mut conn, 1, &at_market, 0, true, market).is_err());
+
+        let mut query = FlowQuery::new(1);
+        query.area = Some(GeoArea::parse_bbox("-91.6,41.6,-91.5,41.7").unwrap());
+        let around_iowa_city = GeoService::flow_features(&mut conn, &query).unwrap();
+        let features = around_iowa_city["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0]["properties"]["flow_count"], 2);
+
+        let center = GeoPoint { latitude: 41.66, longitude: -91.53 };
+        query.area = Some(GeoArea::radius(center, 50.0).unwrap());
+        let within_50km = GeoService::flow_features(&mut conn, &query).unwrap();
+        assert_eq!(within_50km["features"].as_array().unwrap().len(), 2);
+        query.area = Some(GeoArea::radius(center, 10.0).unwrap());
+        let within_10km = GeoService::flow_features(&mut conn, &query).unwrap();
+        assert_eq!(within_10km["features"][0]["properties"]["quantity"], 5.0);
+
+        // a member who may not see the garden must not learn where it is
+        let hidden = EntityChanges { visibility: Some("stewards".to_string()), ..Default::default() };
+        EntityService::update_entity(&mut conn, &garden, hidden).unwrap();
+        query.area = Some(GeoArea::radius(center, 50.0).unwrap());
+        query.viewer = FlowViewer::User { user_id, is_member: true };
+        let as_member = GeoService::flow_features(&mut conn, &query).unwrap();
+        let features = as_member["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0]["properties"]["flow_count"], 1);
+
+        let entities = GeoService::entity_features(&mut conn, 1, &FlowViewer::System, None).unwrap();
+        assert_eq!(entities["features"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::entity_goals::{self as goal_model, EntityGoal, NewEntityGoal};
+use crate::models::ledger_views::{self, PROJECT_ENTITY_TYPES};
+use crate::services::entity_service::EntityService;
+use crate::services::ledger_service::LedgerService;
+use crate::services::resource_service::ResourceService;
+use crate::types::flow_query::FlowViewer;
+
+#[derive(Serialize)]
+pub struct GoalView {
+    #[serde(flatten)]
+    pub goal: EntityGoal,
+    pub achieved: f64,
+    pub flow_count: i32,
+    /// Not capped at 100, so overshooting a goal shows.
+    pub percent_complete: f64,
+    /// How far through the period `now` is, to compare against.
+    pub percent_elapsed: f64,
+}
+
+pub struct GoalService;
+
+impl GoalService {
+    /// Goals with progress, hiding those on entities the viewer can't see.
+    pub fn list(
+        conn: &mut DbConn,
+        host: i32,
+        entity_id: Option<&str>,
+        viewer: &FlowViewer,
+        now: NaiveDateTime,
+    ) -> Result<Vec<GoalView>, AppError> {
+        let goals = goal_model::get_goals(conn, host, entity_id)?;
+        let ids: Vec<String> = goals.iter().map(|g| g.entity_id.clone()).collect();
+        let visible = EntityService::visible_ids(conn, viewer, &ids)?;
+
+        let progress: HashMap<i32, ledger_views::GoalProgress> = ledger_views::get_goal_progress(conn, host)?
+            .into_iter()
+            .map(|p| (p.goal_id, p))
+            .collect();
+
+        Ok(goals
+            .into_iter()
+            .filter(|g| visible.contains(&g.entity_id))
+            .map(|goal| {
+                let (achieved, flow_count) = progress
+                    .get(&goal.id)
+                    .map(|p| (p.achieved, p.flow_count))
+                    .unwrap_or_default();
+                let period = (goal.period_end - goal.period_start).num_seconds() as f64;
+                let elapsed = (now - goal.period_start).num_seconds() as f64;
+                GoalView {
+                    percent_complete: round(achieved / goal.target_value as f64 * 100.0),
+                    percent_elapsed: round((elapsed / period * 100.0).clamp(0.0, 100.0)),
+                    achieved,
+                    flow_count,
+                    goal,
+                }
+            })
+            .collect())
+    }
+
+    pub fn create(
+        conn: &mut DbConn,
+        user: i32,
+        is_host_admin: bool,
+        new: NewEntityGoal,
+    ) -> Result<EntityGoal, AppError> {
+        Self::check(conn, user, is_host_admin, &new)?;
+        goal_model::create_goal(conn, &new).map_err(AppError::Db)
+    }
+
+    pub fn update(
+        conn: &mut DbConn,
+        goal_id: i32,
+        user: i32,
+        is_host_admin: bool,
+        new: NewEntityGoal,
+    ) -> Result<EntityGoal, AppError> {
+        let existing = Self::get_on_host(conn, new.host_id, goal_id)?;
+        // Moving a goal needs rights on the entity it leaves as well.
+        let current = LedgerService::get_entity(conn, &existing.entity_id)?;
+        EntityService::require_people_manager(conn, &current, user, is_host_admin)?;
+        Self::check(conn, user, is_host_admin, &new)?;
+        let new = NewEntityGoal {
+            created_by: existing.created_by,
+            ..new
+        };
+        goal_model::update_goal(conn, goal_id, &new).map_err(AppError::Db)
+    }
+
+    pub fn delete(
+        conn: &mut DbConn,
+        host: i32,
+        goal_id: i32,
+        user: i32,
+        is_host_admin: bool,
+    ) -> Result<(), AppError> {
+        let goal = Self::get_on_host(conn, host, goal_id)?;
+        let entity = LedgerService::get_entity(conn, &goal.entity_id)?;
+        EntityService::require_people_manager(conn, &entity, user, is_host_admin)?;
+        goal_model::delete_goal(conn, goal_id)?;
+        Ok(())
+    }
+
+    /// Goals belong to a project-like entity on the host and are set by
+    /// its owners and stewards or a host admin.
+    fn check(
+        conn: &mut DbConn,
+        user: i32,
+        is_host_admin: bool,
+        new: &NewEntityGoal,
+    ) -> Result<(), AppError> {
+        let entity = LedgerService::get_entity(conn, &new.entity_id)?;
+        if entity.host_id != new.host_id {
+            return Err(AppError::NotFound(format!("Entity {}", new.entity_id)));
+        }
+        EntityService::require_people_manager(conn, &entity, user, is_host_admin)?;
+
+        if !PROJECT_ENTITY_TYPES
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&entity.entity_type))
+        {
+            return Err(AppError::BadRequest(format!(
+                "Goals can only be set on {} entities",
+                PROJECT_ENTITY_TYPES.join(", ")
+            )));
+        }
+        if new.label.trim().is_empty() {
+            return Err(AppError::BadRequest("Goal label is required".into()));
+        }
+        if !(new.target_value.is_finite() && new.target_value > 0.0) {
+            return Err(AppError::BadRequest("target_value must be positive".into()));
+        }
+        if new.period_end <= new.period_start {
+            return Err(AppError::BadRequest("period_end must be after period_start".into()));
+        }
+        ResourceService::validate_flow(conn, new.host_id, &new.resource_type, &new.unit)
+    }
+
+    fn get_on_host(conn: &mut DbConn, host: i32, goal_id: i32) -> Result<EntityGoal, AppError> {
+        goal_model::get_goal(conn, goal_id)
+            .ok()
+            .filter(|g| g.host_id == host)
+            .ok_or_else(|| AppError::NotFound(format!("Goal {}", goal_id)))
+    }
+}
+
+fn round(percent: f64) -> f64 {
+    (percent * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::{NewFlowEvent, create_flow_event};
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::JsonField;
+
+    #[test]
+    fn progress_counts_flows_in_period_in_the_goal_unit() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+
+        let mut entity = |name: &str, entity_type: &str| create_test_entity(&mut conn, 1, name, entity_type).id;
+        let (jane, garden) = (entity("Jane", "Person"), entity("Garden", "project"));
+
+        let goal = |entity_id: &str| NewEntityGoal {
+            host_id: 1,
+            entity_id: entity_id.to_string(),
+            label: "Spring volunteer hours".to_string(),
+            resource_type: "labor_time".to_string(),
+            target_value: 600.0,
+            unit: "minutes".to_string(),
+            period_start: at("2024-03-01 00:00"),
+            period_end: at("2024-05-31 23:59"),
+            created_by: "test".to_string(),
+        };
+        assert!(matches!(
+            GoalService::create(&mut conn, user_id, true, goal(&jane)),
+            Err(AppError::BadRequest(_))
+        ));
+        GoalService::create(&mut conn, user_id, true, goal(&garden)).unwrap();
+
+        for (qty, unit, when) in [
+            (2.0, "hours", "2024-03-10 09:00"),
+            (60.0, "minutes", "2024-04-02 09:00"),
+            (5.0, "hours", "2024-06-02 09:00"),
+        ] {
+            let new = NewFlowEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: at(when),
+                recorded_at: at(when),
+                from_entity: jane.clone(),
+                to_entity: garden.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: qty,
+                quantity_unit: unit.to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: "test".to_string(),
+            };
+            create_flow_event(&mut conn, &new).unwrap();
+        }
+
+        let goals = GoalService::list(&mut conn, 1, None, &FlowViewer::System, at("2024-04-15 12:00")).unwrap();
+        assert_eq!(goals.len(), 1);
+        assert!((goals[0].achieved - 180.0).abs() < 1e-3);
+        assert_eq!((goals[0].flow_count, goals[0].percent_complete), (2, 30.0));
+        assert!(goals[0].percent_elapsed > 40.0 && goals[0].percent_elapsed < 60.0);
+    }
+
+    #[test]
+    fn goals_cannot_be_moved_off_an_entity_the_user_does_not_manage() {
+        use crate::models::entities::{NewEntityUser, create_entity_user};
+        use crate::types::EntityRole;
+
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+
+        let garden = create_test_entity(&mut conn, 1, "Garden", "project").id;
+        let pantry = create_test_entity(&mut conn, 1, "Pantry", "project").id;
+        create_entity_user(
+            &mut conn,
+            &NewEntityUser {
+                entity_id: &pantry,
+                user_id,
+                role: EntityRole::Steward.value(),
+                status: "active",
+            },
+        )
+        .unwrap();
+
+        let goal = |entity_id: &str| NewEntityGoal {
+            host_id: 1,
+            entity_id: entity_id.to_string(),
+            label: "Spring volunteer hours".to_string(),
+            resource_type: "labor_time".to_string(),
+            target_value: 10.0,
+            unit: "hours".to_string(),
+            period_start: at("2024-03-01 00:00"),
+            period_end: at("2024-05-31 23:59"),
+            created_by: "test".to_string(),
+        };
+        let garden_goal = GoalService::create(&mut conn, user_id, true, goal(&garden)).unwrap();
+
+        let moved = GoalService::update(&mut conn, garden_goal.id, user_id, false, goal(&pantry));
+        assert!(matches!(moved, Err(AppError::Auth(_))));
+        let pantry_goal = GoalService::create(&mut conn, user_id, false, goal(&pantry)).unwrap();
+        GoalService::update(&mut conn, pantry_goal.id, user_id, false, goal(&pantry)).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::ledger_views::{self, FlowEdge};
+use crate::schema::entities;
+use crate::services::ledger_service::EntityRef;
+use crate::types::flow_query::FlowQuery;
+
+/// Neighbourhoods deeper than this are as good as the whole host graph.
+pub const MAX_GRAPH_DEPTH: u32 = 5;
+
+#[derive(Serialize)]
+pub struct ResourceTotal {
+    pub resource_type: String,
+    pub unit: String,
+    pub inflow: f64,
+    pub outflow: f64,
+}
+
+#[derive(Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub name: String,
+    pub entity_type: String,
+    pub totals: Vec<ResourceTotal>,
+}
+
+#[derive(Serialize)]
+pub struct FlowGraph {
+    pub host_id: i32,
+    pub since: NaiveDateTime,
+    pub until: NaiveDateTime,
+    pub center: Option<String>,
+    pub depth: Option<u32>,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<FlowEdge>,
+}
+
+/// (inflow, outflow) per (resource type, unit) for one node.
+type NodeTotals<'a> = BTreeMap<(&'a str, &'a str), (f64, f64)>;
+
+pub struct GraphService;
+
+impl GraphService {
+    /// Builds the host's flow graph for the window from the flows the
+    /// query's viewer may see. With a `center`, keeps only entities within
+    /// `depth` hops of it (in either direction).
+    pub fn get_flow_graph(
+        conn: &mut DbConn,
+        flow_query: &FlowQuery,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+        center: Option<&str>,
+        depth: u32,
+    ) -> Result<FlowGraph, AppError> {
+        let edge_query = FlowQuery {
+            entity: None,
+            since: Some(since),
+            until: Some(until),
+            limit: None,
+            offset: None,
+            cursor: None,
+            ..flow_query.clone()
+        };
+        let all_edges = ledger_views::get_flow_edges(conn, &edge_query)?;
+        let depth = depth.min(MAX_GRAPH_DEPTH);
+
+        let edges: Vec<FlowEdge> = match center {
+            Some(center) => {
+                let reached = neighbourhood(&all_edges, center, depth);
+                all_edges
+                    .iter()
+                    .filter(|e| reached.contains(e.source.as_str()) && reached.contains(e.target.as_str()))
+                    .cloned()
+                    .collect()
+            }
+            None => all_edges.clone(),
+        };
+
+        let mut ids: HashSet<&str> = edges
+            .iter()
+            .flat_map(|e| [e.source.as_str(), e.target.as_str()])
+            .collect();
+        if let Some(center) = center {
+            ids.insert(center);
+        }
+
+        let refs: HashMap<String, EntityRef> = entities::table
+            .filter(entities::id.eq_any(&ids))
+            .select(EntityRef::as_select())
+            .load::<EntityRef>(conn)?
+            .into_iter()
+            .map(|e| (e.id.clone(), e))
+            .collect();
+
+        // Totals use every edge in the window, not just the visible ones,
+        // so a node's size doesn't change with the neighbourhood depth.
+        let mut totals: HashMap<&str, NodeTotals> = HashMap::new();
+        for e in &all_edges {
+            let key = (e.resource_type.as_str(), e.unit.as_str());
+            totals.entry(&e.target).or_default().entry(key).or_default().0 += e.quantity;
+            totals.entry(&e.source).or_default().entry(key).or_default().1 += e.quantity;
+        }
+
+        let mut nodes: Vec<GraphNode> = ids
+            .iter()
+            .map(|id| {
+                let entity = refs.get(*id);
+                GraphNode {
+                    id: id.to_string(),
+                    name: entity.map(|e| e.name.clone()).unwrap_or_else(|| id.to_string()),
+                    entity_type: entity.map(|e| e.entity_type.clone()).unwrap_or_default(),
+                    totals: totals
+                        .get(id)
+                        .into_iter()
+                        .flatten()
+                        .map(|((resource_type, unit), (inflow, outflow))| ResourceTotal {
+                            resource_type: resource_type.to_string(),
+                            unit: unit.to_string(),
+                            inflow: *inflow,
+                            outflow: *outflow,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(FlowGraph {
+            host_id: flow_query.host,
+            since,
+            until,
+            center: center.map(str::to_string),
+            depth: center.map(|_| depth),
+            nodes,
+            edges,
+        })
+    }
+}
+
+/// Entities reachable from `center` in at most `depth` hops, ignoring direction.
+fn neighbourhood<'a>(edges: &'a [FlowEdge], center: &'a str, depth: u32) -> HashSet<&'a str> {
+    let mut adjacent: HashMap<&str, Vec<&str>> = HashMap::new();
+    for e in edges {
+        adjacent.entry(&e.source).or_default().push(&e.target);
+        adjacent.entry(&e.target).or_default().push(&e.source);
+    }
+
+    let mut reached = HashSet::from([center]);
+    let mut queue = VecDeque::from([(center, 0)]);
+    while let Some((id, hops)) = queue.pop_front() {
+        if hops == depth {
+            continue;
+        }
+        for next in adjacent.get(id).into_iter().flatten() {
+            if reached.insert(next) {
+                queue.push_back((next, hops + 1));
+            }
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::flow_events::{NewFlowEvent, create_flow_event};
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::flow_query::FlowViewer;
+    use crate::types::{JsonField, Visibility};
+
+    #[test]
+    fn edges_are_aggregated_and_limited_to_the_neighbourhood() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut entity = |name: &str| create_test_entity(&mut conn, 1, name, "project").id;
+        let (jane, garden, kitchen) = (entity("Jane"), entity("Garden"), entity("Kitchen"));
+
+        let flows = [
+            (&jane, &garden, 2.0, "hours"),
+            (&jane, &garden, 30.0, "minutes"),
+            (&garden, &kitchen, 1.0, "hours"),
+        ];
+        for (from, to, qty, unit) in flows {
+            let new = NewFlowEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: now,
+                recorded_at: now,
+                from_entity: from.clone(),
+                to_entity: to.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: qty,
+                quantity_unit: unit.to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: "test".to_string(),
+            };
+            create_flow_event(&mut conn, &new).unwrap();
+        }
+
+        let since = now - chrono::Duration::days(1);
+        let until = now + chrono::Duration::days(1);
+
+        let graph = GraphService::get_flow_graph(&mut conn, &FlowQuery::new(1), since, until, None, 0).unwrap();
+        assert_eq!(graph.nodes.len(), 3);
+        let edge = graph.edges.iter().find(|e| e.source == jane).unwrap();
+        assert_eq!((edge.quantity, edge.flow_count, edge.unit.as_str()), (2.5, 2, "hours"));
+
+        let local = GraphService::get_flow_graph(&mut conn, &FlowQuery::new(1), since, until, Some(&jane), 1).unwrap();
+        assert_eq!(local.edges.len(), 1);
+        let garden_node = local.nodes.iter().find(|n| n.id == garden).unwrap();
+        assert_eq!((garden_node.totals[0].inflow, garden_node.totals[0].outflow), (2.5, 1.0));
+
+        // flows restricted to their parties stay out of other members' edges
+        diesel::update(crate::schema::flow_events::table)
+            .filter(crate::schema::flow_events::to_entity.eq(&kitchen))
+            .set(crate::schema::flow_events::visibility.eq(Visibility::Parties.value()))
+            .execute(&mut conn)
+            .unwrap();
+        let mut member = FlowQuery::new(1);
+        member.viewer = FlowViewer::User { user_id, is_member: true };
+        let shared = GraphService::get_flow_graph(&mut conn, &member, since, until, None, 0).unwrap();
+        assert_eq!(shared.edges.len(), 1);
+        assert!(shared.nodes.iter().all(|n| n.id != kitchen));
+    }
+}
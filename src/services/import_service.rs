@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::app_error::AppError;
+
+/// Maps an import field (`from`, `to`, `quantity`, ...) to the CSV header
+/// that holds it. Fields left out are read from a column of the same name.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct ImportMapping(pub HashMap<String, String>);
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    /// 1-based data row, not counting the header.
+    pub row: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport<T> {
+    pub dry_run: bool,
+    pub committed: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub errors: Vec<ImportRowError>,
+    pub rows: Vec<T>,
+}
+
+/// A CSV row keyed by import field.
+pub struct ImportRow {
+    pub row: usize,
+    values: HashMap<&'static str, String>,
+}
+
+impl ImportRow {
+    /// Trimmed value, or `None` when the column is missing or blank.
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.values.get(field).map(|v| v.trim()).filter(|v| !v.is_empty())
+    }
+
+    pub fn require(&self, field: &str) -> Result<&str, ImportRowError> {
+        self.get(field)
+            .ok_or_else(|| self.error(field, format!("{} is required", field)))
+    }
+
+    pub fn error(&self, field: &str, message: String) -> ImportRowError {
+        ImportRowError {
+            row: self.row,
+            field: Some(field.to_string()),
+            message,
+        }
+    }
+}
+
+pub const ENTITY_FIELDS: &[&str] = &["name", "entity_type"];
+pub const FLOW_FIELDS: &[&str] = &[
+    "from",
+    "to",
+    "resource_type",
+    "quantity",
+    "quantity_unit",
+    "date",
+    "time",
+    "notes",
+];
+
+pub struct ImportService;
+
+impl ImportService {
+    /// Reads a CSV with a header row into rows keyed by `fields`.
+    /// Fails up front when a required column is absent from the header.
+    pub fn read_rows(
+        data: &[u8],
+        mapping: &ImportMapping,
+        fields: &[&'static str],
+        required: &[&str],
+    ) -> Result<Vec<ImportRow>, AppError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(data);
+        let bad_csv = |e: csv::Error| AppError::BadRequest(format!("Invalid CSV: {}", e));
+
+        let headers = reader.headers().map_err(bad_csv)?.clone();
+        let mut columns: Vec<(&'static str, usize)> = Vec::new();
+        for field in fields {
+            let header = mapping.0.get(*field).map(String::as_str).unwrap_or(field);
+            match headers.iter().position(|h| h.eq_ignore_ascii_case(header)) {
+                Some(index) => columns.push((field, index)),
+                None if required.contains(field) => {
+                    return Err(AppError::BadRequest(format!(
+                        "Missing column '{}' for {}",
+                        header, field
+                    )));
+                }
+                None => {}
+            }
+        }
+
+        let mut rows = Vec::new();
+        for (i, record) in reader.records().enumerate() {
+            let record = record.map_err(bad_csv)?;
+            if record.iter().all(|v| v.trim().is_empty()) {
+                continue;
+            }
+            let values = columns
+                .iter()
+                .filter_map(|(field, index)| Some((*field, record.get(*index)?.to_string())))
+                .collect();
+            rows.push(ImportRow { row: i + 1, values });
+        }
+        Ok(rows)
+    }
+
+    /// Accepts `YYYY-MM-DD` (or the loaders' `YY-M-D`) plus an optional
+    /// `HH:MM[:SS]` time, defaulting to 08:00 like the Node loaders.
+    pub fn parse_timestamp(date: &str, time: Option<&str>) -> Option<NaiveDateTime> {
+        if let Ok(ts) = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S") {
+            return Some(ts);
+        }
+        let mut parts = date.split('-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() {
+            return None;
+        }
+        let mut year: i32 = year.parse().ok()?;
+        if year < 100 {
+            year += 2000;
+        }
+        let date = NaiveDate::from_ymd_opt(year, month.parse().ok()?, day.parse().ok()?)?;
+
+        let time = match time {
+            None => NaiveTime::from_hms_opt(8, 0, 0)?,
+            Some(t) => NaiveTime::parse_from_str(t, "%H:%M:%S")
+                .or_else(|_| NaiveTime::parse_from_str(t, "%H:%M"))
+                .ok()?,
+        };
+        Some(date.and_time(time))
+    }
+
+    pub fn parse_quantity(input: &str) -> Option<f32> {
+        input
+            .replace([',', '$'], "")
+            .parse::<f32>()
+            .ok()
+            .filter(|q| q.is_finite() && *q > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_columns_are_read_and_missing_ones_rejected() {
+        let csv = "Giver,Receiver,Kind,Amount,Date\nJane,Garden,labor_time,3,24-5-1\n,,,,\n";
+        let mapping = ImportMapping(HashMap::from([
+            ("from".to_string(), "Giver".to_string()),
+            ("to".to_string(), "Receiver".to_string()),
+            ("resource_type".to_string(), "Kind".to_string()),
+            ("quantity".to_string(), "Amount".to_string()),
+        ]));
+
+        let rows = ImportService::read_rows(csv.as_bytes(), &mapping, FLOW_FIELDS, &["from", "to"]).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get("from"), Some("Jane"));
+        assert_eq!(rows[0].get("notes"), None);
+        assert_eq!(
+            ImportService::parse_timestamp(rows[0].get("date").unwrap(), None).unwrap().to_string(),
+            "2024-05-01 08:00:00"
+        );
+
+        let unmapped = ImportService::read_rows(csv.as_bytes(), &ImportMapping::default(), FLOW_FIELDS, &["from"]);
+        assert!(matches!(unmapped, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn flow_import_previews_then_commits_only_a_clean_batch() {
+        use crate::domains::ledger_domain::LedgerDomain;
+        use crate::test_support::db::{create_test_entity, setup_test_db};
+
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let domain = LedgerDomain::new(pool.clone());
+        for name in ["Jane", "Garden"] {
+            create_test_entity(&mut pool.get().unwrap(), 1, name, "Person");
+        }
+        let mapping = ImportMapping::default();
+
+        let bad = "from,to,resource_type,quantity,quantity_unit,date\n\
+                   jane,Garden,labor_time,90,minutes,2024-05-01\n\
+                   Nobody,Garden,labor_time,1,USD,2024-05-02\n";
+        let report = domain.import_flows_csv(1, "test", bad.as_bytes(), &mapping, false).unwrap();
+        assert!(!report.committed);
+        assert_eq!(report.valid_rows, 1);
+        let fields: Vec<_> = report.errors.iter().map(|e| (e.row, e.field.as_deref())).collect();
+        assert_eq!(fields, vec![(2, Some("from")), (2, Some("resource_type"))]);
+
+        let good = "from,to,resource_type,quantity,date\nJane,Garden,labor_time,3,2024-05-01\n";
+        let preview = domain.import_flows_csv(1, "test", good.as_bytes(), &mapping, true).unwrap();
+        assert!(!preview.committed);
+        assert_eq!(preview.rows[0].quantity_unit, "hours");
+
+        let report = domain.import_flows_csv(1, "test", good.as_bytes(), &mapping, false).unwrap();
+        assert!(report.committed);
+        let mut conn = pool.get().unwrap();
+        assert_eq!(crate::models::flow_events::get_flow_events(&mut conn).unwrap().len(), 1);
+    }
+}
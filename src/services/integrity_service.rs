@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, SubsecRound};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::flow_events::{FlowAction, FlowEvent};
+use crate::models::ledger_chain::{
+    self as chain_model, ChainEntry, GENESIS_HASH, LedgerCheckpoint, NewLedgerCheckpoint,
+    action_link_hash, link_hash,
+};
+use crate::types::FlowActionType;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How often the scheduler checkpoints each host's chain.
+pub const CHECKPOINT_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Where the chain first stops checking out.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChainBreak {
+    pub chain_seq: i32,
+    pub flow_id: Option<String>,
+    /// Set when the broken link is an action on the flow.
+    pub action_id: Option<String>,
+    pub checkpoint_id: Option<i32>,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChainReport {
+    pub host_id: i32,
+    pub ok: bool,
+    /// Links checked before the first break, or all of them.
+    pub verified: i32,
+    pub head_seq: i32,
+    pub head_hash: Option<String>,
+    /// Flows and actions not linked yet; they join the chain on the next write.
+    pub unlinked: i64,
+    pub checkpoints: usize,
+    pub first_break: Option<ChainBreak>,
+}
+
+pub struct IntegrityService;
+
+impl IntegrityService {
+    /// Walks the host's chain from the start, recomputing every link, then
+    /// checks each checkpoint's signature and that its head is still on
+    /// the chain, which also catches entries cut from the end.
+    pub fn verify(conn: &mut DbConn, host: i32, secret: &str) -> Result<ChainReport, AppError> {
+        let chain = chain_model::get_chain(conn, host)?;
+        let checkpoints = chain_model::get_checkpoints(conn, host)?;
+
+        let repoints = Repoint::from_chain(&chain);
+        let mut hashes: HashMap<i32, &str> = HashMap::new();
+        let mut first_break = None;
+        let mut prev = GENESIS_HASH;
+        for (i, entry) in chain.iter().enumerate() {
+            let expected_seq = i as i32 + 1;
+            let broken = |reason: &str| ChainBreak {
+                chain_seq: expected_seq,
+                flow_id: Some(entry.flow_id().to_string()),
+                action_id: match entry {
+                    ChainEntry::Flow(_) => None,
+                    ChainEntry::Action(action) => Some(action.id.clone()),
+                },
+                checkpoint_id: None,
+                reason: reason.to_string(),
+            };
+            let (Some(seq), Some(hash)) = (entry.chain_seq(), entry.hash()) else {
+                break;
+            };
+            if seq != expected_seq {
+                first_break = Some(broken("an entry is missing before this one"));
+                break;
+            }
+            if entry.prev_hash() != Some(prev) {
+                first_break = Some(broken("previous hash does not match the chain"));
+                break;
+            }
+            if !content_matches(prev, seq, entry, hash, &repoints) {
+                first_break = Some(broken("content does not match its hash"));
+                break;
+            }
+            hashes.insert(seq, hash);
+            prev = hash;
+        }
+
+        let verified = hashes.len() as i32;
+        if first_break.is_none() {
+            for checkpoint in &checkpoints {
+                let reason = if !Self::signature_matches(checkpoint, secret)? {
+                    "checkpoint signature is invalid"
+                } else if checkpoint.chain_seq > verified {
+                    "checkpointed entries are missing from the chain"
+                } else if hashes.get(&checkpoint.chain_seq) != Some(&checkpoint.head_hash.as_str()) {
+                    "chain no longer matches the checkpoint"
+                } else {
+                    continue;
+                };
+                first_break = Some(ChainBreak {
+                    chain_seq: checkpoint.chain_seq,
+                    flow_id: None,
+                    action_id: None,
+                    checkpoint_id: Some(checkpoint.id),
+                    reason: reason.to_string(),
+                });
+                break;
+            }
+        }
+
+        let head = chain.last();
+        Ok(ChainReport {
+            host_id: host,
+            ok: first_break.is_none(),
+            verified,
+            head_seq: head.and_then(ChainEntry::chain_seq).unwrap_or(0),
+            head_hash: head.and_then(|entry| entry.hash().map(str::to_string)),
+            unlinked: chain_model::count_unlinked(conn, host)?,
+            checkpoints: checkpoints.len(),
+            first_break,
+        })
+    }
+
+    /// Signs the current chain head. Returns `None` when nothing was added
+    /// since the last checkpoint, and refuses to sign a broken chain.
+    pub fn checkpoint(
+        conn: &mut DbConn,
+        host: i32,
+        secret: &str,
+        now: NaiveDateTime,
+    ) -> Result<Option<LedgerCheckpoint>, AppError> {
+        require_secret(secret)?;
+        chain_model::link_pending(conn, host)?;
+        let report = Self::verify(conn, host, secret)?;
+        if let Some(broken) = report.first_break {
+            return Err(AppError::BadRequest(format!(
+                "Ledger chain is broken at entry {}: {}",
+                broken.chain_seq, broken.reason
+            )));
+        }
+        let Some(head_hash) = report.head_hash else {
+            return Ok(None);
+        };
+        let latest = chain_model::get_checkpoints(conn, host)?.pop();
+        if latest.is_some_and(|c| c.chain_seq == report.head_seq) {
+            return Ok(None);
+        }
+
+        // whole seconds, so the signed time reads back unchanged
+        let created_at = now.trunc_subsecs(0);
+        let new = NewLedgerCheckpoint {
+            host_id: host,
+            chain_seq: report.head_seq,
+            signature: sign(secret, host, report.head_seq, &head_hash, created_at)?,
+            head_hash,
+            created_at,
+        };
+        chain_model::create_checkpoint(conn, &new)
+            .map(Some)
+            .map_err(AppError::Db)
+    }
+
+    pub fn checkpoints(conn: &mut DbConn, host: i32) -> Result<Vec<LedgerCheckpoint>, AppError> {
+        chain_model::get_checkpoints(conn, host).map_err(AppError::Db)
+    }
+
+    /// Checkpoints every host with a ledger; returns how many were signed.
+    pub fn checkpoint_all(conn: &mut DbConn, secret: &str, now: NaiveDateTime) -> Result<usize, AppError> {
+        require_secret(secret)?;
+        let mut signed = 0;
+        for host in chain_model::get_ledger_hosts(conn)? {
+            match Self::checkpoint(conn, host, secret, now) {
+                Ok(Some(_)) => signed += 1,
+                Ok(None) => {}
+                Err(e) => log::error!("Ledger checkpoint for host {} failed: {}", host, e),
+            }
+        }
+        Ok(signed)
+    }
+
+    /// Compares in constant time, so a forged signature can't be found
+    /// byte by byte.
+    fn signature_matches(checkpoint: &LedgerCheckpoint, secret: &str) -> Result<bool, AppError> {
+        let mac = checkpoint_mac(
+            secret,
+            checkpoint.host_id,
+            checkpoint.chain_seq,
+            &checkpoint.head_hash,
+            checkpoint.created_at,
+        )?;
+        Ok(decode_hex(&checkpoint.signature).is_some_and(|signature| mac.verify_slice(&signature).is_ok()))
+    }
+}
+
+/// What a merge repointed, as recorded by its `entity_merge` action.
+struct Repoint {
+    chain_seq: i32,
+    flow_id: String,
+    /// The action whose actor was repointed; `None` for the flow's parties.
+    action_id: Option<String>,
+    details: Value,
+}
+
+impl Repoint {
+    fn from_chain(chain: &[ChainEntry]) -> Vec<Repoint> {
+        chain
+            .iter()
+            .filter_map(|entry| match entry {
+                ChainEntry::Action(action)
+                    if action.action_type == FlowActionType::EntityMerge.value() =>
+                {
+                    let details: Value = serde_json::from_str(&action.details).ok()?;
+                    Some(Repoint {
+                        chain_seq: action.chain_seq?,
+                        flow_id: action.flow_id.clone(),
+                        action_id: details["action_id"].as_str().map(str::to_string),
+                        details,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Merges repoint flows and actions onto the canonical entity after they
+/// were linked. Each merge is on the chain with the ids it replaced, so the
+/// ids an entry was hashed with are read back by replaying its merges,
+/// which must end at the ids it has now.
+fn content_matches(prev: &str, seq: i32, entry: &ChainEntry, hash: &str, repoints: &[Repoint]) -> bool {
+    let merges_of = |action_id: Option<&str>| -> Vec<&Repoint> {
+        repoints
+            .iter()
+            .filter(|r| {
+                r.chain_seq > seq && r.flow_id == entry.flow_id() && r.action_id.as_deref() == action_id
+            })
+            .collect()
+    };
+    match entry {
+        ChainEntry::Flow(flow) => {
+            let current = [flow.from_entity.as_str(), flow.to_entity.as_str()];
+            let keys = ["previous_from_entity", "previous_to_entity"];
+            let Some([from_entity, to_entity]) = linked_ids(current, keys, &merges_of(None)) else {
+                return false;
+            };
+            let original = FlowEvent { from_entity, to_entity, ..flow.clone() };
+            link_hash(prev, seq, &original) == hash
+        }
+        ChainEntry::Action(action) => {
+            let current = [action.actor_entity.as_str()];
+            let keys = ["previous_actor_entity"];
+            let Some([actor_entity]) = linked_ids(current, keys, &merges_of(Some(&action.id))) else {
+                return false;
+            };
+            let original = FlowAction { actor_entity, ..action.clone() };
+            action_link_hash(prev, seq, &original) == hash
+        }
+    }
+}
+
+/// The ids before the first merge, if replaying every merge from there
+/// leads to `current`.
+fn linked_ids<const N: usize>(
+    current: [&str; N],
+    keys: [&str; N],
+    merges: &[&Repoint],
+) -> Option<[String; N]> {
+    let previous = |merge: &Repoint| -> Option<[String; N]> {
+        let ids: Vec<String> = keys
+            .iter()
+            .map(|key| merge.details[*key].as_str().map(str::to_string))
+            .collect::<Option<_>>()?;
+        ids.try_into().ok()
+    };
+    let Some(first) = merges.first() else {
+        return Some(current.map(str::to_string));
+    };
+    let linked = previous(first)?;
+    let mut ids = linked.clone();
+    for merge in merges {
+        if previous(merge)? != ids {
+            return None;
+        }
+        let merged_from = merge.details["merged_from"].as_str()?;
+        let merged_into = merge.details["merged_into"].as_str()?;
+        for id in ids.iter_mut().filter(|id| *id == merged_from) {
+            *id = merged_into.to_string();
+        }
+    }
+    (ids == current).then_some(linked)
+}
+
+/// There is no fallback secret: without `ledger.checkpoint_secret`
+/// checkpoints are neither signed nor checked.
+fn require_secret(secret: &str) -> Result<&str, AppError> {
+    if secret.trim().is_empty() {
+        return Err(AppError::Internal(
+            "ledger.checkpoint_secret is not set; ledger checkpoints are disabled".into(),
+        ));
+    }
+    Ok(secret)
+}
+
+/// HMAC-SHA256 with the server's checkpoint secret. Anyone holding a
+/// published checkpoint can later ask the server to confirm it is still on
+/// the chain; only the server can have produced it.
+fn checkpoint_mac(
+    secret: &str,
+    host: i32,
+    chain_seq: i32,
+    head_hash: &str,
+    created_at: NaiveDateTime,
+) -> Result<HmacSha256, AppError> {
+    let secret = require_secret(secret)?;
+    let message = format!(
+        "{}|{}|{}|{}",
+        host,
+        chain_seq,
+        head_hash,
+        created_at.format("%Y-%m-%dT%H:%M:%S")
+    );
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .map_err(|e| AppError::Internal(format!("Invalid checkpoint secret: {}", e)))?;
+    mac.update(message.as_bytes());
+    Ok(mac)
+}
+
+fn sign(
+    secret: &str,
+    host: i32,
+    chain_seq: i32,
+    head_hash: &str,
+    created_at: NaiveDateTime,
+) -> Result<String, AppError> {
+    let mac = checkpoint_mac(secret, host, chain_seq, head_hash, created_at)?;
+    Ok(format!("{:x}", mac.finalize().into_bytes()))
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use diesel::RunQueryDsl;
+    use crate::models::flow_events::{NewFlowAction, NewFlowEvent, create_flow_action, create_flow_event};
+    use crate::services::entity_service::EntityService;
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+    use crate::types::JsonField;
+
+    const SECRET: &str = "test secret";
+
+    #[test]
+    fn tampering_breaks_the_chain_at_the_edited_entry() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut entity = |name: &str| create_test_entity(&mut conn, 1, name, "project").id;
+        let (jane, garden) = (entity("Jane"), entity("Garden"));
+
+        let mut ids = Vec::new();
+        for hours in [1.0, 2.0, 3.0] {
+            let new = NewFlowEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: now,
+                recorded_at: now,
+                from_entity: jane.clone(),
+                to_entity: garden.clone(),
+                host_id: 1,
+                resource_type: "labor_time".to_string(),
+                quantity_value: hours,
+                quantity_unit: "hours".to_string(),
+                notes: None,
+                details: JsonField::default(),
+                created_by: "test".to_string(),
+            };
+            ids.push(create_flow_event(&mut conn, &new).unwrap().id);
+        }
+
+        let report = IntegrityService::verify(&mut conn, 1, SECRET).unwrap();
+        assert!(report.ok);
+        assert_eq!((report.verified, report.unlinked), (3, 0));
+        assert!(IntegrityService::checkpoint(&mut conn, 1, " ", now).is_err());
+        let checkpoint = IntegrityService::checkpoint(&mut conn, 1, SECRET, now).unwrap().unwrap();
+        assert_eq!(checkpoint.chain_seq, 3);
+        assert!(IntegrityService::checkpoint(&mut conn, 1, SECRET, now).unwrap().is_none());
+
+        // what someone with the database file could do
+        diesel::sql_query("DROP TRIGGER prevent_flow_update").execute(&mut conn).unwrap();
+        diesel::sql_query("UPDATE flow_events SET quantity_value = 20 WHERE id = ?")
+            .bind::<diesel::sql_types::Text, _>(&ids[1])
+            .execute(&mut conn)
+            .unwrap();
+
+        let report = IntegrityService::verify(&mut conn, 1, SECRET).unwrap();
+        assert!(!report.ok);
+        let broken = report.first_break.unwrap();
+        assert_eq!((broken.chain_seq, broken.flow_id), (2, Some(ids[1].clone())));
+        assert!(IntegrityService::checkpoint(&mut conn, 1, SECRET, now).is_err());
+    }
+
+    #[test]
+    fn actions_and_merges_are_chained() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut entity = |name: &str| create_test_entity(&mut conn, 1, name, "Person").id;
+        let (jane, jane_email, garden, pantry) =
+            (entity("Jane Doe"), entity("jane.doe@x.org"), entity("Garden"), entity("Pantry"));
+
+        let new = NewFlowEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: now,
+            recorded_at: now,
+            from_entity: jane_email.clone(),
+            to_entity: garden.clone(),
+            host_id: 1,
+            resource_type: "labor_time".to_string(),
+            quantity_value: 2.0,
+            quantity_unit: "hours".to_string(),
+            notes: None,
+            details: JsonField::default(),
+            created_by: "test".to_string(),
+        };
+        let flow = create_flow_event(&mut conn, &new).unwrap().id;
+        let confirmation = uuid::Uuid::new_v4().to_string();
+        let action = NewFlowAction {
+            id: &confirmation,
+            flow_id: &flow,
+            action_type: FlowActionType::Confirmation.value(),
+            actor_entity: &jane_email,
+            details: "{}",
+        };
+        let confirmed = create_flow_action(&mut conn, &action, &confirmation).unwrap();
+        assert_eq!(confirmed.chain_seq, Some(2));
+
+        // the flow's party and the confirmation's actor both move to Jane
+        EntityService::merge_entities(&mut conn, 1, &jane, &jane_email, &garden).unwrap();
+        let report = IntegrityService::verify(&mut conn, 1, SECRET).unwrap();
+        assert!(report.ok);
+        assert_eq!((report.verified, report.unlinked), (4, 0));
+
+        // an alias alone no longer explains a repointed flow
+        diesel::sql_query("INSERT INTO entity_aliases (id, entity_id, alias, created_by, normalized) VALUES ('forged', ?, ?, 'test', 'forged')")
+            .bind::<diesel::sql_types::Text, _>(&pantry)
+            .bind::<diesel::sql_types::Text, _>(&garden)
+            .execute(&mut conn)
+            .unwrap();
+        diesel::sql_query("UPDATE flow_events SET to_entity = ? WHERE id = ?")
+            .bind::<diesel::sql_types::Text, _>(&pantry)
+            .bind::<diesel::sql_types::Text, _>(&flow)
+            .execute(&mut conn)
+            .unwrap();
+        let broken = IntegrityService::verify(&mut conn, 1, SECRET).unwrap().first_break.unwrap();
+        assert_eq!((broken.chain_seq, broken.flow_id, broken.action_id), (1, Some(flow), None));
+    }
+}
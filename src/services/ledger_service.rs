@@ -1,16 +1,25 @@
 use crate::db::{DbConn, DbPool};
 use crate::errors::app_error::AppError;
+use crate::errors::auth_error::AuthError;
 use crate::models::entities::{Entity, EntityUser, NewEntity, NewEntityUser};
-use crate::models::flow_events::{FlowEvent, NewFlowEvent};
+use crate::models::flow_events::{self as flow_model, FlowAction, FlowEvent, NewFlowAction, NewFlowEvent};
+use crate::models::ledger_chain;
 use crate::schema::flow_events::host_id;
-use crate::schema::{entities, entity_users, flow_events};
-use crate::types::{Audience, ConfigHash, JsonField};
-use crate::types::flow_query::{FlowDirection, FlowQuery, FlowQueryBox};
+use crate::schema::{entities, entity_users, flow_actions, flow_events};
+use crate::models::resource_types::ResourceType;
+use crate::services::commitment_service::CommitmentService;
+use crate::services::entity_service::EntityService;
+use crate::services::resource_service::ResourceService;
+use crate::types::{
+    Audience, ConfigHash, EntityRole, EntityUserStatus, FlowActionType, FlowStatus, JsonField, Visibility,
+};
+use crate::types::flow_query::FlowQuery;
 use chrono::NaiveDateTime;
 use diesel::{alias, prelude::*};
 use serde::Serialize;
+use serde_json::json;
 use uuid::Uuid;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Service layer for interacting with entities and flow events in the ledger.
 pub struct LedgerService {
@@ -38,7 +47,15 @@ pub struct EntityRef {
     pub entity_type: String,
 }
 
-#[derive(Queryable, Serialize)]
+/// Result of a reversal or amendment.
+#[derive(Serialize)]
+pub struct FlowCorrection {
+    pub original_id: String,
+    pub reversal: FlowEvent,
+    pub replacement: Option<FlowEvent>,
+}
+
+#[derive(Serialize)]
 pub struct LedgerEventRow {
     pub id: String,
     pub timestamp: NaiveDateTime,
@@ -49,6 +66,52 @@ pub struct LedgerEventRow {
     pub from_entity: String,
     pub to_entity: String,
     pub notes: Option<String>,
+
+    pub status: FlowStatus,
+    pub actions: Vec<FlowActionView>,
+}
+
+/// Rows, the entities they mention, their distinct resource types and the
+/// host's resource registry.
+pub type LedgerEvents = (
+    Vec<LedgerEventRow>,
+    Vec<EntityRef>,
+    Vec<String>,
+    Vec<ResourceType>,
+);
+
+/// Columns loaded for a `LedgerEventRow` before its actions are attached.
+type LedgerEventColumns = (
+    String,
+    NaiveDateTime,
+    String,
+    f32,
+    String,
+    String,
+    String,
+    Option<String>,
+);
+
+/// One entry in a flow's action history.
+#[derive(Serialize, Clone)]
+pub struct FlowActionView {
+    pub id: String,
+    pub action_type: String,
+    pub actor_entity: String,
+    pub timestamp: NaiveDateTime,
+    pub details: serde_json::Value,
+}
+
+impl From<FlowAction> for FlowActionView {
+    fn from(action: FlowAction) -> Self {
+        FlowActionView {
+            details: serde_json::from_str(&action.details).unwrap_or_default(),
+            id: action.id,
+            action_type: action.action_type,
+            actor_entity: action.actor_entity,
+            timestamp: action.timestamp,
+        }
+    }
 }
 
 // impl From<LedgerEventRow> for LedgerEventDto {
@@ -127,11 +190,11 @@ impl LedgerService {
         conn: &mut DbConn,
         payload: Vec<NewEntity>,
     ) -> Result<String, AppError> {
-        let r = conn.transaction(|conn| {
+        conn.transaction(|conn| {
             diesel::insert_into(entities::table)
                 .values(&payload)
                 .execute(conn)
-        });
+        })?;
         Ok("saved".to_string())
     }
 
@@ -139,21 +202,31 @@ impl LedgerService {
         conn: &mut DbConn,
         payload: Vec<NewFlowEvent>,
     ) -> Result<String, AppError> {
-        let r = conn.transaction(|conn| {
+        let mut hosts: Vec<i32> = payload.iter().map(|f| f.host_id).collect();
+        hosts.sort();
+        hosts.dedup();
+        conn.transaction(|conn| {
             diesel::insert_into(flow_events::table)
                 .values(&payload)
-                .execute(conn)
-        });
+                .execute(conn)?;
+            for host in hosts {
+                ledger_chain::link_pending(conn, host)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })?;
         Ok("saved".to_string())
     }
 
     pub fn get_user_entity_id(conn: &mut DbConn, host: i32, user: i32) -> Result<String, AppError> {
         use crate::schema::{entities::dsl as e, entity_users::dsl as eu};
 
+        // Prefer the user's own Person entity over projects they are linked to.
         let entity_id_result = eu::entity_users
             .inner_join(e::entities.on(e::id.eq(eu::entity_id)))
             .filter(eu::user_id.eq(user))
+            .filter(eu::status.eq(EntityUserStatus::Active.value()))
             .filter(e::host_id.eq(host))
+            .order((e::entity_type.eq("Person").desc(), eu::id.asc()))
             .select(eu::entity_id)
             .first::<String>(conn);
 
@@ -178,8 +251,8 @@ impl LedgerService {
                 let new_entity_user = NewEntityUser {
                     entity_id: &entity.id,
                     user_id: user,
-                    role: "member",
-                    status: "active",
+                    role: EntityRole::Owner.value(),
+                    status: EntityUserStatus::Active.value(),
                 };
                 Self::create_entity_user(conn, new_entity_user)?;
 
@@ -203,12 +276,19 @@ impl LedgerService {
             .map_err(|e| e.into())
     }
 
-    pub fn get_entities(conn: &mut DbConn, host: i32) -> Result<Vec<Entity>, AppError> {
-        entities::table
+    pub fn get_entities(
+        conn: &mut DbConn,
+        host: i32,
+        include_archived: bool,
+    ) -> Result<Vec<Entity>, AppError> {
+        let mut query = entities::table
             .select(Entity::as_select())
             .filter(entities::host_id.eq(host))
-            .load(conn)
-            .map_err(|e| e.into())
+            .into_boxed();
+        if !include_archived {
+            query = query.filter(entities::archived_at.is_null());
+        }
+        query.load(conn).map_err(|e| e.into())
     }
 
     pub fn update_entity(
@@ -239,6 +319,7 @@ impl LedgerService {
             .values(&new)
             .execute(conn)
             .map_err(|e| AppError::User(e.to_string()))?;
+        ledger_chain::link_pending(conn, new.host_id)?;
 
         flow_events::table
             .find(&new.id)
@@ -277,6 +358,7 @@ impl LedgerService {
             .order(name.asc())
             .filter(host_id.eq(host))
             .filter(entity_type.eq("project"))
+            .filter(archived_at.is_null())
             .select((id, name))
             .into_boxed(); // <-- important
 
@@ -300,52 +382,25 @@ impl LedgerService {
     pub fn get_flow_events(
         conn: &mut DbConn,
         flow_query: FlowQuery,
-    ) -> Result<(Vec<LedgerEventRow>, Vec<EntityRef>, Vec<String>), AppError> {
+    ) -> Result<LedgerEvents, AppError> {
         use diesel::prelude::*;
 
-        // --- Step 1: Query flow_events filtered by FlowQuery ---
-        let mut query = flow_events::table
-            .filter(flow_events::host_id.eq(flow_query.host))
-            .into_boxed::<diesel::sqlite::Sqlite>();
-
-        // Entity + direction filters
-        if let Some(entity) = flow_query.entity {
-            match flow_query.direction {
-                FlowDirection::From => {
-                    query = query.filter(flow_events::from_entity.eq(entity.to_string()));
-                }
-                FlowDirection::To => {
-                    query = query.filter(flow_events::to_entity.eq(entity.to_string()));
-                }
-                FlowDirection::Both => {
-                    query = query.filter(
-                        flow_events::from_entity
-                            .eq(entity.to_string())
-                            .or(flow_events::to_entity.eq(entity.to_string())),
-                    );
-                }
-            }
-        }
+        let hidden = if flow_query.hide_reversed {
+            Self::reversed_pair_ids(conn, flow_query.host)?
+        } else {
+            Vec::new()
+        };
 
-        // Date filters
-        if let Some(since) = flow_query.since {
-            query = query.filter(flow_events::timestamp.ge(since));
-        }
-        if let Some(until) = flow_query.until {
-            query = query.filter(flow_events::timestamp.le(until));
-        }
+        // --- Step 1: Query flow_events filtered by FlowQuery ---
+        let mut query = flow_query.apply(flow_events::table.into_boxed());
 
-        // Pagination
-        if let Some(limit) = flow_query.limit {
-            query = query.limit(limit);
-        }
-        if let Some(offset) = flow_query.offset {
-            query = query.offset(offset);
+        // Correction filters
+        if !hidden.is_empty() {
+            query = query.filter(flow_events::id.ne_all(hidden));
         }
 
         // Execute the query
-        let rows: Vec<LedgerEventRow> = query
-            .order(flow_events::timestamp.desc())
+        let columns: Vec<LedgerEventColumns> = query
             .select((
                 flow_events::id,
                 flow_events::timestamp,
@@ -358,6 +413,28 @@ impl LedgerService {
             ))
             .load(conn)?;
 
+        let ids: Vec<String> = columns.iter().map(|c| c.0.clone()).collect();
+        let mut history = Self::get_flow_actions(conn, ids)?;
+
+        let rows: Vec<LedgerEventRow> = columns
+            .into_iter()
+            .map(|(id, timestamp, resource_type, quantity_value, quantity_unit, from_entity, to_entity, notes)| {
+                let actions = history.remove(&id).unwrap_or_default();
+                LedgerEventRow {
+                    status: status_of(&actions),
+                    actions,
+                    id,
+                    timestamp,
+                    resource_type,
+                    quantity_value,
+                    quantity_unit,
+                    from_entity,
+                    to_entity,
+                    notes,
+                }
+            })
+            .collect();
+
             // --- Step 2: Collect unique entity UUIDs from rows ---
     let mut entity_set = HashSet::new();
     let mut rt_set = HashSet::new();
@@ -365,17 +442,358 @@ impl LedgerService {
         entity_set.insert(row.from_entity.clone());
         entity_set.insert(row.to_entity.clone());
         rt_set.insert(row.resource_type.clone());
+        for action in &row.actions {
+            entity_set.insert(action.actor_entity.clone());
+        }
     }
     let uniq_rt: Vec<String> = rt_set.into_iter().collect();
 
     let uniq_entities: Vec<String> = entity_set.into_iter().collect();
-    let entities = Self::get_all_entities(conn, uniq_entities)?;
+    let mut entities = Self::get_all_entities(conn, uniq_entities)?;
+    EntityService::anonymise(conn, &flow_query.viewer, &mut entities)?;
 
     // --- Step 3: Load entities from DB ---
 
+    let registry = ResourceService::get_registry(conn, flow_query.host)?;
+
         // Convert rows to DTOs
         //Ok(rows.into_iter().map(Into::into).collect())
-        Ok((rows, entities, uniq_rt))
+        Ok((rows, entities, uniq_rt, registry))
+    }
+
+    // ----------------------------
+    // FLOW APPROVAL
+    // ----------------------------
+
+    /// Records `new` together with a `proposal` action by its creator.
+    /// The flow stays out of vital signs until the receiving side confirms it.
+    pub fn propose_flow(conn: &mut DbConn, new: NewFlowEvent) -> Result<FlowEvent, AppError> {
+        conn.transaction(|conn| {
+            let flow = flow_model::create_flow_event(conn, &new)?;
+
+            let action_id = Uuid::new_v4().to_string();
+            flow_model::create_flow_action(
+                conn,
+                &NewFlowAction {
+                    id: &action_id,
+                    flow_id: &flow.id,
+                    action_type: FlowActionType::Proposal.value(),
+                    actor_entity: &flow.created_by,
+                    details: "{}",
+                },
+                &action_id,
+            )?;
+
+            Ok(flow)
+        })
+    }
+
+    /// Parties to a flow (anyone actively linked to either side) and host
+    /// admins may change who sees it.
+    pub fn set_flow_visibility(
+        conn: &mut DbConn,
+        host: i32,
+        flow_id: &str,
+        user: i32,
+        is_host_admin: bool,
+        visibility: Visibility,
+    ) -> Result<(), AppError> {
+        let flow: FlowEvent = flow_events::table
+            .find(flow_id)
+            .filter(flow_events::host_id.eq(host))
+            .select(FlowEvent::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Flow {}", flow_id)))?;
+
+        let is_party = EntityService::active_role(conn, &flow.from_entity, user)?.is_some()
+            || EntityService::active_role(conn, &flow.to_entity, user)?.is_some();
+        if !is_host_admin && !is_party {
+            return Err(AuthError::Forbidden("Only parties to a flow can change its visibility").into());
+        }
+
+        diesel::update(flow_events::table.find(&flow.id))
+            .set(flow_events::visibility.eq(visibility.value()))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Confirms, disputes or rejects a flow on behalf of `user`, who must be
+    /// an active member of the flow's receiving entity.
+    pub fn decide_flow(
+        conn: &mut DbConn,
+        host: i32,
+        flow_id: &str,
+        user: i32,
+        action: FlowActionType,
+        reason: Option<&str>,
+    ) -> Result<FlowActionView, AppError> {
+        if action.status().is_none() || action == FlowActionType::Proposal {
+            return Err(AppError::BadRequest(format!(
+                "{} is not a decision",
+                action.value()
+            )));
+        }
+
+        conn.transaction(|conn| {
+            let flow: FlowEvent = flow_events::table
+                .find(flow_id)
+                .filter(flow_events::host_id.eq(host))
+                .select(FlowEvent::as_select())
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("Flow {}", flow_id)))?;
+
+            let role = EntityService::active_role(conn, &flow.to_entity, user)?;
+            if !role.is_some_and(EntityRole::records_flows) {
+                return Err(AuthError::Forbidden(
+                    "Only members of the receiving entity can decide on this flow",
+                )
+                .into());
+            }
+
+            let actions = Self::get_flow_actions(conn, vec![flow.id.clone()])?
+                .remove(&flow.id)
+                .unwrap_or_default();
+            let status = status_of(&actions);
+            if !status.allows(action) {
+                return Err(AppError::BadRequest(format!(
+                    "Cannot record {} on a {} flow",
+                    action.value(),
+                    status.value()
+                )));
+            }
+
+            let actor = Self::get_user_entity_id(conn, host, user)?;
+            let action_id = Uuid::new_v4().to_string();
+            let details = json!({ "user_id": user, "reason": reason }).to_string();
+            let recorded = flow_model::create_flow_action(
+                conn,
+                &NewFlowAction {
+                    id: &action_id,
+                    flow_id: &flow.id,
+                    action_type: action.value(),
+                    actor_entity: &actor,
+                    details: &details,
+                },
+                &action_id,
+            )?;
+
+            Ok(recorded.into())
+        })
+    }
+
+    /// Current approval status of one flow.
+    pub fn get_flow_status(conn: &mut DbConn, flow_id: &str) -> Result<FlowStatus, AppError> {
+        let actions = Self::get_flow_actions(conn, vec![flow_id.to_string()])?
+            .remove(flow_id)
+            .unwrap_or_default();
+        Ok(status_of(&actions))
+    }
+
+    /// Action history for each flow, oldest first.
+    pub fn get_flow_actions(
+        conn: &mut DbConn,
+        flow_ids: Vec<String>,
+    ) -> Result<HashMap<String, Vec<FlowActionView>>, AppError> {
+        let actions: Vec<FlowAction> = flow_actions::table
+            .filter(flow_actions::flow_id.eq_any(flow_ids))
+            // rowid breaks ties between actions recorded in the same second
+            .order((
+                flow_actions::timestamp.asc(),
+                diesel::dsl::sql::<diesel::sql_types::BigInt>("flow_actions.rowid").asc(),
+            ))
+            .select(FlowAction::as_select())
+            .load(conn)?;
+
+        let mut history: HashMap<String, Vec<FlowActionView>> = HashMap::new();
+        for action in actions {
+            history
+                .entry(action.flow_id.clone())
+                .or_default()
+                .push(action.into());
+        }
+        Ok(history)
+    }
+
+    // ----------------------------
+    // FLOW CORRECTIONS
+    // ----------------------------
+
+    /// Records a compensating flow that cancels `flow_id` and links the pair
+    /// with a `reversal` flow action. The original row is never modified.
+    pub fn reverse_flow(
+        conn: &mut DbConn,
+        host: i32,
+        flow_id: &str,
+        actor_entity: &str,
+        reason: Option<&str>,
+    ) -> Result<FlowCorrection, AppError> {
+        conn.transaction(|conn| {
+            let reversal = Self::record_reversal(conn, host, flow_id, actor_entity, reason)?;
+            Ok(FlowCorrection {
+                original_id: flow_id.to_string(),
+                reversal,
+                replacement: None,
+            })
+        })
+    }
+
+    /// Reverses `flow_id` and records `replacement` in its place, all in one
+    /// transaction. The replacement carries `"amends": <flow_id>` in details.
+    pub fn amend_flow(
+        conn: &mut DbConn,
+        host: i32,
+        flow_id: &str,
+        actor_entity: &str,
+        mut replacement: NewFlowEvent,
+        reason: Option<&str>,
+    ) -> Result<FlowCorrection, AppError> {
+        conn.transaction(|conn| {
+            let reversal = Self::record_reversal(conn, host, flow_id, actor_entity, reason)?;
+
+            let mut details = match replacement.details.0.take() {
+                serde_json::Value::Object(map) => map,
+                serde_json::Value::Null => serde_json::Map::new(),
+                other => {
+                    let mut map = serde_json::Map::new();
+                    map.insert("value".to_string(), other);
+                    map
+                }
+            };
+            details.insert("amends".to_string(), json!(flow_id));
+            replacement.details = JsonField(serde_json::Value::Object(details));
+
+            let replacement = flow_model::create_flow_event(conn, &replacement)?;
+
+            let action_id = Uuid::new_v4().to_string();
+            let action_details = json!({
+                "replacement_flow_id": replacement.id,
+                "reversal_flow_id": reversal.id,
+                "reason": reason,
+            })
+            .to_string();
+            flow_model::create_flow_action(
+                conn,
+                &NewFlowAction {
+                    id: &action_id,
+                    flow_id,
+                    action_type: FlowActionType::Amendment.value(),
+                    actor_entity,
+                    details: &action_details,
+                },
+                &action_id,
+            )?;
+
+            Ok(FlowCorrection {
+                original_id: flow_id.to_string(),
+                reversal,
+                replacement: Some(replacement),
+            })
+        })
+    }
+
+    fn record_reversal(
+        conn: &mut DbConn,
+        host: i32,
+        flow_id: &str,
+        actor_entity: &str,
+        reason: Option<&str>,
+    ) -> Result<FlowEvent, AppError> {
+        let original: FlowEvent = flow_events::table
+            .find(flow_id)
+            .filter(flow_events::host_id.eq(host))
+            .select(FlowEvent::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Flow {}", flow_id)))?;
+
+        // The compensating flow is recorded as confirmed, so reversing a
+        // flow that never counted would create a balance out of nothing.
+        let status = Self::get_flow_status(conn, &original.id)?;
+        if status != FlowStatus::Confirmed {
+            return Err(AppError::BadRequest(format!(
+                "Only confirmed flows can be reversed; flow {} is {}",
+                flow_id,
+                status.value()
+            )));
+        }
+
+        if original.details.0.get("reverses").is_some() {
+            return Err(AppError::BadRequest(
+                "A reversal cannot itself be reversed".to_string(),
+            ));
+        }
+
+        let already_reversed: i64 = flow_actions::table
+            .filter(flow_actions::flow_id.eq(flow_id))
+            .filter(flow_actions::action_type.eq(FlowActionType::Reversal.value()))
+            .count()
+            .get_result(conn)?;
+        if already_reversed > 0 {
+            return Err(AppError::BadRequest(format!(
+                "Flow {} has already been reversed",
+                flow_id
+            )));
+        }
+
+        // Same timestamp as the original so period totals net out;
+        // recorded_at shows when the correction was made.
+        let reversal = NewFlowEvent {
+            id: Uuid::new_v4().to_string(),
+            timestamp: original.timestamp,
+            recorded_at: chrono::Utc::now().naive_utc(),
+            from_entity: original.to_entity.clone(),
+            to_entity: original.from_entity.clone(),
+            host_id: host,
+            resource_type: original.resource_type.clone(),
+            quantity_value: original.quantity_value,
+            quantity_unit: original.quantity_unit.clone(),
+            notes: Some(format!("Reversal of {}", original.id)),
+            details: json!({ "type": "reversal", "reverses": original.id, "reason": reason }).into(),
+            created_by: actor_entity.to_string(),
+        };
+        let reversal = flow_model::create_flow_event(conn, &reversal)?;
+
+        let action_id = Uuid::new_v4().to_string();
+        let action_details = json!({ "reversal_flow_id": reversal.id, "reason": reason }).to_string();
+        flow_model::create_flow_action(
+            conn,
+            &NewFlowAction {
+                id: &action_id,
+                flow_id,
+                action_type: FlowActionType::Reversal.value(),
+                actor_entity,
+                details: &action_details,
+            },
+            &action_id,
+        )?;
+        // Whatever the flow covered is owed again.
+        CommitmentService::release_flow(conn, flow_id)?;
+
+        Ok(reversal)
+    }
+
+    /// Ids of every reversed flow on the host plus the flows that reversed them.
+    pub fn reversed_pair_ids(conn: &mut DbConn, host: i32) -> Result<Vec<String>, AppError> {
+        let actions: Vec<(String, String)> = flow_actions::table
+            .inner_join(flow_events::table)
+            .filter(flow_events::host_id.eq(host))
+            .filter(flow_actions::action_type.eq(FlowActionType::Reversal.value()))
+            .select((flow_actions::flow_id, flow_actions::details))
+            .load(conn)?;
+
+        let mut ids = Vec::with_capacity(actions.len() * 2);
+        for (original_id, details) in actions {
+            if let Some(reversal_id) = serde_json::from_str::<serde_json::Value>(&details)
+                .ok()
+                .and_then(|d| d.get("reversal_flow_id")?.as_str().map(str::to_string))
+            {
+                ids.push(reversal_id);
+            }
+            ids.push(original_id);
+        }
+        Ok(ids)
     }
 
     pub fn get_inflows(conn: &mut DbConn, entity_id: &str) -> Result<Vec<FlowEvent>, AppError> {
@@ -408,3 +826,215 @@ impl LedgerService {
             .map_err(|e| e.into())
     }
 }
+
+/// Status after replaying a flow's history; flows never proposed are confirmed.
+fn status_of(actions: &[FlowActionView]) -> FlowStatus {
+    actions
+        .iter()
+        .rev()
+        .find_map(|a| FlowActionType::from_value(&a.action_type)?.status())
+        .unwrap_or(FlowStatus::Confirmed)
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+
+    fn hours(from: &str, to: &str, qty: f32) -> NewFlowEvent {
+        let now = chrono::Utc::now().naive_utc();
+        NewFlowEvent {
+            id: Uuid::new_v4().to_string(),
+            timestamp: now,
+            recorded_at: now,
+            from_entity: from.to_string(),
+            to_entity: to.to_string(),
+            host_id: 1,
+            resource_type: "labor_time".to_string(),
+            quantity_value: qty,
+            quantity_unit: "hours".to_string(),
+            notes: None,
+            details: JsonField::default(),
+            created_by: from.to_string(),
+        }
+    }
+
+    #[test]
+    fn reversal_is_recorded_once_and_can_be_hidden() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+        let flow = LedgerService::create_flow_event(&mut conn, hours(&jane, &garden, 3.0)).unwrap();
+
+        let correction =
+            LedgerService::reverse_flow(&mut conn, 1, &flow.id, &jane, Some("typo")).unwrap();
+        assert_eq!(correction.reversal.from_entity, garden);
+        assert_eq!(correction.reversal.to_entity, jane);
+
+        assert!(LedgerService::reverse_flow(&mut conn, 1, &flow.id, &jane, None).is_err());
+        assert!(
+            LedgerService::reverse_flow(&mut conn, 1, &correction.reversal.id, &jane, None).is_err()
+        );
+
+        let mut query = FlowQuery::new(1).both();
+        query.hide_reversed = true;
+        let (rows, _, _, _) = LedgerService::get_flow_events(&mut conn, query).unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn only_confirmed_flows_can_be_reversed() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+        LedgerService::create_entity_user(
+            &mut conn,
+            NewEntityUser {
+                entity_id: &garden,
+                user_id,
+                role: "steward",
+                status: "active",
+            },
+        )
+        .unwrap();
+
+        let proposed = LedgerService::propose_flow(&mut conn, hours(&jane, &garden, 2.0)).unwrap();
+        assert!(LedgerService::reverse_flow(&mut conn, 1, &proposed.id, &jane, None).is_err());
+
+        let rejected = LedgerService::propose_flow(&mut conn, hours(&jane, &garden, 3.0)).unwrap();
+        LedgerService::decide_flow(&mut conn, 1, &rejected.id, user_id, FlowActionType::Rejection, None)
+            .unwrap();
+        assert!(LedgerService::reverse_flow(&mut conn, 1, &rejected.id, &jane, None).is_err());
+
+        LedgerService::decide_flow(&mut conn, 1, &proposed.id, user_id, FlowActionType::Confirmation, None)
+            .unwrap();
+        LedgerService::reverse_flow(&mut conn, 1, &proposed.id, &jane, None).unwrap();
+    }
+
+    #[test]
+    fn only_receiving_members_decide_and_vital_signs_wait_for_confirmation() {
+        use crate::models::ledger_views::get_total_hours;
+
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let since = chrono::Utc::now().naive_utc() - chrono::Duration::days(1);
+        let until = since + chrono::Duration::days(2);
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+        let flow = LedgerService::propose_flow(&mut conn, hours(&jane, &garden, 2.0)).unwrap();
+
+        let (rows, _, _, _) = LedgerService::get_flow_events(&mut conn, FlowQuery::new(1)).unwrap();
+        assert_eq!(rows[0].status, FlowStatus::Proposed);
+        assert_eq!(get_total_hours(&mut conn, 1, since, until).unwrap().total_hours, 0.0);
+
+        let decide = |conn: &mut DbConn, action| {
+            LedgerService::decide_flow(conn, 1, &flow.id, user_id, action, None)
+        };
+        assert!(decide(&mut conn, FlowActionType::Confirmation).is_err());
+
+        LedgerService::create_entity_user(
+            &mut conn,
+            NewEntityUser {
+                entity_id: &garden,
+                user_id,
+                role: "steward",
+                status: "active",
+            },
+        )
+        .unwrap();
+        decide(&mut conn, FlowActionType::Dispute).unwrap();
+        decide(&mut conn, FlowActionType::Confirmation).unwrap();
+        assert!(decide(&mut conn, FlowActionType::Rejection).is_err());
+
+        let (rows, _, _, _) = LedgerService::get_flow_events(&mut conn, FlowQuery::new(1)).unwrap();
+        assert_eq!(rows[0].status, FlowStatus::Confirmed);
+        assert_eq!(rows[0].actions.len(), 3);
+        assert_eq!(get_total_hours(&mut conn, 1, since, until).unwrap().total_hours, 2.0);
+    }
+
+    #[test]
+    fn keyset_pages_do_not_drift_when_flows_arrive() {
+        use crate::types::flow_query::FlowCursor;
+
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+        let base = chrono::Utc::now().naive_utc() - chrono::Duration::days(1);
+        for i in 0..4 {
+            let mut flow = hours(&jane, &garden, (i + 1) as f32);
+            flow.timestamp = base + chrono::Duration::minutes(i);
+            LedgerService::create_flow_event(&mut conn, flow).unwrap();
+        }
+
+        let mut query = FlowQuery::new(1);
+        query.limit = Some(2);
+        let (first, _, _, _) = LedgerService::get_flow_events(&mut conn, query.clone()).unwrap();
+        let quantities: Vec<f32> = first.iter().map(|r| r.quantity_value).collect();
+        assert_eq!(quantities, vec![4.0, 3.0]);
+
+        // a newer flow lands between page requests
+        LedgerService::create_flow_event(&mut conn, hours(&jane, &garden, 9.0)).unwrap();
+
+        let last = first.last().unwrap();
+        query.cursor = FlowCursor::decode(
+            &FlowCursor { timestamp: last.timestamp, id: last.id.clone() }.encode(),
+        );
+        let (second, _, _, _) = LedgerService::get_flow_events(&mut conn, query).unwrap();
+        let quantities: Vec<f32> = second.iter().map(|r| r.quantity_value).collect();
+        assert_eq!(quantities, vec![2.0, 1.0]);
+    }
+
+    #[test]
+    fn visibility_limits_rows_and_names_by_viewer() {
+        use crate::types::flow_query::FlowViewer;
+
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        let jane = create_test_entity(&mut conn, 1, "Jane", "Person").id;
+        let garden = create_test_entity(&mut conn, 1, "Garden", "Person").id;
+        let mut ids = Vec::new();
+        for visibility in [Visibility::Public, Visibility::Members, Visibility::Parties] {
+            let flow = LedgerService::create_flow_event(&mut conn, hours(&jane, &garden, 1.0)).unwrap();
+            LedgerService::set_flow_visibility(&mut conn, 1, &flow.id, user_id, true, visibility).unwrap();
+            ids.push(flow.id);
+        }
+
+        let visible_to = |conn: &mut DbConn, viewer: FlowViewer| {
+            let mut query = FlowQuery::new(1);
+            query.viewer = viewer;
+            let (rows, entities, _, _) = LedgerService::get_flow_events(conn, query).unwrap();
+            let names: HashSet<String> = entities.into_iter().map(|e| e.name).collect();
+            (rows.len(), names)
+        };
+
+        let (count, names) = visible_to(&mut conn, FlowViewer::Anonymous);
+        assert_eq!(count, 1);
+        assert!(names.iter().all(|n| n == "Anonymous"));
+
+        let member = FlowViewer::User { user_id, is_member: true };
+        assert_eq!(visible_to(&mut conn, member.clone()).0, 2);
+
+        LedgerService::create_entity_user(
+            &mut conn,
+            NewEntityUser {
+                entity_id: &garden,
+                user_id,
+                role: "member",
+                status: "active",
+            },
+        )
+        .unwrap();
+        let (count, names) = visible_to(&mut conn, member);
+        assert_eq!(count, 3);
+        assert!(names.contains("Jane") && names.contains("Garden"));
+        assert_eq!(visible_to(&mut conn, FlowViewer::System).0, 3);
+    }
+}
@@ -6,5 +6,18 @@ pub mod weekly_reflection_service;
 pub mod dto;
 
 pub mod ledger_service;
+pub mod balance_service;
+pub mod entity_service;
+pub mod resource_service;
+pub mod entity_type_service;
+pub mod import_service;
+pub mod export_service;
+pub mod graph_service;
+pub mod template_service;
+pub mod commitment_service;
+pub mod goal_service;
+pub mod geo_service;
+pub mod integrity_service;
 pub mod member_content_service;
-pub mod draft_service;
\ No newline at end of file
+pub mod draft_service;
+pub mod publish_service;
\ No newline at end of file
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+
+use crate::errors::app_error::AppError;
+use crate::settings::PublishConfig;
+use crate::types::DocType;
+
+/// One lock per site repository, so a host's deploys run one at a time.
+static REPO_LOCKS: Lazy<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone)]
+pub struct GitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+/// Writes deployed drafts into a host's static site repository and
+/// commits each change. Uses the `git` on the server's PATH.
+pub struct SitePublisher {
+    repo_dir: PathBuf,
+    content_dir: String,
+    committer: GitAuthor,
+}
+
+impl SitePublisher {
+    /// `None` when publishing is not configured.
+    pub fn for_host(config: &PublishConfig, host_slug: &str) -> Option<Self> {
+        if config.repo_dir.trim().is_empty() {
+            return None;
+        }
+        let or = |value: &str, default: &str| {
+            if value.trim().is_empty() { default.to_string() } else { value.to_string() }
+        };
+        Some(Self {
+            repo_dir: PathBuf::from(config.repo_dir.replace("{host}", &slugify(host_slug))),
+            content_dir: or(config.content_dir.trim_matches('/'), "content"),
+            committer: GitAuthor {
+                name: or(&config.committer_name, "Heron"),
+                email: or(&config.committer_email, "heron@localhost"),
+            },
+        })
+    }
+
+    /// Path of a document inside the repository, e.g. `content/recipes/<slug>.md`.
+    pub fn path_for(&self, doc_type: DocType, slug: &str) -> Result<String, AppError> {
+        let slug = slugify(slug);
+        if slug.is_empty() {
+            return Err(AppError::BadRequest(
+                "A published page needs a slug or title with letters or digits".into(),
+            ));
+        }
+        Ok(format!("{}/{}/{}.md", self.content_dir, doc_type.content_dir(), slug))
+    }
+
+    /// Runs `work` while holding the repository's lock.
+    pub fn exclusive<T>(&self, work: impl FnOnce() -> Result<T, AppError>) -> Result<T, AppError> {
+        let lock = {
+            let mut locks = REPO_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+            locks.entry(self.repo_dir.clone()).or_default().clone()
+        };
+        let _held = lock.lock().unwrap_or_else(|e| e.into_inner());
+        work()
+    }
+
+    /// The current commit.
+    pub fn head(&self) -> Result<String, AppError> {
+        self.ensure_repo()?;
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    /// Undoes the commit `publish` or `unpublish` made on top of `before`,
+    /// e.g. when recording a deploy failed. Only `path` is restored; the
+    /// rest of the work tree is left as it is.
+    pub fn undo_commit(&self, before: &str, path: &str) -> Result<(), AppError> {
+        if self.git(&["rev-parse", "HEAD"])? == before {
+            return Ok(());
+        }
+        if self.git(&["rev-parse", "HEAD^"])? != before {
+            return Err(AppError::Internal(format!(
+                "The site has moved on from {}; not undoing its last commit",
+                before
+            )));
+        }
+        self.git(&["reset", "-q", "--soft", before])?;
+        let source = format!("--source={}", before);
+        self.git(&["restore", "-q", &source, "--staged", "--worktree", "--", path])
+            .map(|_| ())
+    }
+
+    /// Writes the file and commits it; returns the commit id. Publishing
+    /// unchanged content makes no commit and returns the current one.
+    pub fn publish(
+        &self,
+        path: &str,
+        contents: &str,
+        author: &GitAuthor,
+        message: &str,
+    ) -> Result<String, AppError> {
+        self.ensure_repo()?;
+        let file = self.repo_dir.join(path);
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+        }
+        std::fs::write(&file, contents).map_err(|e| io_error(&file, e))?;
+        self.git(&["add", "--", path])?;
+        self.commit(path, author, message)
+    }
+
+    /// Removes the file in a new commit and returns its id.
+    pub fn unpublish(&self, path: &str, author: &GitAuthor, message: &str) -> Result<String, AppError> {
+        self.ensure_repo()?;
+        self.git(&["rm", "-q", "--ignore-unmatch", "--", path])?;
+        self.commit(path, author, message)
+    }
+
+    fn commit(&self, path: &str, author: &GitAuthor, message: &str) -> Result<String, AppError> {
+        let staged = self.git(&["status", "--porcelain", "--", path])?;
+        if !staged.trim().is_empty() {
+            let author = format!("{} <{}>", author.name, author.email);
+            self.git(&["commit", "-q", "--author", &author, "-m", message, "--", path])?;
+        }
+        self.git(&["rev-parse", "HEAD"])
+    }
+
+    fn ensure_repo(&self) -> Result<(), AppError> {
+        if !self.repo_dir.join(".git").exists() {
+            std::fs::create_dir_all(&self.repo_dir).map_err(|e| io_error(&self.repo_dir, e))?;
+            self.git(&["init", "-q"])?;
+        }
+        // an empty first commit, so there is always one to reset to
+        if self.git(&["rev-parse", "--verify", "-q", "HEAD"]).is_err() {
+            self.git(&["commit", "-q", "--allow-empty", "-m", "Start site"])?;
+        }
+        Ok(())
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, AppError> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.repo_dir)
+            .args(["-c", &format!("user.name={}", self.committer.name)])
+            .args(["-c", &format!("user.email={}", self.committer.email)])
+            .args(args)
+            .output()
+            .map_err(|e| AppError::Internal(format!("Could not run git: {}", e)))?;
+        if !output.status.success() {
+            return Err(AppError::Internal(format!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// Lowercase letters and digits joined by single hyphens, so a slug can
+/// only name a file inside its folder.
+pub fn slugify(value: &str) -> String {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> AppError {
+    AppError::Internal(format!("Could not write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::models::drafts::{Draft, NewDraft};
+    use diesel::RunQueryDsl;
+    use crate::models::users::get_user_by_id;
+    use crate::services::draft_service::{DraftActor, DraftService};
+    use crate::test_support::db::setup_test_db;
+    use crate::types::DraftAction;
+
+    #[test]
+    fn deploying_commits_the_page_and_unpublishing_removes_it() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let site = tempfile::tempdir().unwrap();
+        let config = PublishConfig {
+            repo_dir: format!("{}/{{host}}", site.path().display()),
+            ..Default::default()
+        };
+        let publisher = SitePublisher::for_host(&config, "Iowa City").unwrap();
+
+        let new = NewDraft {
+            doc_type: "recipe".to_string(),
+            title: "Rhubarb Crisp!".to_string(),
+            description: None,
+            tags: None,
+            author: None,
+            body_md: "Oats, butter, rhubarb.".to_string(),
+            meta: None,
+            status: Some("draft".to_string()),
+            submitted_by: Some(user_id),
+            submitted_at: None,
+            reviewed_by: None,
+            reviewed_at: None,
+            review_notes: None,
+            details: None,
+            host_id: 1,
+        };
+        let id = DraftService::create_draft(&mut conn, &new).unwrap().id;
+        let author = DraftActor { user_id, is_reviewer: false };
+        let reviewer = DraftActor { user_id, is_reviewer: true };
+        DraftService::transition(&mut conn, 1, id, DraftAction::Submit, &author, None).unwrap();
+
+        let markdown = |draft: &Draft| format!("---\ntitle: \"{}\"\n---\n\n{}", draft.title, draft.body_md);
+        assert!(DraftService::publish(&mut conn, 1, id, &reviewer, &publisher, markdown).is_err());
+        DraftService::transition(&mut conn, 1, id, DraftAction::Approve, &reviewer, None).unwrap();
+        let deployed = DraftService::publish(&mut conn, 1, id, &reviewer, &publisher, markdown).unwrap();
+
+        let file = site.path().join("iowa-city/content/recipes/rhubarb-crisp.md");
+        assert_eq!(deployed.published_path.as_deref(), Some("content/recipes/rhubarb-crisp.md"));
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "---\ntitle: \"Rhubarb Crisp!\"\n---\n\nOats, butter, rhubarb."
+        );
+        let head = publisher.git(&["log", "-1", "--format=%H %ae"]).unwrap();
+        let user = get_user_by_id(&mut conn, user_id).unwrap();
+        assert_eq!(head, format!("{} {}", deployed.published_commit.unwrap(), user.email));
+
+        let unpublished = DraftService::unpublish(&mut conn, 1, id, &reviewer, &publisher).unwrap();
+        assert_eq!((unpublished.status.as_str(), unpublished.published_path), ("approved", None));
+        assert!(!file.exists());
+        let history = DraftService::transitions(&mut conn, 1, id).unwrap();
+        let last = history.last().unwrap();
+        assert_eq!(last.action, "unpublish");
+        assert_eq!(last.commit_id.as_deref(), Some(publisher.git(&["rev-parse", "HEAD"]).unwrap().as_str()));
+
+        // a second page with the same title doesn't overwrite the first
+        let mut approved = |title: &str| {
+            let new = NewDraft { title: title.to_string(), ..new.clone() };
+            let id = DraftService::create_draft(&mut conn, &new).unwrap().id;
+            DraftService::transition(&mut conn, 1, id, DraftAction::Submit, &author, None).unwrap();
+            DraftService::transition(&mut conn, 1, id, DraftAction::Approve, &reviewer, None).unwrap();
+            id
+        };
+        let (twin, unnamed) = (approved("Rhubarb Crisp"), approved("!!!"));
+        DraftService::publish(&mut conn, 1, id, &reviewer, &publisher, markdown).unwrap();
+        let deployed = DraftService::publish(&mut conn, 1, twin, &reviewer, &publisher, markdown).unwrap();
+        let twin_path = format!("content/recipes/rhubarb-crisp-{}.md", twin);
+        assert_eq!(deployed.published_path, Some(twin_path.clone()));
+        assert!(file.exists());
+        assert!(DraftService::publish(&mut conn, 1, unnamed, &reviewer, &publisher, markdown).is_err());
+
+        // the site commit is undone when the draft can't be updated,
+        // leaving other edits in the work tree alone
+        let head = publisher.head().unwrap();
+        std::fs::write(&file, "edited by hand").unwrap();
+        diesel::sql_query(
+            "CREATE TRIGGER fail_publication BEFORE UPDATE OF published_path ON drafts \
+             BEGIN SELECT RAISE(FAIL, 'disk full'); END",
+        )
+        .execute(&mut conn)
+        .unwrap();
+        assert!(DraftService::unpublish(&mut conn, 1, twin, &reviewer, &publisher).is_err());
+        assert_eq!(publisher.head().unwrap(), head);
+        assert!(site.path().join("iowa-city").join(&twin_path).exists());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "edited by hand");
+    }
+}
@@ -0,0 +1,139 @@
+use diesel::prelude::*;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::resource_types::{self as resource_model, NewResourceType, ResourceType};
+use crate::schema::resource_types;
+
+pub struct ResourceService;
+
+impl ResourceService {
+    pub fn get_registry(conn: &mut DbConn, host: i32) -> Result<Vec<ResourceType>, AppError> {
+        resource_model::get_resource_types(conn, host).map_err(AppError::Db)
+    }
+
+    pub fn create(conn: &mut DbConn, new: NewResourceType) -> Result<ResourceType, AppError> {
+        Self::check(&new)?;
+        let exists = resource_model::get_resource_type(conn, new.host_id, &new.key)
+            .optional()?
+            .is_some();
+        if exists {
+            return Err(AppError::BadRequest(format!(
+                "Resource type '{}' already exists",
+                new.key
+            )));
+        }
+        resource_model::create_resource_type(conn, &new).map_err(AppError::Db)
+    }
+
+    pub fn update(conn: &mut DbConn, id: i32, new: NewResourceType) -> Result<ResourceType, AppError> {
+        Self::check(&new)?;
+        Self::get_on_host(conn, new.host_id, id)?;
+        resource_model::update_resource_type(conn, id, &new).map_err(AppError::Db)
+    }
+
+    pub fn delete(conn: &mut DbConn, host: i32, id: i32) -> Result<(), AppError> {
+        Self::get_on_host(conn, host, id)?;
+        resource_model::delete_resource_type(conn, id)?;
+        Ok(())
+    }
+
+    /// Checks a flow's resource type and unit against the host registry.
+    /// Hosts that have not set up a registry accept anything.
+    pub fn validate_flow(
+        conn: &mut DbConn,
+        host: i32,
+        resource_type: &str,
+        unit: &str,
+    ) -> Result<(), AppError> {
+        let registry = Self::get_registry(conn, host)?;
+        Self::check_flow(&registry, resource_type, unit)
+    }
+
+    /// `validate_flow` against a registry the caller already loaded.
+    pub fn check_flow(registry: &[ResourceType], resource_type: &str, unit: &str) -> Result<(), AppError> {
+        if registry.is_empty() {
+            return Ok(());
+        }
+
+        let resource = registry
+            .iter()
+            .find(|r| r.key == resource_type)
+            .ok_or_else(|| {
+                let known: Vec<&str> = registry.iter().map(|r| r.key.as_str()).collect();
+                AppError::BadRequest(format!(
+                    "Unknown resource type '{}'; expected one of: {}",
+                    resource_type,
+                    known.join(", ")
+                ))
+            })?;
+
+        if resource.factor(unit).is_none() {
+            return Err(AppError::BadRequest(format!(
+                "Unit '{}' is not allowed for {}; expected one of: {}",
+                unit,
+                resource.key,
+                resource.allowed_units().join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn check(new: &NewResourceType) -> Result<(), AppError> {
+        if new.key.trim().is_empty() || new.label.trim().is_empty() {
+            return Err(AppError::BadRequest("Resource key and label are required".into()));
+        }
+        if new.default_unit.trim().is_empty() {
+            return Err(AppError::BadRequest("Resource default_unit is required".into()));
+        }
+        let units = match &new.units.0 {
+            serde_json::Value::Null => return Ok(()),
+            serde_json::Value::Object(map) => map,
+            _ => return Err(AppError::BadRequest("units must be an object of unit: factor".into())),
+        };
+        for (unit, factor) in units {
+            if !factor.as_f64().is_some_and(|f| f > 0.0) {
+                return Err(AppError::BadRequest(format!(
+                    "Conversion factor for '{}' must be a positive number",
+                    unit
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_on_host(conn: &mut DbConn, host: i32, id: i32) -> Result<ResourceType, AppError> {
+        resource_types::table
+            .find(id)
+            .filter(resource_types::host_id.eq(host))
+            .select(ResourceType::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Resource type {}", id)))
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_support::db::setup_test_db;
+
+    #[test]
+    fn flows_must_use_a_registered_type_and_unit() {
+        let (_tmp, pool, _user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+
+        assert!(ResourceService::validate_flow(&mut conn, 1, "labor_time", "minutes").is_ok());
+        assert!(matches!(
+            ResourceService::validate_flow(&mut conn, 1, "labor_time", "USD"),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            ResourceService::validate_flow(&mut conn, 1, "vibes", "hours"),
+            Err(AppError::BadRequest(_))
+        ));
+
+        // host 0 has no registry, so it accepts anything
+        assert!(ResourceService::validate_flow(&mut conn, 0, "vibes", "hours").is_ok());
+    }
+}
@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::DbConn;
+use crate::errors::app_error::AppError;
+use crate::models::flow_events::NewFlowEvent;
+use crate::models::flow_templates::{self as template_model, FlowTemplate, NewFlowTemplate};
+use crate::schema::flow_templates;
+use crate::services::entity_service::EntityService;
+use crate::services::ledger_service::LedgerService;
+use crate::services::resource_service::ResourceService;
+use crate::types::{JsonField, Recurrence};
+
+/// How often the scheduler looks for due templates.
+pub const TEMPLATE_SCHEDULER_PERIOD: Duration = Duration::from_secs(15 * 60);
+
+/// Occurrences backfilled per template per run, so a long outage can't
+/// flood the ledger in one go. The rest follow on later runs.
+pub const MAX_CATCH_UP: usize = 31;
+
+/// What running a template produces: the flows to propose and the state
+/// the template moves on to.
+pub struct TemplateRun {
+    pub flows: Vec<NewFlowEvent>,
+    pub next_run: NaiveDateTime,
+    pub run_count: i32,
+    pub finished: bool,
+}
+
+pub struct TemplateService;
+
+impl TemplateService {
+    /// Creates a template on behalf of a user who records flows for
+    /// `new.from_entity`, or a host admin.
+    pub fn create(
+        conn: &mut DbConn,
+        user: i32,
+        is_host_admin: bool,
+        new: NewFlowTemplate,
+    ) -> Result<FlowTemplate, AppError> {
+        EntityService::require_recorder(conn, &new.from_entity, user, is_host_admin)?;
+        ResourceService::validate_flow(conn, new.host_id, &new.resource_type, &new.quantity_unit)?;
+
+        if Recurrence::from_value(&new.recurrence).is_none() {
+            return Err(AppError::BadRequest(format!("Unknown recurrence '{}'", new.recurrence)));
+        }
+        if new.every < 1 {
+            return Err(AppError::BadRequest("every must be at least 1".into()));
+        }
+        if !(new.quantity_value.is_finite() && new.quantity_value > 0.0) {
+            return Err(AppError::BadRequest("quantity_value must be positive".into()));
+        }
+        if new.ends_at.is_some_and(|end| end < new.starts_at) {
+            return Err(AppError::BadRequest("ends_at is before starts_at".into()));
+        }
+
+        template_model::create_flow_template(conn, &new).map_err(AppError::Db)
+    }
+
+    pub fn get_for_entity(
+        conn: &mut DbConn,
+        host: i32,
+        entity_id: &str,
+    ) -> Result<Vec<FlowTemplate>, AppError> {
+        let templates = template_model::get_templates_for_entity(conn, entity_id)?;
+        Ok(templates.into_iter().filter(|t| t.host_id == host).collect())
+    }
+
+    /// Pauses or resumes a template. Resuming skips the occurrences missed
+    /// while paused instead of backfilling them.
+    pub fn set_paused(
+        conn: &mut DbConn,
+        host: i32,
+        template_id: &str,
+        user: i32,
+        is_host_admin: bool,
+        paused: bool,
+        now: NaiveDateTime,
+    ) -> Result<FlowTemplate, AppError> {
+        let template = Self::get_on_host(conn, host, template_id)?;
+        let from = EntityService::require_recorder(conn, &template.from_entity, user, is_host_admin);
+        if from.is_err() {
+            EntityService::require_recorder(conn, &template.to_entity, user, is_host_admin)?;
+        }
+
+        if paused {
+            template_model::set_paused(conn, template_id, true)?;
+        } else {
+            let recurrence = recurrence_of(&template)?;
+            let every = template.every as u32;
+            let mut run_count = template.run_count as u32;
+            let mut next_run = template.next_run;
+            while next_run < now {
+                run_count += 1;
+                next_run = recurrence
+                    .occurrence(template.starts_at, every, run_count)
+                    .ok_or_else(|| AppError::BadRequest("Template schedule overflowed".into()))?;
+            }
+            if template.ends_at.is_some_and(|end| next_run > end) {
+                return Err(AppError::BadRequest("This template has ended".into()));
+            }
+            template_model::advance(
+                conn,
+                template_id,
+                next_run,
+                run_count as i32,
+                template.last_run_at,
+                false,
+            )?;
+        }
+
+        template_model::get_flow_template(conn, template_id).map_err(AppError::Db)
+    }
+
+    /// The flows `template` owes up to `now`, at most `MAX_CATCH_UP` of them.
+    /// Each flow is stamped with its occurrence time.
+    pub fn due_flows(template: &FlowTemplate, now: NaiveDateTime) -> Result<TemplateRun, AppError> {
+        let recurrence = recurrence_of(template)?;
+        let every = template.every as u32;
+        let within_end = |at: NaiveDateTime| template.ends_at.is_none_or(|end| at <= end);
+
+        let mut details = template.details.0.clone();
+        if let Some(map) = details.as_object_mut() {
+            map.insert("template_id".into(), template.id.clone().into());
+        }
+
+        let mut flows = Vec::new();
+        let mut run_count = template.run_count as u32;
+        let mut next_run = template.next_run;
+        while next_run <= now && within_end(next_run) && flows.len() < MAX_CATCH_UP {
+            flows.push(NewFlowEvent {
+                id: Uuid::new_v4().to_string(),
+                timestamp: next_run,
+                recorded_at: now,
+                from_entity: template.from_entity.clone(),
+                to_entity: template.to_entity.clone(),
+                host_id: template.host_id,
+                resource_type: template.resource_type.clone(),
+                quantity_value: template.quantity_value,
+                quantity_unit: template.quantity_unit.clone(),
+                notes: template.notes.clone(),
+                details: JsonField(details.clone()),
+                created_by: template.created_by.clone(),
+            });
+            run_count += 1;
+            next_run = recurrence
+                .occurrence(template.starts_at, every, run_count)
+                .ok_or_else(|| AppError::BadRequest("Template schedule overflowed".into()))?;
+        }
+
+        Ok(TemplateRun {
+            flows,
+            next_run,
+            run_count: run_count as i32,
+            finished: !within_end(next_run),
+        })
+    }
+
+    /// Proposes every due template flow and advances the templates. Each
+    /// template commits on its own, so one bad template doesn't hold up
+    /// the rest. Returns the number of flows proposed.
+    pub fn run_due(conn: &mut DbConn, now: NaiveDateTime) -> Result<usize, AppError> {
+        let mut proposed = 0;
+        for template in template_model::get_due_templates(conn, now)? {
+            let result = conn.transaction(|conn| {
+                let run = Self::due_flows(&template, now)?;
+                let count = run.flows.len();
+                for flow in run.flows {
+                    LedgerService::propose_flow(conn, flow)?;
+                }
+                let last_run_at = if count > 0 { Some(now) } else { template.last_run_at };
+                template_model::advance(
+                    conn,
+                    &template.id,
+                    run.next_run,
+                    run.run_count,
+                    last_run_at,
+                    run.finished,
+                )?;
+                Ok::<_, AppError>(count)
+            });
+            match result {
+                Ok(count) => proposed += count,
+                Err(e) => log::error!("Flow template {} failed: {}", template.id, e),
+            }
+        }
+        Ok(proposed)
+    }
+
+    fn get_on_host(conn: &mut DbConn, host: i32, template_id: &str) -> Result<FlowTemplate, AppError> {
+        flow_templates::table
+            .find(template_id)
+            .filter(flow_templates::host_id.eq(host))
+            .select(FlowTemplate::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Flow template {}", template_id)))
+    }
+}
+
+fn recurrence_of(template: &FlowTemplate) -> Result<Recurrence, AppError> {
+    Recurrence::from_value(&template.recurrence)
+        .ok_or_else(|| AppError::Internal(format!("Unknown recurrence '{}'", template.recurrence)))
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use crate::test_support::db::{create_test_entity, setup_test_db};
+
+    #[test]
+    fn due_templates_propose_each_missed_occurrence_once() {
+        let (_tmp, pool, user_id) = setup_test_db();
+        let mut conn = pool.get().unwrap();
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+
+        let mut entity = |name: &str| create_test_entity(&mut conn, 1, name, "Person").id;
+        let (jane, garden) = (entity("Jane"), entity("Garden"));
+
+        let pledge = NewFlowTemplate {
+            id: Uuid::new_v4().to_string(),
+            host_id: 1,
+            from_entity: jane.clone(),
+            to_entity: garden,
+            resource_type: "Dollars".to_string(),
+            quantity_value: 25.0,
+            quantity_unit: "USD".to_string(),
+            notes: Some("Monthly pledge".to_string()),
+            details: JsonField(serde_json::json!({})),
+            recurrence: Recurrence::Monthly.value().to_string(),
+            every: 1,
+            starts_at: at("2024-01-31 09:00"),
+            ends_at: Some(at("2024-04-30 09:00")),
+            next_run: at("2024-01-31 09:00"),
+            created_by: jane,
+        };
+        let never = NewFlowTemplate { every: 0, ..pledge.clone() };
+        assert!(TemplateService::create(&mut conn, user_id, true, never).is_err());
+        let template = TemplateService::create(&mut conn, user_id, true, pledge).unwrap();
+
+        assert_eq!(TemplateService::run_due(&mut conn, at("2024-03-01 00:00")).unwrap(), 2);
+        assert_eq!(TemplateService::run_due(&mut conn, at("2024-03-01 00:00")).unwrap(), 0);
+
+        let template = template_model::get_flow_template(&mut conn, &template.id).unwrap();
+        assert_eq!(template.next_run, at("2024-03-31 09:00"));
+
+        assert_eq!(TemplateService::run_due(&mut conn, at("2025-01-01 00:00")).unwrap(), 2);
+        let template = template_model::get_flow_template(&mut conn, &template.id).unwrap();
+        assert!(template.paused);
+
+        let stamps: Vec<NaiveDateTime> = crate::models::flow_events::get_flow_events(&mut conn)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.timestamp)
+            .collect();
+        assert_eq!(
+            stamps,
+            ["2024-01-31 09:00", "2024-02-29 09:00", "2024-03-31 09:00", "2024-04-30 09:00"].map(at)
+        );
+    }
+}
@@ -63,6 +63,29 @@ pub struct Twilio {
 }
 
 
+/// Where deployed drafts are published as markdown for the static site.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PublishConfig {
+    /// Git work tree each host publishes into; `{host}` is replaced by the
+    /// host's slug. Empty turns publishing off.
+    pub repo_dir: String,
+    /// Content root inside the work tree; defaults to `content`.
+    pub content_dir: String,
+    /// Committer for published changes; the reviewer is the author.
+    pub committer_name: String,
+    pub committer_email: String,
+}
+
+/// Ledger integrity settings.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LedgerConfig {
+    /// HMAC key for chain checkpoints. Empty turns checkpoints off.
+    pub checkpoint_secret: String,
+}
+
+
 #[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq, Hash)]
 
 pub enum DeployedEnvironment {
@@ -86,6 +109,10 @@ pub struct Settings {
     pub smtp: SmtpConfig,
     pub gpt: Gpt,
     pub templates: String, 
+    #[serde(default)]
+    pub publish: PublishConfig,
+    #[serde(default)]
+    pub ledger: LedgerConfig,
 }
 
 impl Settings {
@@ -1,10 +1,12 @@
+use diesel::SqliteConnection;
 use tempfile::NamedTempFile;
 use crate::db::DbPool;
+use crate::models::entities::{Entity, NewEntity, create_entity};
+use crate::types::JsonField;
 
 #[allow(dead_code)]
 pub fn setup_test_db() -> (NamedTempFile, DbPool, i32) {
     use crate::models::users::create_user;
-    use diesel::SqliteConnection;
     use diesel::r2d2::{ConnectionManager, Pool};
 
     let tmp = NamedTempFile::new().unwrap();
@@ -23,3 +25,23 @@ pub fn setup_test_db() -> (NamedTempFile, DbPool, i32) {
         return (tmp, pool, this_user_id);
     }
 }
+
+/// Inserts an entity straight into the table, skipping service checks.
+#[allow(dead_code)]
+pub fn create_test_entity(
+    conn: &mut SqliteConnection,
+    host: i32,
+    name: &str,
+    entity_type: &str,
+) -> Entity {
+    let new = NewEntity {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        entity_type: entity_type.to_string(),
+        host_id: host,
+        created_by: "test".to_string(),
+        created_at: chrono::Utc::now().naive_utc(),
+        details: JsonField::default(),
+    };
+    create_entity(conn, &new).unwrap()
+}
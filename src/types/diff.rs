@@ -0,0 +1,184 @@
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LineChange {
+    pub op: DiffOp,
+    /// 1-based line numbers in the old and new text, where the line exists.
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// Dotted path into the object, e.g. `meta.location.city`.
+    pub path: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Largest LCS table `diff_lines` builds, in cells (old lines times new
+/// lines, after common leading and trailing lines are set aside).
+const MAX_LCS_CELLS: usize = 1_000_000;
+
+/// Line diff from the longest common subsequence of the two texts.
+/// Deletions come before insertions where lines were replaced. When the
+/// changed region is too large to align, it is reported as deleted and
+/// re-inserted in full.
+pub fn diff_lines(old: &str, new: &str) -> Vec<LineChange> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let change = |op, old_line, new_line, text: &str| LineChange {
+        op,
+        old_line,
+        new_line,
+        text: text.to_string(),
+    };
+    let mut changes = Vec::with_capacity(a.len().max(b.len()));
+    for (i, line) in a[..prefix].iter().enumerate() {
+        changes.push(change(DiffOp::Equal, Some(i + 1), Some(i + 1), line));
+    }
+
+    // lcs[i][j]: common lines between a_mid[i..] and b_mid[j..]
+    let aligned = a_mid.len().saturating_mul(b_mid.len()) <= MAX_LCS_CELLS;
+    let mut lcs = vec![vec![0u32; b_mid.len() + 1]; if aligned { a_mid.len() + 1 } else { 0 }];
+    if aligned {
+        for i in (0..a_mid.len()).rev() {
+            for j in (0..b_mid.len()).rev() {
+                lcs[i][j] = if a_mid[i] == b_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a_mid.len() || j < b_mid.len() {
+        let (old_line, new_line) = (prefix + i + 1, prefix + j + 1);
+        if aligned && i < a_mid.len() && j < b_mid.len() && a_mid[i] == b_mid[j] {
+            changes.push(change(DiffOp::Equal, Some(old_line), Some(new_line), a_mid[i]));
+            i += 1;
+            j += 1;
+        } else if j == b_mid.len() || (i < a_mid.len() && (!aligned || lcs[i + 1][j] >= lcs[i][j + 1])) {
+            changes.push(change(DiffOp::Delete, Some(old_line), None, a_mid[i]));
+            i += 1;
+        } else {
+            changes.push(change(DiffOp::Insert, None, Some(new_line), b_mid[j]));
+            j += 1;
+        }
+    }
+
+    for k in 0..suffix {
+        let (old_line, new_line) = (a.len() - suffix + k, b.len() - suffix + k);
+        changes.push(change(DiffOp::Equal, Some(old_line + 1), Some(new_line + 1), a[old_line]));
+    }
+    changes
+}
+
+/// Leaf values that differ between two JSON documents. Objects are
+/// compared key by key; arrays and scalars as whole values.
+pub fn diff_fields(old: &Value, new: &Value) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    collect_field_changes("", Some(old), Some(new), &mut changes);
+    changes
+}
+
+fn collect_field_changes(
+    path: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    changes: &mut Vec<FieldChange>,
+) {
+    // a missing key and an explicit null read the same to a form
+    let old = old.filter(|v| !v.is_null());
+    let new = new.filter(|v| !v.is_null());
+    match (old, new) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                collect_field_changes(&child, a.get(key), b.get(key), changes);
+            }
+        }
+        (a, b) if a != b => changes.push(FieldChange {
+            path: path.to_string(),
+            before: a.cloned(),
+            after: b.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn lines_and_fields_diff() {
+        let ops: Vec<_> = diff_lines("a\nb\nc", "a\nB\nc\nd")
+            .into_iter()
+            .map(|c| (c.op, c.text))
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                (DiffOp::Equal, "a".to_string()),
+                (DiffOp::Delete, "b".to_string()),
+                (DiffOp::Insert, "B".to_string()),
+                (DiffOp::Equal, "c".to_string()),
+                (DiffOp::Insert, "d".to_string()),
+            ]
+        );
+
+        let changes = diff_fields(
+            &json!({ "title": "Plot", "meta": { "location": { "city": "Ames" }, "tags": ["a"] } }),
+            &json!({ "title": "Plot", "meta": { "location": { "city": "Boone" }, "size": 2, "tags": null } }),
+        );
+        let paths: Vec<_> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, ["meta.location.city", "meta.size", "meta.tags"]);
+        assert_eq!(changes[1].before, None);
+    }
+
+    #[test]
+    fn large_rewrites_are_not_aligned() {
+        let old: Vec<String> = (0..2000).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..2000).map(|i| format!("new {}", i)).collect();
+        let old = format!("title\n{}\nend", old.join("\n"));
+        let new = format!("title\n{}\nend", new.join("\n"));
+
+        let changes = diff_lines(&old, &new);
+        assert_eq!(changes.len(), 4002);
+        assert_eq!(changes[1].op, DiffOp::Delete);
+        assert_eq!(changes[2001].op, DiffOp::Insert);
+        let last = changes.last().unwrap();
+        assert_eq!((last.op, last.old_line, last.new_line), (DiffOp::Equal, Some(2002), Some(2002)));
+    }
+}
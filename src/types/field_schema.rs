@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 
 
@@ -33,6 +34,67 @@ pub struct FieldSchema {
     pub actions: Option<Vec<FieldAction>>,
 }
 
+impl FieldSchema {
+    /// Checks one value against the field's type, options and `required`
+    /// flag. Blank strings count as missing.
+    pub fn check_value(&self, value: Option<&Value>) -> Result<(), String> {
+        let value = value.filter(|v| !v.is_null() && v.as_str().is_none_or(|s| !s.trim().is_empty()));
+        let Some(value) = value else {
+            return match self.required {
+                Some(true) => Err(format!("{} is required", self.label)),
+                _ => Ok(()),
+            };
+        };
+
+        let allowed = |v: &str| {
+            self.options
+                .as_ref()
+                .is_none_or(|options| options.iter().any(|o| o.value == v))
+        };
+        let ok = match self.field_type.as_str() {
+            "number" => value.is_number() || value.as_str().is_some_and(|s| s.trim().parse::<f64>().is_ok()),
+            "boolean" => value.is_boolean(),
+            "date" => value
+                .as_str()
+                .is_some_and(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok()),
+            "email" => value.as_str().is_some_and(|s| {
+                s.split_once('@').is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'))
+            }),
+            "url" => value
+                .as_str()
+                .is_some_and(|s| s.starts_with("http://") || s.starts_with("https://")),
+            "select" => value.as_str().is_some_and(allowed),
+            "multiselect" => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(|i| i.as_str().is_some_and(allowed))),
+            "checkbox" => match value {
+                Value::Bool(_) => self.options.is_none(),
+                Value::Array(items) => items.iter().all(|i| i.as_str().is_some_and(allowed)),
+                _ => false,
+            },
+            _ => value.is_string(),
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("{} is not a valid {}", self.label, self.field_type))
+        }
+    }
+}
+
+/// Every field problem in `details`, as `(key, message)`. Keys without a
+/// field are left alone.
+pub fn check_fields(fields: &[FieldSchema], details: &Value) -> Vec<(String, String)> {
+    fields
+        .iter()
+        .filter_map(|f| {
+            f.check_value(details.get(&f.key))
+                .err()
+                .map(|message| (f.key.clone(), message))
+        })
+        .collect()
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 #[serde(rename_all = "snake_case")]
 pub struct DocTypeSchema {
@@ -360,4 +422,24 @@ mod tests {
         // Cleanup
         fs::remove_file(path).ok();
     }
+
+    #[test]
+    fn details_are_checked_against_field_types() {
+        let fields: Vec<FieldSchema> = serde_json::from_str(
+            r#"[
+                { "key": "acres", "label": "Acres", "type": "number", "required": true },
+                { "key": "soil", "label": "Soil", "type": "select",
+                  "options": [{ "label": "Clay", "value": "clay" }, { "label": "Loam", "value": "loam" }] },
+                { "key": "website", "label": "Website", "type": "url" }
+            ]"#,
+        )
+        .unwrap();
+
+        let ok = serde_json::json!({ "acres": 12.5, "soil": "loam", "owner": "anything" });
+        assert!(check_fields(&fields, &ok).is_empty());
+
+        let bad = serde_json::json!({ "acres": " ", "soil": "sand", "website": "example.org" });
+        let keys: Vec<String> = check_fields(&fields, &bad).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, ["acres", "soil", "website"]);
+    }
 }
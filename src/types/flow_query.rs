@@ -1,18 +1,157 @@
-use chrono::NaiveDateTime;
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime};
+use diesel::dsl::sql;
+use diesel::sql_types::Bool;
 use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl};
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::Serialize;
 use uuid::Uuid;
 
+use crate::schema::{entities, entity_users};
+use crate::types::geo::GeoArea;
+use crate::types::{EntityUserStatus, Visibility};
 use crate::schema::flow_events::{self, BoxedQuery};
 
 pub type FlowQueryBox<'a> = BoxedQuery<'a, diesel::sqlite::Sqlite>;
 
 
+const MANAGER_ROLES: &[&str] = &["owner", "steward"];
+const ANY_ROLE: &[&str] = &["owner", "steward", "member", "observer"];
+
+/// Where a flow is mapped: its own location, else its receiving entity's.
+pub const FLOW_LATITUDE: &str = "IFNULL(flow_events.latitude, \
+    (SELECT e.latitude FROM entities e WHERE e.id = flow_events.to_entity))";
+pub const FLOW_LONGITUDE: &str = "IFNULL(flow_events.longitude, \
+    (SELECT e.longitude FROM entities e WHERE e.id = flow_events.to_entity))";
+
+#[derive(Clone, Copy, Debug)]
 pub enum FlowDirection {
     From,
     To,
     Both,
 }
 
+/// Bucket size for time-series rollups. Buckets are labelled by their
+/// first day: the day itself, the Monday of the week, or the 1st of the month.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeBucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl TimeBucket {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            "month" => Some(Self::Month),
+            _ => None,
+        }
+    }
+
+    /// SQLite expression giving the bucket label for `flow_events.timestamp`.
+    pub fn sql(self) -> &'static str {
+        match self {
+            Self::Day => "date(flow_events.timestamp)",
+            Self::Week => "date(flow_events.timestamp, '-6 days', 'weekday 1')",
+            Self::Month => "strftime('%Y-%m-01', flow_events.timestamp)",
+        }
+    }
+
+    pub fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Self::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// Every bucket label from `since` to `until`, so callers can zero-fill.
+    pub fn labels(self, since: NaiveDateTime, until: NaiveDateTime) -> Vec<String> {
+        let mut labels = Vec::new();
+        let mut bucket = self.start_of(since.date());
+        while bucket <= until.date() {
+            labels.push(bucket.format("%Y-%m-%d").to_string());
+            bucket = match self {
+                Self::Day => bucket + Duration::days(1),
+                Self::Week => bucket + Duration::days(7),
+                Self::Month => bucket + Months::new(1),
+            };
+        }
+        labels
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FlowSort {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+impl FlowSort {
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "newest" | "desc" => Some(Self::Newest),
+            "oldest" | "asc" => Some(Self::Oldest),
+            _ => None,
+        }
+    }
+}
+
+/// Keyset position after the last row of a page. Encoded as an opaque
+/// base64 token so clients don't depend on its shape.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlowCursor {
+    pub timestamp: NaiveDateTime,
+    pub id: String,
+}
+
+const CURSOR_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+impl FlowCursor {
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}", self.timestamp.format(CURSOR_FORMAT), self.id);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+        let (timestamp, id) = raw.split_once('|')?;
+        Some(Self {
+            timestamp: NaiveDateTime::parse_from_str(timestamp, CURSOR_FORMAT).ok()?,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Who is asking, for visibility checks. Host admins and internal
+/// callers use `System` and see everything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FlowViewer {
+    #[default]
+    System,
+    Anonymous,
+    User { user_id: i32, is_member: bool },
+}
+
+impl FlowViewer {
+    /// Visibility levels this viewer sees without being linked to a party.
+    pub fn open_levels(&self) -> Vec<&'static str> {
+        match self {
+            FlowViewer::System => Visibility::ALL.into_iter().map(Visibility::value).collect(),
+            FlowViewer::Anonymous => vec![Visibility::Public.value()],
+            FlowViewer::User { is_member: true, .. } => {
+                vec![Visibility::Public.value(), Visibility::Members.value()]
+            }
+            FlowViewer::User { .. } => vec![Visibility::Public.value()],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct FlowQuery {
     pub host: i32,
     pub entity: Option<Uuid>,
@@ -21,6 +160,16 @@ pub struct FlowQuery {
     pub until: Option<NaiveDateTime>,
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    pub hide_reversed: bool,
+    pub sort: FlowSort,
+    pub cursor: Option<FlowCursor>,
+    pub resource_type: Option<String>,
+    /// Matches flows where either side has this entity type.
+    pub entity_type: Option<String>,
+    pub created_by: Option<String>,
+    /// Matches flows mapped inside the area; unlocated flows never match.
+    pub area: Option<GeoArea>,
+    pub viewer: FlowViewer,
 }
 
 impl FlowQuery {
@@ -33,6 +182,14 @@ impl FlowQuery {
             until: None,
             limit: None,
             offset: None,
+            hide_reversed: false,
+            sort: FlowSort::Newest,
+            cursor: None,
+            resource_type: None,
+            entity_type: None,
+            created_by: None,
+            area: None,
+            viewer: FlowViewer::System,
         }
     }
 
@@ -111,6 +268,79 @@ impl FlowQuery {
             query = query.filter(flow_events::timestamp.le(until));
         }
 
+        if let Some(resource_type) = &self.resource_type {
+            query = query.filter(flow_events::resource_type.eq(resource_type.clone()));
+        }
+
+        if let Some(created_by) = &self.created_by {
+            query = query.filter(flow_events::created_by.eq(created_by.clone()));
+        }
+
+        if let Some(entity_type) = &self.entity_type {
+            let typed = || {
+                entities::table
+                    .filter(entities::entity_type.eq(entity_type.clone()))
+                    .select(entities::id)
+            };
+            query = query.filter(
+                flow_events::from_entity
+                    .eq_any(typed())
+                    .or(flow_events::to_entity.eq_any(typed())),
+            );
+        }
+
+        if let Some(area) = &self.area {
+            query = query.filter(sql::<Bool>(&area.sql(FLOW_LATITUDE, FLOW_LONGITUDE)));
+        }
+
+        if let FlowViewer::User { user_id, .. } = self.viewer {
+            let linked = |roles: &'static [&'static str]| {
+                entity_users::table
+                    .filter(entity_users::user_id.eq(user_id))
+                    .filter(entity_users::status.eq(EntityUserStatus::Active.value()))
+                    .filter(entity_users::role.eq_any(roles))
+                    .select(entity_users::entity_id)
+            };
+            let on_flow = |roles| {
+                flow_events::from_entity
+                    .eq_any(linked(roles))
+                    .or(flow_events::to_entity.eq_any(linked(roles)))
+            };
+            query = query.filter(
+                flow_events::visibility
+                    .eq_any(self.viewer.open_levels())
+                    .or(flow_events::visibility
+                        .eq(Visibility::Stewards.value())
+                        .and(on_flow(MANAGER_ROLES)))
+                    .or(flow_events::visibility
+                        .eq(Visibility::Parties.value())
+                        .and(on_flow(ANY_ROLE))),
+            );
+        } else if self.viewer != FlowViewer::System {
+            query = query.filter(flow_events::visibility.eq_any(self.viewer.open_levels()));
+        }
+
+        if let Some(cursor) = &self.cursor {
+            let (ts, id) = (cursor.timestamp, cursor.id.clone());
+            query = match self.sort {
+                FlowSort::Newest => query.filter(
+                    flow_events::timestamp
+                        .lt(ts)
+                        .or(flow_events::timestamp.eq(ts).and(flow_events::id.lt(id))),
+                ),
+                FlowSort::Oldest => query.filter(
+                    flow_events::timestamp
+                        .gt(ts)
+                        .or(flow_events::timestamp.eq(ts).and(flow_events::id.gt(id))),
+                ),
+            };
+        }
+
+        query = match self.sort {
+            FlowSort::Newest => query.order((flow_events::timestamp.desc(), flow_events::id.desc())),
+            FlowSort::Oldest => query.order((flow_events::timestamp.asc(), flow_events::id.asc())),
+        };
+
         if let Some(limit) = self.limit {
             query = query.limit(limit);
         }
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// Mean Earth radius, for radius filters.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    pub fn new(latitude: f64, longitude: f64) -> Result<Self, String> {
+        if !(latitude.is_finite() && (-90.0..=90.0).contains(&latitude)) {
+            return Err(format!("Latitude {} is out of range", latitude));
+        }
+        if !(longitude.is_finite() && (-180.0..=180.0).contains(&longitude)) {
+            return Err(format!("Longitude {} is out of range", longitude));
+        }
+        Ok(Self { latitude, longitude })
+    }
+
+    /// Parses "lat,lon".
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (lat, lon) = value
+            .split_once(',')
+            .ok_or_else(|| format!("Expected 'lat,lon', got '{}'", value))?;
+        Self::new(parse_number(lat)?, parse_number(lon)?)
+    }
+
+    /// GeoJSON positions are `[longitude, latitude]`.
+    fn from_position(position: &Value) -> Result<Self, String> {
+        match position.as_array().map(Vec::as_slice) {
+            Some([lon, lat, ..]) => match (lon.as_f64(), lat.as_f64()) {
+                (Some(lon), Some(lat)) => Self::new(lat, lon),
+                _ => Err("Coordinates must be numbers".into()),
+            },
+            _ => Err("A position needs [longitude, latitude]".into()),
+        }
+    }
+
+    pub fn geometry(&self) -> Value {
+        json!({ "type": "Point", "coordinates": [self.longitude, self.latitude] })
+    }
+}
+
+/// An area to filter locations by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeoArea {
+    BoundingBox {
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    },
+    Radius { center: GeoPoint, km: f64 },
+}
+
+impl GeoArea {
+    /// Parses "minLon,minLat,maxLon,maxLat", the GeoJSON bbox order.
+    pub fn parse_bbox(value: &str) -> Result<Self, String> {
+        let parts = value
+            .split(',')
+            .map(parse_number)
+            .collect::<Result<Vec<f64>, String>>()?;
+        let [min_lon, min_lat, max_lon, max_lat] = parts[..] else {
+            return Err(format!("Expected 'minLon,minLat,maxLon,maxLat', got '{}'", value));
+        };
+        GeoPoint::new(min_lat, min_lon)?;
+        GeoPoint::new(max_lat, max_lon)?;
+        if min_lat > max_lat || min_lon > max_lon {
+            return Err("Bounding box minimums must not exceed its maximums".into());
+        }
+        Ok(Self::BoundingBox { min_lon, min_lat, max_lon, max_lat })
+    }
+
+    pub fn radius(center: GeoPoint, km: f64) -> Result<Self, String> {
+        if !(km.is_finite() && km > 0.0) {
+            return Err("radius_km must be positive".into());
+        }
+        Ok(Self::Radius { center, km })
+    }
+
+    /// SQLite condition on the given latitude and longitude expressions.
+    /// Radius uses an equirectangular approximation, which is close enough
+    /// at the distances a host maps. Only numbers are interpolated.
+    pub fn sql(&self, latitude: &str, longitude: &str) -> String {
+        match *self {
+            Self::BoundingBox { min_lon, min_lat, max_lon, max_lat } => format!(
+                "({lat} BETWEEN {min_lat:?} AND {max_lat:?} AND {lon} BETWEEN {min_lon:?} AND {max_lon:?})",
+                lat = latitude,
+                lon = longitude,
+            ),
+            Self::Radius { center, km } => {
+                let lat_scale = EARTH_RADIUS_KM.to_radians();
+                let lon_scale = lat_scale * center.latitude.to_radians().cos();
+                format!(
+                    "(({lat} - {clat:?}) * {lat_scale:?}) * (({lat} - {clat:?}) * {lat_scale:?}) \
+                     + (({lon} - {clon:?}) * {lon_scale:?}) * (({lon} - {clon:?}) * {lon_scale:?}) \
+                     <= {limit:?}",
+                    lat = latitude,
+                    lon = longitude,
+                    clat = center.latitude,
+                    clon = center.longitude,
+                    limit = km * km,
+                )
+            }
+        }
+    }
+}
+
+/// Checks a GeoJSON Point or Polygon and returns the point it is mapped
+/// at: the point itself, or the average of the polygon's outer ring.
+pub fn parse_geometry(geometry: &Value) -> Result<GeoPoint, String> {
+    let coordinates = &geometry["coordinates"];
+    match geometry["type"].as_str() {
+        Some("Point") => GeoPoint::from_position(coordinates),
+        Some("Polygon") => {
+            let ring = coordinates
+                .get(0)
+                .and_then(Value::as_array)
+                .ok_or("A polygon needs an outer ring")?;
+            let points = ring
+                .iter()
+                .map(GeoPoint::from_position)
+                .collect::<Result<Vec<_>, _>>()?;
+            if points.len() < 4 || points.first() != points.last() {
+                return Err("A polygon ring needs at least four positions and must be closed".into());
+            }
+            // the closing position repeats the first
+            let vertices = &points[1..];
+            let n = vertices.len() as f64;
+            Ok(GeoPoint {
+                latitude: vertices.iter().map(|p| p.latitude).sum::<f64>() / n,
+                longitude: vertices.iter().map(|p| p.longitude).sum::<f64>() / n,
+            })
+        }
+        Some(other) => Err(format!("Unsupported geometry type '{}'; use Point or Polygon", other)),
+        None => Err("Geometry needs a type".into()),
+    }
+}
+
+pub fn feature(geometry: Value, properties: Value) -> Value {
+    json!({ "type": "Feature", "geometry": geometry, "properties": properties })
+}
+
+pub fn feature_collection(features: Vec<Value>) -> Value {
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometries_and_areas_parse() {
+        let square = json!({
+            "type": "Polygon",
+            "coordinates": [[[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0], [0.0, 0.0]]]
+        });
+        assert_eq!(parse_geometry(&square).unwrap(), GeoPoint { latitude: 1.0, longitude: 1.0 });
+        assert!(parse_geometry(&json!({ "type": "Point", "coordinates": [200.0, 0.0] })).is_err());
+        assert!(parse_geometry(&json!({ "type": "LineString", "coordinates": [] })).is_err());
+
+        assert_eq!(
+            GeoArea::parse_bbox("-91.6, 41.6, -91.5, 41.7").unwrap(),
+            GeoArea::BoundingBox { min_lon: -91.6, min_lat: 41.6, max_lon: -91.5, max_lat: 41.7 }
+        );
+        assert!(GeoArea::parse_bbox("1,2,3").is_err());
+        assert!(GeoArea::parse_bbox("0,5,1,4").is_err());
+    }
+}
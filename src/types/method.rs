@@ -5,6 +5,7 @@ pub enum Method {
     GET,
     POST,
     PUT,
+    PATCH,
     DELETE,
 }
 
@@ -14,15 +15,28 @@ impl Method {
             Method::GET => actix_web::web::get(),
             Method::POST => actix_web::web::post(),
             Method::PUT => actix_web::web::put(),
+            Method::PATCH => actix_web::web::patch(),
             Method::DELETE => actix_web::web::delete(),
         }
     }
 
+    /// Resource guard so several methods can share one path.
+    pub fn to_guard(self) -> impl actix_web::guard::Guard {
+        actix_web::guard::Method(match self {
+            Method::GET => actix_web::http::Method::GET,
+            Method::POST => actix_web::http::Method::POST,
+            Method::PUT => actix_web::http::Method::PUT,
+            Method::PATCH => actix_web::http::Method::PATCH,
+            Method::DELETE => actix_web::http::Method::DELETE,
+        })
+    }
+
     pub fn as_str(self) -> &'static str {
         match self {
             Method::GET => "GET",
             Method::POST => "POST",
             Method::PUT => "PUT",
+            Method::PATCH => "PATCH",
             Method::DELETE => "DELETE",
         }
     }
@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+use chrono::{Days, Months, NaiveDateTime};
 use diesel::deserialize::{self, FromSql, FromSqlRow};
 use diesel::expression::AsExpression;
 use diesel::serialize::{self, IsNull, Output, ToSql};
@@ -8,12 +9,14 @@ use diesel::sqlite::{Sqlite, SqliteValue};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
+pub(crate) mod diff;
 mod field_schema;
 pub(crate) mod flow_query;
+pub(crate) mod geo;
 
 pub(crate) mod method;
 
-pub use field_schema::{ FrontendSchema, load_frontend_schema};
+pub use field_schema::{ FieldSchema, FrontendSchema, check_fields, load_frontend_schema};
 mod auth_context;
 //pub use auth_context::{AdminContext, MembershipContext};
 
@@ -242,6 +245,20 @@ impl DraftStatus {
         })
         .collect()
     }
+
+    pub fn from_value(value: &str) -> Option<Self> {
+        [
+            DraftStatus::Draft,
+            DraftStatus::Submitted,
+            DraftStatus::Approved,
+            DraftStatus::Pending,
+            DraftStatus::ChangesRequested,
+            DraftStatus::Rejected,
+            DraftStatus::Deployed,
+        ]
+        .into_iter()
+        .find(|s| s.value() == value)
+    }
 }
 
 
@@ -270,6 +287,59 @@ impl ToSql<Text, Sqlite> for DraftStatus {
     }
 }
 
+/// The moves a draft can make through review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftAction {
+    Submit,
+    RequestChanges,
+    Approve,
+    Reject,
+    Deploy,
+    Unpublish,
+}
+
+impl DraftAction {
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            DraftAction::Submit => ("submit", "Submit"),
+            DraftAction::RequestChanges => ("request_changes", "Request Changes"),
+            DraftAction::Approve => ("approve", "Approve"),
+            DraftAction::Reject => ("reject", "Reject"),
+            DraftAction::Deploy => ("deploy", "Deploy"),
+            DraftAction::Unpublish => ("unpublish", "Unpublish"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    /// The status a draft in `from` moves to, or `None` if the action is
+    /// not allowed there. `pending` is the old name for `submitted`.
+    pub fn apply(self, from: DraftStatus) -> Option<DraftStatus> {
+        use DraftStatus::*;
+        match (self, from) {
+            (DraftAction::Submit, Draft | ChangesRequested) => Some(Submitted),
+            (DraftAction::RequestChanges, Submitted | Pending) => Some(ChangesRequested),
+            (DraftAction::Approve, Submitted | Pending) => Some(Approved),
+            (DraftAction::Reject, Submitted | Pending) => Some(Rejected),
+            (DraftAction::Deploy, Approved) => Some(Deployed),
+            (DraftAction::Unpublish, Deployed) => Some(Approved),
+            _ => None,
+        }
+    }
+
+    /// Authors submit their own drafts; everything else is a review step.
+    pub fn needs_reviewer(self) -> bool {
+        self != DraftAction::Submit
+    }
+}
+
 
 #[derive(Debug, Clone,Copy,PartialEq,Eq,Serialize,Deserialize,FromSqlRow)]
 #[serde(rename_all = "snake_case")]
@@ -458,6 +528,31 @@ impl DocType {
         .collect()
     }
 
+    pub fn from_value(value: &str) -> Option<Self> {
+        [
+            DocType::Recipe,
+            DocType::Post,
+            DocType::Event,
+            DocType::Organization,
+            DocType::Page,
+            DocType::Idea,
+        ]
+        .into_iter()
+        .find(|d| d.value() == value)
+    }
+
+    /// Folder under the site's content directory.
+    pub fn content_dir(self) -> &'static str {
+        match self {
+            DocType::Recipe => "recipes",
+            DocType::Post => "posts",
+            DocType::Event => "events",
+            DocType::Organization => "organizations",
+            DocType::Page => "pages",
+            DocType::Idea => "ideas",
+        }
+    }
+
     pub fn list() -> Vec<&'static str> {
         [
             DocType::Recipe,
@@ -496,6 +591,411 @@ impl ToSql<Text, Sqlite> for DocType {
     }
 }
 
+/// `flow_actions.action_type` values. Flows are append-only, so every
+/// change to a flow's standing is recorded as one of these actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowActionType {
+    Reversal,
+    Amendment,
+    Proposal,
+    Confirmation,
+    Dispute,
+    Rejection,
+    EntityMerge,
+}
+
+impl FlowActionType {
+    const ALL: [FlowActionType; 7] = [
+        FlowActionType::Reversal,
+        FlowActionType::Amendment,
+        FlowActionType::Proposal,
+        FlowActionType::Confirmation,
+        FlowActionType::Dispute,
+        FlowActionType::Rejection,
+        FlowActionType::EntityMerge,
+    ];
+
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            FlowActionType::Reversal => ("reversal", "Reversal"),
+            FlowActionType::Amendment => ("amendment", "Amendment"),
+            FlowActionType::Proposal => ("proposal", "Proposal"),
+            FlowActionType::Confirmation => ("confirmation", "Confirmation"),
+            FlowActionType::Dispute => ("dispute", "Dispute"),
+            FlowActionType::Rejection => ("rejection", "Rejection"),
+            FlowActionType::EntityMerge => ("entity_merge", "Entity Merge"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    #[allow(dead_code)]
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    pub fn from_value(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.value() == value)
+    }
+
+    /// The approval status a flow moves to when this action is recorded.
+    pub fn status(self) -> Option<FlowStatus> {
+        match self {
+            FlowActionType::Proposal => Some(FlowStatus::Proposed),
+            FlowActionType::Confirmation => Some(FlowStatus::Confirmed),
+            FlowActionType::Dispute => Some(FlowStatus::Disputed),
+            FlowActionType::Rejection => Some(FlowStatus::Rejected),
+            FlowActionType::Reversal
+            | FlowActionType::Amendment
+            | FlowActionType::EntityMerge => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn all() -> Vec<ConfigOption> {
+        Self::ALL
+            .into_iter()
+            .map(|a| ConfigOption {
+                value: a.value(),
+                label: a.label(),
+            })
+            .collect()
+    }
+}
+
+/// Approval status of a flow, derived from its latest lifecycle action.
+/// Flows recorded without a proposal (imports, corrections) count as confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowStatus {
+    Proposed,
+    Confirmed,
+    Disputed,
+    Rejected,
+}
+
+impl FlowStatus {
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            FlowStatus::Proposed => ("proposed", "Proposed"),
+            FlowStatus::Confirmed => ("confirmed", "Confirmed"),
+            FlowStatus::Disputed => ("disputed", "Disputed"),
+            FlowStatus::Rejected => ("rejected", "Rejected"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    #[allow(dead_code)]
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    #[allow(dead_code)]
+    pub fn all() -> Vec<ConfigOption> {
+        [
+            FlowStatus::Proposed,
+            FlowStatus::Confirmed,
+            FlowStatus::Disputed,
+            FlowStatus::Rejected,
+        ]
+        .into_iter()
+        .map(|s| ConfigOption {
+            value: s.value(),
+            label: s.label(),
+        })
+        .collect()
+    }
+
+    /// Whether `action` may be recorded against a flow in this status.
+    /// Confirmed and rejected flows are settled; undo them with a reversal.
+    pub fn allows(self, action: FlowActionType) -> bool {
+        matches!(
+            (self, action),
+            (FlowStatus::Proposed, FlowActionType::Confirmation)
+                | (FlowStatus::Proposed, FlowActionType::Dispute)
+                | (FlowStatus::Proposed, FlowActionType::Rejection)
+                | (FlowStatus::Disputed, FlowActionType::Confirmation)
+                | (FlowStatus::Disputed, FlowActionType::Rejection)
+        )
+    }
+}
+
+/// Role of a user linked to an entity through `entity_users`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityRole {
+    Owner,
+    Steward,
+    Member,
+    Observer,
+}
+
+impl EntityRole {
+    const ALL: [EntityRole; 4] = [
+        EntityRole::Owner,
+        EntityRole::Steward,
+        EntityRole::Member,
+        EntityRole::Observer,
+    ];
+
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            EntityRole::Owner => ("owner", "Owner"),
+            EntityRole::Steward => ("steward", "Steward"),
+            EntityRole::Member => ("member", "Member"),
+            EntityRole::Observer => ("observer", "Observer"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    #[allow(dead_code)]
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    pub fn from_value(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.value() == value)
+    }
+
+    /// Owners and stewards invite people and change their roles.
+    pub fn manages_people(self) -> bool {
+        matches!(self, EntityRole::Owner | EntityRole::Steward)
+    }
+
+    /// Everyone but observers may record and decide flows for the entity.
+    pub fn records_flows(self) -> bool {
+        self != EntityRole::Observer
+    }
+
+    #[allow(dead_code)]
+    pub fn all() -> Vec<ConfigOption> {
+        Self::ALL
+            .into_iter()
+            .map(|r| ConfigOption {
+                value: r.value(),
+                label: r.label(),
+            })
+            .collect()
+    }
+}
+
+/// Status of an `entity_users` link. Only active links grant anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityUserStatus {
+    Invited,
+    Active,
+    Declined,
+}
+
+impl EntityUserStatus {
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            EntityUserStatus::Invited => ("invited", "Invited"),
+            EntityUserStatus::Active => ("active", "Active"),
+            EntityUserStatus::Declined => ("declined", "Declined"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    #[allow(dead_code)]
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    #[allow(dead_code)]
+    pub fn all() -> Vec<ConfigOption> {
+        [
+            EntityUserStatus::Invited,
+            EntityUserStatus::Active,
+            EntityUserStatus::Declined,
+        ]
+        .into_iter()
+        .map(|s| ConfigOption {
+            value: s.value(),
+            label: s.label(),
+        })
+        .collect()
+    }
+}
+
+/// Who may see a flow or entity. Host admins always see everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    /// Anyone, including the public site.
+    Public,
+    /// Members of the host.
+    Members,
+    /// Owners and stewards of either entity on the flow.
+    Stewards,
+    /// Anyone actively linked to either entity on the flow.
+    Parties,
+}
+
+impl Visibility {
+    const ALL: [Visibility; 4] = [
+        Visibility::Public,
+        Visibility::Members,
+        Visibility::Stewards,
+        Visibility::Parties,
+    ];
+
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            Visibility::Public => ("public", "Public"),
+            Visibility::Members => ("members", "Members"),
+            Visibility::Stewards => ("stewards", "Stewards"),
+            Visibility::Parties => ("parties", "Private to parties"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    #[allow(dead_code)]
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    #[allow(dead_code)]
+    pub fn from_value(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.value() == value)
+    }
+
+    #[allow(dead_code)]
+    pub fn all() -> Vec<ConfigOption> {
+        Self::ALL
+            .into_iter()
+            .map(|v| ConfigOption {
+                value: v.value(),
+                label: v.label(),
+            })
+            .collect()
+    }
+}
+
+/// Where a commitment stands. Fulfilment moves it to `Fulfilled`
+/// automatically once linked flows cover the promised quantity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitmentStatus {
+    Open,
+    Fulfilled,
+    Cancelled,
+}
+
+impl CommitmentStatus {
+    const ALL: [CommitmentStatus; 3] = [
+        CommitmentStatus::Open,
+        CommitmentStatus::Fulfilled,
+        CommitmentStatus::Cancelled,
+    ];
+
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            CommitmentStatus::Open => ("open", "Open"),
+            CommitmentStatus::Fulfilled => ("fulfilled", "Fulfilled"),
+            CommitmentStatus::Cancelled => ("cancelled", "Cancelled"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    #[allow(dead_code)]
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    #[allow(dead_code)]
+    pub fn from_value(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.value() == value)
+    }
+
+    #[allow(dead_code)]
+    pub fn all() -> Vec<ConfigOption> {
+        Self::ALL
+            .into_iter()
+            .map(|s| ConfigOption {
+                value: s.value(),
+                label: s.label(),
+            })
+            .collect()
+    }
+}
+
+/// How often a flow template repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Recurrence {
+    const ALL: [Recurrence; 3] = [Recurrence::Daily, Recurrence::Weekly, Recurrence::Monthly];
+
+    fn meta(self) -> (&'static str, &'static str) {
+        match self {
+            Recurrence::Daily => ("daily", "Daily"),
+            Recurrence::Weekly => ("weekly", "Weekly"),
+            Recurrence::Monthly => ("monthly", "Monthly"),
+        }
+    }
+
+    pub fn value(self) -> &'static str {
+        self.meta().0
+    }
+
+    #[allow(dead_code)]
+    pub fn label(self) -> &'static str {
+        self.meta().1
+    }
+
+    pub fn from_value(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.value() == value)
+    }
+
+    #[allow(dead_code)]
+    pub fn all() -> Vec<ConfigOption> {
+        Self::ALL
+            .into_iter()
+            .map(|r| ConfigOption {
+                value: r.value(),
+                label: r.label(),
+            })
+            .collect()
+    }
+
+    /// The `n`th occurrence (0-based) of a schedule starting at `start` and
+    /// repeating every `every` periods. Months past the end of a shorter
+    /// month land on its last day.
+    pub fn occurrence(self, start: NaiveDateTime, every: u32, n: u32) -> Option<NaiveDateTime> {
+        let periods = every.checked_mul(n)?;
+        match self {
+            Recurrence::Daily => start.checked_add_days(Days::new(periods.into())),
+            Recurrence::Weekly => start.checked_add_days(Days::new(u64::from(periods) * 7)),
+            Recurrence::Monthly => start.checked_add_months(Months::new(periods)),
+        }
+    }
+}
+
 pub enum Audience {
     Public,
     Authenticated,